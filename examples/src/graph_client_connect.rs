@@ -1,5 +1,8 @@
+use std::{collections::HashMap, time::Duration};
+
 use rust_nebula::{
-    graph::query::GraphQuery as _, HostAddress, SingleConnSessionConf, SingleConnSessionManager,
+    common::types::Value, graph::query::GraphQuery as _, HostAddress, SingleConnSessionConf,
+    SingleConnSessionManager,
 };
 
 #[tokio::main]
@@ -49,6 +52,30 @@ async fn run() -> Result<(), Box<dyn std::error::Error>> {
         println!("{:?}", dataset);
     }
 
+    let mut params = HashMap::new();
+    params.insert(b"name".to_vec(), Value::sVal(b"Tim Duncan".to_vec()));
+    let output = session
+        .query_with_params(
+            "MATCH (v:player{name: $name}) RETURN v.player.age AS age;",
+            &params,
+        )
+        .await?;
+    if let Some(dataset) = output.dataset() {
+        println!("{}", dataset);
+    }
+
+    // The statement is killed on the server if it doesn't finish in time,
+    // and the session has to be dropped afterwards.
+    let output = session
+        .query_with_timeout(
+            "MATCH (v:player)-[:follow*1..3]->(v2) RETURN count(v2);",
+            Duration::from_secs(3),
+        )
+        .await?;
+    if let Some(dataset) = output.dataset() {
+        println!("{}", dataset);
+    }
+
     let output = session.query("SHOW HOSTS META;").await?;
     if let Some(dataset) = output.dataset() {
         println!("{}", dataset);
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use rust_nebula::{
+    graph::query::GraphQuery as _, ConnectionPool, ConnectionPoolConf, HostAddress,
+    SessionPoolManager, SingleConnSessionConf,
+};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    run().await
+}
+
+async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let config = SingleConnSessionConf::new(
+        vec![HostAddress::new("127.0.0.1", 9669)],
+        "root".to_owned(),
+        "password".to_owned(),
+        Some("basketballplayer".to_string()),
+    );
+
+    // At most 4 sockets per graphd, shared by all the sessions.
+    let mut pool_conf = ConnectionPoolConf::default();
+    pool_conf.set_max_size(4);
+    pool_conf.set_min_idle(Some(1));
+    let connections = Arc::new(ConnectionPool::new(config, pool_conf));
+
+    //
+    let manager = SessionPoolManager::new(connections.clone());
+    let pool = bb8::Pool::builder().max_size(20).build(manager).await?;
+
+    //
+    {
+        let mut session = pool.get().await?;
+        let res = session
+            .query("MATCH (v:player)-[:follow]->() RETURN v LIMIT 10;")
+            .await?;
+        println!("{:?}", res.dataset());
+    }
+
+    //
+    for (host, state) in connections.state() {
+        println!("{}: {state:?}", host.to_string());
+    }
+
+    Ok(())
+}
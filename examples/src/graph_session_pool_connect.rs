@@ -8,12 +8,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 async fn run() -> Result<(), Box<dyn std::error::Error>> {
-    let config = SingleConnSessionConf::new(
+    let mut config = SingleConnSessionConf::new(
         vec![HostAddress::new("127.0.0.1", 9669)],
         "root".to_owned(),
         "password".to_owned(),
         Some("basketballplayer".to_string()),
     );
+    // Ping sessions that have been idle for more than 30s before handing them out.
+    config.set_health_check(true);
+    config.set_ping_timeout(1000);
+    config.set_ping_idle_threshold(30 * 1000);
 
     //
     let manager = SingleConnSessionManager::new(config);
@@ -0,0 +1,441 @@
+//! `#[derive(NebulaTag)]` and `#[derive(NebulaEdge)]` of rust-nebula.
+//!
+//! ## Attributes
+//! - `#[nebula(name = "...")]` on the struct sets the name of the tag or edge
+//!   type. The snake case of the struct name is used by default.
+//! - `#[nebula(vid)]` marks the field holding the vid of a tag.
+//! - `#[nebula(src)]`, `#[nebula(dst)]` and the optional `#[nebula(rank)]` mark
+//!   the fields holding the key of an edge.
+//! - `#[nebula(rename = "...")]` sets the property name of a field.
+//! - `#[nebula(data_type = "...")]` sets the nGQL type of a field, e.g.
+//!   `fixed_string(32)`. It's required for types without a default mapping,
+//!   like the types of chrono, which also need a `ToNgqlLiteral`.
+//! - `#[nebula(skip)]` leaves a field out of the schema.
+//!
+//! Fields are yielded as the column `#[serde(rename)]` gives them, so the
+//! output of the generated fetch statements can be read with `scan`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    meta::ParseNestedMeta, parse_macro_input, Data, DeriveInput, Error, Field, Fields, LitStr, Type,
+};
+
+#[proc_macro_derive(NebulaTag, attributes(nebula))]
+pub fn derive_nebula_tag(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, Kind::Tag)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(NebulaEdge, attributes(nebula))]
+pub fn derive_nebula_edge(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, Kind::Edge)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Tag,
+    Edge,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Prop,
+    Vid,
+    Src,
+    Dst,
+    Rank,
+    Skip,
+}
+
+struct FieldDef<'a> {
+    field: &'a Field,
+    role: Role,
+    name: String,
+    column: String,
+    data_type: Option<String>,
+}
+
+fn expand(input: &DeriveInput, kind: Kind) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    input,
+                    "only structs with named fields are supported",
+                ))
+            }
+        },
+        _ => return Err(Error::new_spanned(input, "only structs are supported")),
+    };
+
+    let mut name = to_snake_case(&input.ident.to_string());
+    for attr in &input.attrs {
+        if attr.path().is_ident("nebula") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    name = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown nebula attribute"))
+                }
+            })?;
+        } else if attr.path().is_ident("serde") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename_all") {
+                    return Err(meta
+                        .error("rename_all isn't supported, use #[serde(rename)] on the fields"));
+                }
+                skip_meta(&meta)
+            })?;
+        }
+    }
+
+    let fields = fields
+        .iter()
+        .map(parse_field)
+        .collect::<syn::Result<Vec<_>>>()?;
+    let find = |role: Role| fields.iter().filter(move |v| v.role == role);
+    let single = |role: Role, attr: &str, required: bool| -> syn::Result<Option<&FieldDef>> {
+        let mut found = find(role);
+        let first = found.next();
+        if let Some(second) = found.next() {
+            return Err(Error::new_spanned(
+                second.field,
+                format!("duplicate #[nebula({attr})]"),
+            ));
+        }
+        if required && first.is_none() {
+            return Err(Error::new_spanned(
+                input,
+                format!("a field has to be marked with #[nebula({attr})]"),
+            ));
+        }
+        Ok(first)
+    };
+
+    let mut props = vec![];
+    let mut values = vec![];
+    for def in find(Role::Prop) {
+        let (data_type, nullable) = match (&def.data_type, nullable_inner(&def.field.ty)) {
+            (Some(data_type), (_, nullable)) => (data_type.clone(), nullable),
+            (None, (ty, nullable)) => match default_data_type(ty) {
+                Some(data_type) => (data_type.to_owned(), nullable),
+                None => {
+                    return Err(Error::new_spanned(
+                        &def.field.ty,
+                        "unknown nGQL type, set it with #[nebula(data_type = \"...\")]",
+                    ))
+                }
+            },
+        };
+        let (prop_name, column) = (&def.name, &def.column);
+        props.push(quote! {
+            ::rust_nebula::ngql::PropDef {
+                name: #prop_name,
+                data_type: #data_type,
+                nullable: #nullable,
+                column: #column,
+            }
+        });
+        let ident = &def.field.ident;
+        values.push(quote! { &self.#ident });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let common = quote! {
+        const NAME: &'static str = #name;
+        const PROPS: &'static [::rust_nebula::ngql::PropDef] = &[#(#props),*];
+
+        fn values(&self) -> ::std::vec::Vec<&dyn ::rust_nebula::ngql::ToNgqlLiteral> {
+            ::std::vec![#(#values),*]
+        }
+    };
+
+    let tokens = match kind {
+        Kind::Tag => {
+            for role in [Role::Src, Role::Dst, Role::Rank] {
+                if let Some(def) = find(role).next() {
+                    return Err(Error::new_spanned(
+                        def.field,
+                        "src, dst and rank are only allowed on edges",
+                    ));
+                }
+            }
+            let vid = single(Role::Vid, "vid", true)?.unwrap();
+            let (vid_ident, vid_column) = (&vid.field.ident, &vid.column);
+            quote! {
+                impl #impl_generics ::rust_nebula::ngql::NebulaTag for #ident #ty_generics #where_clause {
+                    #common
+                    const VID_COLUMN: &'static str = #vid_column;
+
+                    fn vid(&self) -> &dyn ::rust_nebula::ngql::ToNgqlLiteral {
+                        &self.#vid_ident
+                    }
+                }
+            }
+        }
+        Kind::Edge => {
+            if let Some(def) = find(Role::Vid).next() {
+                return Err(Error::new_spanned(
+                    def.field,
+                    "vid is only allowed on tags, use src and dst",
+                ));
+            }
+            let src = single(Role::Src, "src", true)?.unwrap();
+            let dst = single(Role::Dst, "dst", true)?.unwrap();
+            let rank = single(Role::Rank, "rank", false)?;
+            let (src_ident, src_column) = (&src.field.ident, &src.column);
+            let (dst_ident, dst_column) = (&dst.field.ident, &dst.column);
+            let (rank_column, rank_value) = match rank {
+                Some(def) => {
+                    let (ident, column) = (&def.field.ident, &def.column);
+                    (
+                        quote! { ::core::option::Option::Some(#column) },
+                        quote! { ::core::option::Option::Some(i64::from(self.#ident)) },
+                    )
+                }
+                None => (
+                    quote! { ::core::option::Option::None },
+                    quote! { ::core::option::Option::None },
+                ),
+            };
+            quote! {
+                impl #impl_generics ::rust_nebula::ngql::NebulaEdge for #ident #ty_generics #where_clause {
+                    #common
+                    const SRC_COLUMN: &'static str = #src_column;
+                    const DST_COLUMN: &'static str = #dst_column;
+                    const RANK_COLUMN: ::core::option::Option<&'static str> = #rank_column;
+
+                    fn src(&self) -> &dyn ::rust_nebula::ngql::ToNgqlLiteral {
+                        &self.#src_ident
+                    }
+
+                    fn dst(&self) -> &dyn ::rust_nebula::ngql::ToNgqlLiteral {
+                        &self.#dst_ident
+                    }
+
+                    fn rank(&self) -> ::core::option::Option<i64> {
+                        #rank_value
+                    }
+                }
+            }
+        }
+    };
+    Ok(tokens)
+}
+
+fn parse_field(field: &Field) -> syn::Result<FieldDef<'_>> {
+    let ident = field.ident.as_ref().expect("named field").to_string();
+    let ident = ident.trim_start_matches("r#").to_owned();
+    let mut def = FieldDef {
+        field,
+        role: Role::Prop,
+        name: ident.clone(),
+        column: ident,
+        data_type: None,
+    };
+
+    for attr in &field.attrs {
+        if attr.path().is_ident("nebula") {
+            attr.parse_nested_meta(|meta| {
+                let role = if meta.path.is_ident("vid") {
+                    Role::Vid
+                } else if meta.path.is_ident("src") {
+                    Role::Src
+                } else if meta.path.is_ident("dst") {
+                    Role::Dst
+                } else if meta.path.is_ident("rank") {
+                    Role::Rank
+                } else if meta.path.is_ident("skip") {
+                    Role::Skip
+                } else if meta.path.is_ident("rename") {
+                    def.name = meta.value()?.parse::<LitStr>()?.value();
+                    return Ok(());
+                } else if meta.path.is_ident("data_type") {
+                    def.data_type = Some(meta.value()?.parse::<LitStr>()?.value());
+                    return Ok(());
+                } else {
+                    return Err(meta.error("unknown nebula attribute"));
+                };
+                if def.role != Role::Prop {
+                    return Err(
+                        meta.error("a field can only have one of vid, src, dst, rank and skip")
+                    );
+                }
+                def.role = role;
+                Ok(())
+            })?;
+        } else if attr.path().is_ident("serde") {
+            attr.parse_nested_meta(|meta| {
+                if !meta.path.is_ident("rename") {
+                    return skip_meta(&meta);
+                }
+                if meta.input.peek(syn::Token![=]) {
+                    def.column = meta.value()?.parse::<LitStr>()?.value();
+                    return Ok(());
+                }
+                meta.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("deserialize") {
+                        def.column = meta.value()?.parse::<LitStr>()?.value();
+                        Ok(())
+                    } else {
+                        skip_meta(&meta)
+                    }
+                })
+            })?;
+        }
+    }
+    Ok(def)
+}
+
+/// Skip the value of an attribute that isn't ours, e.g. `default` or
+/// `with = "..."` of serde.
+fn skip_meta(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|meta| skip_meta(&meta))?;
+    }
+    Ok(())
+}
+
+/// Returns the inner type of an `Option`, and whether the property is nullable.
+fn nullable_inner(ty: &Type) -> (&Type, bool) {
+    if let Type::Path(path) = ty {
+        if let Some(segment) = path.path.segments.last() {
+            if segment.ident == "Option" {
+                if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
+                    if let Some(syn::GenericArgument::Type(inner)) = args.args.first() {
+                        return (inner, true);
+                    }
+                }
+            }
+        }
+    }
+    (ty, false)
+}
+
+fn default_data_type(ty: &Type) -> Option<&'static str> {
+    let ty = match ty {
+        Type::Reference(reference) => &reference.elem,
+        ty => ty,
+    };
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let last = path.path.segments.last()?;
+    // The date and time types of rust-nebula and std only, `chrono::DateTime<Utc>`,
+    // `chrono::Duration` or `time::Duration` have no `ToNgqlLiteral`.
+    let foreign = !last.arguments.is_none()
+        || path
+            .path
+            .segments
+            .first()
+            .is_some_and(|v| v.ident == "chrono" || v.ident == "time");
+    let data_type = match last.ident.to_string().as_str() {
+        "String" | "str" | "char" => "string",
+        "bool" => "bool",
+        "i8" => "int8",
+        "i16" | "u8" => "int16",
+        "i32" | "u16" => "int32",
+        "i64" | "isize" | "u32" => "int64",
+        "f32" => "float",
+        "f64" => "double",
+        "Date" | "Time" | "DateTime" | "Duration" if foreign => return None,
+        "Date" => "date",
+        "Time" => "time",
+        "DateTime" => "datetime",
+        "Duration" => "duration",
+        "Geography" => "geography",
+        _ => return None,
+    };
+    Some(data_type)
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.char_indices() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_snake_case() {
+        assert_eq!(to_snake_case("Player"), "player");
+        assert_eq!(to_snake_case("TeamMember"), "team_member");
+    }
+
+    #[test]
+    fn test_default_data_type() {
+        let ty = |s: &str| syn::parse_str::<Type>(s).unwrap();
+        assert_eq!(default_data_type(&ty("String")), Some("string"));
+        assert_eq!(default_data_type(&ty("&'static str")), Some("string"));
+        assert_eq!(default_data_type(&ty("u32")), Some("int64"));
+        assert_eq!(
+            default_data_type(&ty("rust_nebula::common::types::DateTime")),
+            Some("datetime")
+        );
+        assert_eq!(
+            default_data_type(&ty("std::time::Duration")),
+            Some("duration")
+        );
+        assert_eq!(default_data_type(&ty("DateTime<Utc>")), None);
+        assert_eq!(default_data_type(&ty("chrono::Duration")), None);
+        assert_eq!(default_data_type(&ty("time::Duration")), None);
+        assert_eq!(default_data_type(&ty("Vec<String>")), None);
+
+        let option = ty("Option<i64>");
+        let (inner, nullable) = nullable_inner(&option);
+        assert!(nullable);
+        assert_eq!(default_data_type(inner), Some("int64"));
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let input = |s: &str| syn::parse_str::<DeriveInput>(s).unwrap();
+
+        let err = expand(&input("struct Player { name: String }"), Kind::Tag).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a field has to be marked with #[nebula(vid)]"
+        );
+
+        let err = expand(
+            &input("struct Player { #[nebula(vid)] id: String, tags: Vec<String> }"),
+            Kind::Tag,
+        )
+        .unwrap_err();
+        assert!(err.to_string().starts_with("unknown nGQL type"));
+
+        let err = expand(
+            &input("struct Follow { #[nebula(src)] src: String, #[nebula(vid)] dst: String }"),
+            Kind::Edge,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "vid is only allowed on tags, use src and dst"
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use nebula_fbthrift_graph_v3::errors::graph_service::SignoutError;
+use tokio::runtime::Runtime;
+
+use crate::common::Value;
+use crate::graph::{GraphQuery as _, GraphQueryOutput, JsonQueryOutput};
+use crate::{SingleConnSessionConf, SingleConnSessionError};
+
+/// The blocking counterpart of `crate::SingleConnSessionManager`.
+pub struct SingleConnSessionManager {
+    inner: crate::SingleConnSessionManager,
+    runtime: Arc<Runtime>,
+}
+
+impl SingleConnSessionManager {
+    pub fn new(config: SingleConnSessionConf) -> io::Result<Self> {
+        Ok(Self {
+            inner: crate::SingleConnSessionManager::new(config),
+            runtime: super::runtime()?,
+        })
+    }
+
+    pub fn get_session(&self) -> Result<SingleConnSession, SingleConnSessionError> {
+        let inner = self.runtime.block_on(self.inner.get_session())?;
+        Ok(SingleConnSession {
+            inner,
+            runtime: self.runtime.clone(),
+        })
+    }
+
+    /// Kill the running statements of the session `session_id` from a separate session.
+    pub fn kill_session_queries(&self, session_id: i64) -> Result<(), SingleConnSessionError> {
+        self.runtime
+            .block_on(self.inner.kill_session_queries(session_id))
+    }
+}
+
+/// The blocking counterpart of `crate::SingleConnSession`.
+pub struct SingleConnSession {
+    inner: crate::SingleConnSession,
+    runtime: Arc<Runtime>,
+}
+
+impl SingleConnSession {
+    pub fn query(&mut self, stmt: &str) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        self.runtime.block_on(self.inner.query(stmt))
+    }
+
+    pub fn execute(&mut self, stmt: &str) -> Result<(), SingleConnSessionError> {
+        self.runtime.block_on(self.inner.execute(stmt))
+    }
+
+    pub fn query_with_params(
+        &mut self,
+        stmt: &str,
+        params: &HashMap<Vec<u8>, Value>,
+    ) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        self.runtime
+            .block_on(self.inner.query_with_params(stmt, params))
+    }
+
+    pub fn execute_with_params(
+        &mut self,
+        stmt: &str,
+        params: &HashMap<Vec<u8>, Value>,
+    ) -> Result<(), SingleConnSessionError> {
+        self.runtime
+            .block_on(self.inner.execute_with_params(stmt, params))
+    }
+
+    pub fn query_with_timeout(
+        &mut self,
+        stmt: &str,
+        timeout: Duration,
+    ) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        self.runtime
+            .block_on(self.inner.query_with_timeout(stmt, timeout))
+    }
+
+    pub fn execute_with_timeout(
+        &mut self,
+        stmt: &str,
+        timeout: Duration,
+    ) -> Result<(), SingleConnSessionError> {
+        self.runtime
+            .block_on(self.inner.execute_with_timeout(stmt, timeout))
+    }
+
+    pub fn query_idempotent(
+        &mut self,
+        stmt: &str,
+    ) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        self.runtime.block_on(self.inner.query_idempotent(stmt))
+    }
+
+    pub fn execute_json(&mut self, stmt: &str) -> Result<Vec<u8>, SingleConnSessionError> {
+        self.runtime.block_on(self.inner.execute_json(stmt))
+    }
+
+    pub fn query_json(&mut self, stmt: &str) -> Result<JsonQueryOutput, SingleConnSessionError> {
+        self.runtime.block_on(self.inner.query_json(stmt))
+    }
+
+    pub fn ping(&mut self) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        self.runtime.block_on(self.inner.ping())
+    }
+
+    pub fn signout(self) -> Result<(), SignoutError> {
+        self.runtime.block_on(self.inner.signout())
+    }
+
+    pub fn is_close_required(&self) -> bool {
+        self.inner.is_close_required()
+    }
+
+    /// Returns how long the session has been idle since its last query.
+    pub fn get_idle_duration(&self) -> Duration {
+        self.inner.get_idle_duration()
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc;
+
+    use serde::Deserialize;
+
+    use crate::testing::{graph, FakeGraphServer, Reply};
+    use crate::HostAddress;
+
+    #[derive(Deserialize)]
+    struct N {
+        n: i64,
+    }
+
+    /// Run a fake graphd on a thread of its own.
+    fn fake_graph() -> HostAddress {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let runtime = super::super::runtime().unwrap();
+            runtime.block_on(async {
+                let server = FakeGraphServer::start().await.unwrap();
+                server.on_statement(
+                    "YIELD 1 AS n;",
+                    Reply::new(graph::response(Some(graph::data_set(
+                        &["n"],
+                        vec![vec![Value::iVal(1)]],
+                    )))),
+                );
+                tx.send(server.addr()).unwrap();
+                std::future::pending::<()>().await
+            })
+        });
+        rx.recv().unwrap()
+    }
+
+    #[test]
+    fn test_session() -> Result<(), Box<dyn std::error::Error>> {
+        let conf = SingleConnSessionConf::new(
+            vec![fake_graph()],
+            "root".to_owned(),
+            "nebula".to_owned(),
+            None,
+        );
+        let manager = SingleConnSessionManager::new(conf)?;
+        let mut session = manager.get_session()?;
+        assert_eq!(session.query("YIELD 1 AS n;")?.scan::<N>()?[0].n, 1);
+
+        // A session opened on one thread is used on another.
+        let handle = std::thread::spawn(move || -> Result<(), SingleConnSessionError> {
+            session.execute("YIELD 2;")?;
+            session
+                .signout()
+                .map_err(SingleConnSessionError::SignoutError)
+        });
+        handle.join().unwrap()?;
+
+        let handles = (0..2)
+            .map(|_| {
+                let mut session = manager.get_session().unwrap();
+                std::thread::spawn(move || session.ping().map(|_| ()))
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+        Ok(())
+    }
+}
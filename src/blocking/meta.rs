@@ -0,0 +1,102 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use nebula_fbthrift_meta_v3::Schema;
+use tokio::runtime::Runtime;
+
+use crate::common::HostAddr;
+use crate::{HostAddress, MetaClientError, TlsConfig};
+
+/// The blocking counterpart of `crate::MetaClient`.
+pub struct MetaClient {
+    pub(super) inner: crate::MetaClient,
+    pub(super) runtime: Arc<Runtime>,
+}
+
+impl MetaClient {
+    pub fn new(maddr: &[HostAddress]) -> Result<Self, MetaClientError> {
+        let runtime = super::runtime().map_err(MetaClientError::CreateTransportError)?;
+        let inner = runtime.block_on(crate::MetaClient::new(maddr))?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// Connect to the metad servers with TLS.
+    pub fn new_with_tls(maddr: &[HostAddress], tls: TlsConfig) -> Result<Self, MetaClientError> {
+        let runtime = super::runtime().map_err(MetaClientError::CreateTransportError)?;
+        let inner = runtime.block_on(crate::MetaClient::new_with_tls(maddr, tls))?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// Gets all storage addresses.
+    pub fn get_all_storage_addrs(&mut self) -> Result<&Vec<HostAddr>, MetaClientError> {
+        self.runtime.block_on(self.inner.get_all_storage_addrs())
+    }
+
+    /// Gets the ID of a space.
+    pub fn get_space_id(&mut self, space_name: &str) -> Result<i32, MetaClientError> {
+        self.runtime.block_on(self.inner.get_space_id(space_name))
+    }
+
+    /// Gets the ID of a tag.
+    pub fn get_tag_id(&mut self, space_name: &str, tag_name: &str) -> Result<i32, MetaClientError> {
+        self.runtime
+            .block_on(self.inner.get_tag_id(space_name, tag_name))
+    }
+
+    /// Gets the type of an edge.
+    pub fn get_edge_type(
+        &mut self,
+        space_name: &str,
+        edge_name: &str,
+    ) -> Result<i32, MetaClientError> {
+        self.runtime
+            .block_on(self.inner.get_edge_type(space_name, edge_name))
+    }
+
+    /// Gets the schema of a tag.
+    pub fn get_tag_schema(
+        &mut self,
+        space_name: &str,
+        tag_name: &str,
+    ) -> Result<&Schema, MetaClientError> {
+        self.runtime
+            .block_on(self.inner.get_tag_schema(space_name, tag_name))
+    }
+
+    /// Gets the schema of an edge.
+    pub fn get_edge_schema(
+        &mut self,
+        space_name: &str,
+        edge_name: &str,
+    ) -> Result<&Schema, MetaClientError> {
+        self.runtime
+            .block_on(self.inner.get_edge_schema(space_name, edge_name))
+    }
+
+    /// Gets the leader of a partition.
+    pub fn get_part_leader(
+        &mut self,
+        space_name: &str,
+        part_id: i32,
+    ) -> Result<&HostAddr, MetaClientError> {
+        self.runtime
+            .block_on(self.inner.get_part_leader(space_name, part_id))
+    }
+
+    /// Gets all part leaders of a space.
+    pub fn get_part_leaders(
+        &mut self,
+        space_name: &str,
+    ) -> Result<&HashMap<i32, HostAddr>, MetaClientError> {
+        self.runtime
+            .block_on(self.inner.get_part_leaders(space_name))
+    }
+
+    /// Gets all part allocations of a space.
+    pub fn get_part_alloc(
+        &mut self,
+        space_name: &str,
+    ) -> Result<&BTreeMap<i32, Vec<HostAddr>>, MetaClientError> {
+        self.runtime.block_on(self.inner.get_part_alloc(space_name))
+    }
+}
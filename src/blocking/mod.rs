@@ -0,0 +1,43 @@
+//! Synchronous clients, behind the `blocking` feature.
+//!
+//! Each client owns a current-thread tokio runtime and blocks the calling
+//! thread on the async client. Sessions opened by a
+//! `blocking::SingleConnSessionManager`, and the `blocking::StorageClient` built
+//! from a `blocking::MetaClient`, share the runtime of their creator.
+//! ## Panics
+//! The methods panic when they're called from within an async context, use the
+//! async clients there.
+//! ## Example
+//! ```ignore
+//! use rust_nebula::{blocking, SingleConnSessionConf};
+//!
+//! let manager = blocking::SingleConnSessionManager::new(conf)?;
+//! let mut session = manager.get_session()?;
+//! let output = session.query("YIELD 1 AS n;")?;
+//! ```
+
+use std::io;
+use std::sync::Arc;
+
+use tokio::runtime::{Builder, Runtime};
+
+#[cfg(feature = "graph")]
+mod graph;
+#[cfg(feature = "graph")]
+pub use graph::{SingleConnSession, SingleConnSessionManager};
+
+#[cfg(feature = "meta")]
+mod meta;
+#[cfg(feature = "meta")]
+pub use meta::MetaClient;
+
+#[cfg(feature = "storage")]
+mod storage;
+#[cfg(feature = "storage")]
+pub use storage::StorageClient;
+
+fn runtime() -> io::Result<Arc<Runtime>> {
+    Ok(Arc::new(
+        Builder::new_current_thread().enable_all().build()?,
+    ))
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use tokio::runtime::Runtime;
+
+use super::MetaClient;
+use crate::storage::query::StorageQueryOutput;
+use crate::{StorageClientError, TlsConfig};
+
+/// The blocking counterpart of `crate::StorageClient`, sharing the runtime of
+/// its `MetaClient`.
+pub struct StorageClient {
+    inner: crate::StorageClient,
+    runtime: Arc<Runtime>,
+}
+
+impl StorageClient {
+    pub fn new(mclient: MetaClient) -> Self {
+        let runtime = mclient.runtime;
+        let inner = runtime.block_on(crate::StorageClient::new(mclient.inner));
+        Self { inner, runtime }
+    }
+
+    /// Connect to the storaged servers with TLS.
+    pub fn new_with_tls(mclient: MetaClient, tls: TlsConfig) -> Self {
+        let runtime = mclient.runtime;
+        let inner = runtime.block_on(crate::StorageClient::new_with_tls(mclient.inner, tls));
+        Self { inner, runtime }
+    }
+
+    /// `prop_names` is None means return all properties
+    pub fn scan_vertex(
+        &mut self,
+        space_name: &str,
+        tag_name: &str,
+        prop_names: Option<Vec<&str>>,
+    ) -> Result<Vec<StorageQueryOutput>, StorageClientError> {
+        self.runtime
+            .block_on(self.inner.scan_vertex(space_name, tag_name, prop_names))
+    }
+
+    /// `prop_names` is None means return all properties
+    pub fn scan_edge(
+        &mut self,
+        space_name: &str,
+        edge_name: &str,
+        prop_names: Option<Vec<&str>>,
+    ) -> Result<Vec<StorageQueryOutput>, StorageClientError> {
+        self.runtime
+            .block_on(self.inner.scan_edge(space_name, edge_name, prop_names))
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc;
+
+    use nebula_fbthrift_meta_v3::{EdgeItem, TagItem};
+
+    use crate::common::Value;
+    use crate::testing::{graph::data_set, FakeMetaServer, FakeStorageServer};
+    use crate::HostAddress;
+
+    /// Run a fake metad and storaged with a tag and an edge on a thread of
+    /// their own.
+    fn fake_cluster() -> HostAddress {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let runtime = super::super::runtime().unwrap();
+            runtime.block_on(async {
+                let storage = FakeStorageServer::start().await.unwrap();
+                storage.add_vertices(
+                    1,
+                    1,
+                    2,
+                    data_set(&["player._vid"], vec![vec![Value::iVal(1)]]),
+                );
+                storage.add_edges(
+                    1,
+                    1,
+                    3,
+                    data_set(&["follow._src"], vec![vec![Value::iVal(1)]]),
+                );
+                let meta = FakeMetaServer::start().await.unwrap();
+                meta.add_space(1, "test", [(1, vec![storage.addr()])].into());
+                meta.add_tag(
+                    1,
+                    TagItem {
+                        tag_id: 2,
+                        tag_name: b"player".to_vec(),
+                        ..Default::default()
+                    },
+                );
+                meta.add_edge(
+                    1,
+                    EdgeItem {
+                        edge_type: 3,
+                        edge_name: b"follow".to_vec(),
+                        ..Default::default()
+                    },
+                );
+                tx.send(meta.addr()).unwrap();
+                std::future::pending::<()>().await
+            })
+        });
+        rx.recv().unwrap()
+    }
+
+    #[test]
+    fn test_scan() -> Result<(), Box<dyn std::error::Error>> {
+        let addr = fake_cluster();
+        let handle = std::thread::spawn(
+            move || -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+                let mut mclient = MetaClient::new(&[addr])?;
+                assert_eq!(mclient.get_space_id("test")?, 1);
+                assert_eq!(mclient.get_edge_type("test", "follow")?, 3);
+                assert_eq!(mclient.get_part_leaders("test")?.len(), 1);
+
+                let mut sclient = StorageClient::new(mclient);
+                let vertices = sclient.scan_vertex("test", "player", Some(vec![]))?;
+                assert_eq!(vertices[0].get_row_size(), 1);
+                let edges = sclient.scan_edge("test", "follow", Some(vec![]))?;
+                assert_eq!(edges[0].get_row_size(), 1);
+                Ok(())
+            },
+        );
+        handle
+            .join()
+            .unwrap()
+            .map_err(|err| err as Box<dyn std::error::Error>)?;
+        Ok(())
+    }
+}
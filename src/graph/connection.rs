@@ -3,23 +3,21 @@ use fbthrift::{
     ApplicationException, ApplicationExceptionErrorCode, BinaryProtocol, BufMutExt, Framing,
     FramingDecoded, FramingEncodedFinal, ProtocolEncoded, Transport,
 };
-use fbthrift_transport::{
-    impl_tokio::{TokioSleep, TokioTcpStream},
-    AsyncTransport,
-};
+use fbthrift_transport::{impl_tokio::TokioSleep, AsyncTransport};
 use nebula_fbthrift_graph_v3::{
     client::{GraphService, GraphServiceImpl},
     dependencies::common::types::ErrorCode,
-    errors::graph_service::AuthenticateError,
+    errors::graph_service::{AuthenticateError, VerifyClientVersionError},
+    types::{VerifyClientVersionReq, VerifyClientVersionResp},
 };
 
-use crate::GraphTransportResponseHandler;
+use crate::{GraphTransportResponseHandler, NebulaStream};
 
 //
 //
 //
 pub(super) struct GraphConnection<
-    T = AsyncTransport<TokioTcpStream, TokioSleep, GraphTransportResponseHandler>,
+    T = AsyncTransport<NebulaStream, TokioSleep, GraphTransportResponseHandler>,
 > where
     T: Transport + Framing<DecBuf = std::io::Cursor<Bytes>>,
     Bytes: Framing<DecBuf = FramingDecoded<T>>,
@@ -41,6 +39,19 @@ where
         }
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                username = username,
+                session_id = tracing::field::Empty,
+                error_code = tracing::field::Empty,
+            ),
+            err(Display)
+        )
+    )]
     pub(super) async fn authenticate(
         &self,
         username: &str,
@@ -51,6 +62,8 @@ where
             .authenticate(&username.as_bytes().to_vec(), &password.as_bytes().to_vec())
             .await?;
 
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("error_code", tracing::field::display(res.error_code));
         if res.error_code != ErrorCode::SUCCEEDED {
             return Err(ApplicationException::new(
                 ApplicationExceptionErrorCode::Unknown,
@@ -67,6 +80,19 @@ where
             )
         })?;
 
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("session_id", session_id);
         Ok(session_id)
     }
+
+    pub(super) async fn verify_client_version(
+        &self,
+        version: &str,
+    ) -> Result<VerifyClientVersionResp, VerifyClientVersionError> {
+        let req = VerifyClientVersionReq {
+            version: version.as_bytes().to_vec(),
+            ..Default::default()
+        };
+        self.service.verifyClientVersion(&req).await
+    }
 }
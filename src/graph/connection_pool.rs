@@ -0,0 +1,362 @@
+//! Connections to graphd, pooled per host and shared by sessions.
+//!
+//! A graphd session isn't bound to the connection it was authenticated on, so
+//! `SessionPool` keeps authenticated session ids and borrows a connection from
+//! `ConnectionPool` for every statement. A broken connection is dropped by the
+//! pool and replaced by a new one without authenticating again.
+//! ## Example
+//! ```ignore
+//! use std::sync::Arc;
+//! use rust_nebula::graph::{ConnectionPool, ConnectionPoolConf, SessionPool, SessionPoolManager};
+//!
+//! let connections = Arc::new(ConnectionPool::new(conf, ConnectionPoolConf::default()));
+//! let pool: SessionPool = bb8::Pool::builder()
+//!     .max_size(100)
+//!     .build(SessionPoolManager::new(connections))
+//!     .await?;
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use nebula_fbthrift_graph_v3::{
+    client::GraphService as _,
+    dependencies::common::types::{ErrorCode, Value},
+    errors::graph_service::{AuthenticateError, ExecuteError, SignoutError},
+};
+
+use crate::graph::{
+    connection::GraphConnection,
+    load_balancer::HostLease,
+    query::{GraphQueryError, GraphQueryOutput},
+    single_conn_session::single_conn_session_manager::is_connect_error,
+    SingleConnSessionConf, SingleConnSessionError, SingleConnSessionManager,
+};
+use crate::{HostAddress, TimezoneInfo};
+
+const DEFAULT_MAX_SIZE: u32 = 10;
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+/// Idle connections are checked for `idle_timeout` at least this often
+const MAX_REAPER_RATE: Duration = Duration::from_secs(30);
+
+/// Sizes and timeouts of the connections to every host.
+#[derive(Debug, Clone)]
+pub struct ConnectionPoolConf {
+    /// Maximum number of connections to a host, idle or in use
+    pub max_size: u32,
+    /// Number of idle connections kept open to every host
+    pub min_idle: Option<u32>,
+    /// Idle connections above `min_idle` are closed after this long
+    pub idle_timeout: Option<Duration>,
+    /// How long `ConnectionPool::get` waits for a host to accept a new connection,
+    /// or to have a free one
+    pub connection_timeout: Duration,
+}
+
+impl Default for ConnectionPoolConf {
+    fn default() -> Self {
+        Self {
+            max_size: DEFAULT_MAX_SIZE,
+            min_idle: None,
+            idle_timeout: Some(DEFAULT_IDLE_TIMEOUT),
+            connection_timeout: DEFAULT_CONNECTION_TIMEOUT,
+        }
+    }
+}
+
+impl ConnectionPoolConf {
+    pub fn set_max_size(&mut self, size: u32) {
+        self.max_size = size;
+    }
+    pub fn set_min_idle(&mut self, size: Option<u32>) {
+        self.min_idle = size;
+    }
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+    pub fn set_connection_timeout(&mut self, timeout: Duration) {
+        self.connection_timeout = timeout;
+    }
+}
+
+//
+//
+//
+/// A connection that hasn't failed yet.
+///
+/// `broken` is set while a request is in flight and cleared once its response
+/// has been read, so a failed or cancelled request closes the connection.
+struct HostConnection {
+    conn: GraphConnection,
+    broken: bool,
+}
+
+/// Opens the connections to one host.
+struct HostConnectionManager {
+    manager: Arc<SingleConnSessionManager>,
+    addr: HostAddress,
+}
+
+#[async_trait]
+impl bb8::ManageConnection for HostConnectionManager {
+    type Connection = HostConnection;
+    type Error = SingleConnSessionError;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        let conn = self.manager.connect(&self.addr).await?;
+        Ok(HostConnection {
+            conn,
+            broken: false,
+        })
+    }
+
+    async fn is_valid(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        conn.broken
+    }
+}
+
+/// Quarantines a host whose connections opened in the background, e.g. for
+/// `min_idle`, fail.
+#[derive(Clone)]
+struct HostErrorSink {
+    manager: Arc<SingleConnSessionManager>,
+    addr: HostAddress,
+}
+
+impl fmt::Debug for HostErrorSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostErrorSink")
+            .field("addr", &self.addr)
+            .finish()
+    }
+}
+
+impl bb8::ErrorSink<SingleConnSessionError> for HostErrorSink {
+    fn sink(&self, err: SingleConnSessionError) {
+        if is_connect_error(&err) {
+            self.manager.config.quarantine_host(&self.addr);
+        }
+    }
+
+    fn boxed_clone(&self) -> Box<dyn bb8::ErrorSink<SingleConnSessionError>> {
+        Box::new(self.clone())
+    }
+}
+
+//
+//
+//
+/// Pools of connections to the graphd servers of `SingleConnSessionConf::host_addrs`.
+///
+/// The hosts are tried in the order of `SingleConnSessionConf::get_connect_order`,
+/// and hosts that can't be reached are quarantined like by `SingleConnSessionManager`.
+pub struct ConnectionPool {
+    manager: Arc<SingleConnSessionManager>,
+    pools: Vec<(HostAddress, bb8::Pool<HostConnectionManager>)>,
+    conf: ConnectionPoolConf,
+}
+
+impl ConnectionPool {
+    /// Create the pools, the connections are opened lazily.
+    pub fn new(config: SingleConnSessionConf, conf: ConnectionPoolConf) -> Self {
+        let manager = Arc::new(SingleConnSessionManager::new(config));
+        let pools = manager
+            .config
+            .host_addrs
+            .iter()
+            .map(|addr| {
+                let pool = bb8::Pool::builder()
+                    .max_size(conf.max_size)
+                    .min_idle(conf.min_idle)
+                    .idle_timeout(conf.idle_timeout)
+                    .reaper_rate(
+                        conf.idle_timeout
+                            .map_or(MAX_REAPER_RATE, |v| v.min(MAX_REAPER_RATE)),
+                    )
+                    .connection_timeout(conf.connection_timeout)
+                    .retry_connection(false)
+                    .error_sink(Box::new(HostErrorSink {
+                        manager: manager.clone(),
+                        addr: addr.clone(),
+                    }))
+                    .build_unchecked(HostConnectionManager {
+                        manager: manager.clone(),
+                        addr: addr.clone(),
+                    });
+                (addr.clone(), pool)
+            })
+            .collect();
+        Self {
+            manager,
+            pools,
+            conf,
+        }
+    }
+
+    pub fn config(&self) -> &SingleConnSessionConf {
+        &self.manager.config
+    }
+
+    /// Borrow a connection from the first host that has one.
+    ///
+    /// If a host has no idle connection, a new one is opened here rather than
+    /// by the pool, so a host that can't be reached within `connection_timeout`
+    /// is quarantined and the next one is tried. A host whose pool is exhausted
+    /// for `connection_timeout` is skipped, but not quarantined.
+    pub async fn get(&self) -> Result<PooledConnection<'_>, SingleConnSessionError> {
+        let mut failures = vec![];
+        for addr in self.manager.config.get_connect_order() {
+            let pool = match self.pools.iter().find(|(v, _)| v == &addr) {
+                Some((_, pool)) => pool,
+                None => continue,
+            };
+            let state = pool.state();
+            if state.idle_connections == 0 && state.connections < self.conf.max_size {
+                let timeout = self.conf.connection_timeout;
+                let res = tokio::time::timeout(timeout, self.manager.connect(&addr))
+                    .await
+                    .unwrap_or(Err(SingleConnSessionError::ConnectionPoolTimeout(timeout)));
+                match res {
+                    // The pool may have filled up in the meantime, the connection
+                    // is closed then.
+                    Ok(conn) => {
+                        let _ = pool.add(HostConnection {
+                            conn,
+                            broken: false,
+                        });
+                    }
+                    Err(err)
+                        if is_connect_error(&err)
+                            || matches!(err, SingleConnSessionError::ConnectionPoolTimeout(_)) =>
+                    {
+                        self.manager.config.quarantine_host(&addr);
+                        failures.push((addr, err));
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            match pool.get().await {
+                Ok(conn) => {
+                    self.manager.config.release_host(&addr);
+                    return Ok(PooledConnection {
+                        conn,
+                        host_lease: self.manager.config.lease_host(&addr),
+                        host: addr,
+                        timezone_info: TimezoneInfo {},
+                    });
+                }
+                Err(bb8::RunError::User(err)) => return Err(err),
+                Err(bb8::RunError::TimedOut) => failures.push((
+                    addr,
+                    SingleConnSessionError::ConnectionPoolTimeout(self.conf.connection_timeout),
+                )),
+            }
+        }
+        Err(SingleConnSessionError::NoAvailableHost(failures))
+    }
+
+    /// The number of open and idle connections of every host.
+    pub fn state(&self) -> Vec<(HostAddress, bb8::State)> {
+        self.pools
+            .iter()
+            .map(|(addr, pool)| (addr.clone(), pool.state()))
+            .collect()
+    }
+}
+
+//
+//
+//
+/// A connection borrowed from `ConnectionPool`, it's returned when dropped.
+pub struct PooledConnection<'a> {
+    conn: bb8::PooledConnection<'a, HostConnectionManager>,
+    host: HostAddress,
+    /// Counts the connection as outstanding while it's borrowed
+    host_lease: HostLease,
+    timezone_info: TimezoneInfo,
+}
+
+impl PooledConnection<'_> {
+    pub fn host(&self) -> &HostAddress {
+        &self.host
+    }
+
+    /// Close the connection instead of returning it to the pool, e.g. because
+    /// a response may still arrive.
+    pub fn mark_broken(&mut self) {
+        self.conn.broken = true;
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.conn.broken
+    }
+
+    pub async fn authenticate(
+        &mut self,
+        username: &str,
+        password: &str,
+    ) -> Result<i64, SingleConnSessionError> {
+        self.conn.broken = true;
+        let res = self.conn.conn.authenticate(username, password).await;
+        self.conn.broken = matches!(res, Err(AuthenticateError::ThriftError(_)));
+        res.map_err(SingleConnSessionError::AuthenticateError)
+    }
+
+    pub async fn signout(&mut self, session_id: i64) -> Result<(), SignoutError> {
+        self.conn.broken = true;
+        let res = self.conn.conn.service.signout(session_id).await;
+        self.conn.broken = matches!(res, Err(SignoutError::ThriftError(_)));
+        res
+    }
+
+    /// Execute stmt in the session `session_id`.
+    ///
+    /// The connection is marked as broken until the whole response has been
+    /// read, so the pool replaces it after a transport error or if the future
+    /// is dropped.
+    pub async fn execute(
+        &mut self,
+        session_id: i64,
+        stmt: &str,
+        params: Option<&BTreeMap<Vec<u8>, Value>>,
+    ) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        let stmt = stmt.as_bytes().to_vec();
+        self.conn.broken = true;
+        let service = &self.conn.conn.service;
+        let res = match params {
+            Some(params) => {
+                service
+                    .executeWithParameter(session_id, &stmt, params)
+                    .await
+            }
+            None => service.execute(session_id, &stmt).await,
+        };
+        let res = match res {
+            Ok(res) => res,
+            Err(err @ ExecuteError::ThriftError(_)) => {
+                self.host_lease.record_query(false);
+                return Err(GraphQueryError::ExecuteError(err).into());
+            }
+            Err(err) => {
+                self.conn.broken = false;
+                return Err(GraphQueryError::ExecuteError(err).into());
+            }
+        };
+        self.conn.broken = false;
+        self.host_lease.record_query(true);
+
+        if res.error_code != ErrorCode::SUCCEEDED {
+            return Err(GraphQueryError::ResponseError(res.error_code, res.error_msg).into());
+        }
+        Ok(GraphQueryOutput::new(res, self.timezone_info.clone()))
+    }
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+use crate::common::types::ErrorCode;
+
+/// Typed model of the response of graphd's `executeJson`.
+///
+/// Field names follow the JSON produced by graphd, so the value can be
+/// serialized again and forwarded as is.
+/// ## Example
+/// ```json
+/// {
+///   "errors": [{"code": 0}],
+///   "results": [{
+///     "spaceName": "basketballplayer",
+///     "latencyInUs": 1234,
+///     "columns": ["name"],
+///     "data": [{"row": ["Tim Duncan"], "meta": [null]}],
+///     "errors": {"code": 0}
+///   }]
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct JsonQueryOutput {
+    #[serde(default)]
+    pub errors: Vec<JsonQueryErrorInfo>,
+    #[serde(default)]
+    pub results: Vec<JsonQueryResult>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct JsonQueryErrorInfo {
+    pub code: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonQueryResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub space_name: Option<String>,
+    #[serde(default)]
+    pub latency_in_us: i64,
+    #[serde(default)]
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub data: Vec<JsonRow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errors: Option<JsonQueryErrorInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan_desc: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// One row of a result. `meta` describes the graph elements in `row`,
+/// e.g. the vid and type of a vertex, and is `null` for plain values.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct JsonRow {
+    #[serde(default)]
+    pub row: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub meta: Vec<serde_json::Value>,
+}
+
+impl JsonQueryOutput {
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(bytes)
+    }
+
+    /// Returns the first non-zero error code, either the global one or one of a result.
+    pub fn get_error_code(&self) -> ErrorCode {
+        self.first_error()
+            .map_or(ErrorCode::SUCCEEDED, |v| ErrorCode(v.code))
+    }
+
+    pub fn get_error_msg(&self) -> Option<String> {
+        self.first_error().and_then(|v| v.message.clone())
+    }
+
+    pub fn is_succeed(&self) -> bool {
+        self.get_error_code() == ErrorCode::SUCCEEDED
+    }
+
+    pub fn get_space_name(&self) -> Option<String> {
+        self.results.last().and_then(|v| v.space_name.clone())
+    }
+
+    pub fn get_latency(&self) -> i64 {
+        self.results.iter().map(|v| v.latency_in_us).sum()
+    }
+
+    fn first_error(&self) -> Option<&JsonQueryErrorInfo> {
+        self.errors
+            .iter()
+            .chain(self.results.iter().filter_map(|v| v.errors.as_ref()))
+            .find(|v| v.code != ErrorCode::SUCCEEDED.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize() -> Result<(), Box<dyn std::error::Error>> {
+        let bytes = br#"{
+            "errors": [{"code": 0}],
+            "results": [{
+                "spaceName": "basketballplayer",
+                "latencyInUs": 1234,
+                "columns": ["v", "age"],
+                "data": [{
+                    "row": [{"player.name": "Tim Duncan", "player.age": 42}, 42],
+                    "meta": [{"type": "vertex", "id": "player100"}, null]
+                }],
+                "errors": {"code": 0}
+            }]
+        }"#;
+        let output = JsonQueryOutput::from_slice(bytes)?;
+
+        assert!(output.is_succeed());
+        assert_eq!(output.get_space_name(), Some("basketballplayer".to_owned()));
+        assert_eq!(output.get_latency(), 1234);
+        let result = &output.results[0];
+        assert_eq!(result.columns, vec!["v", "age"]);
+        assert_eq!(result.data[0].row[1], serde_json::json!(42));
+        assert_eq!(result.data[0].meta[0]["id"], serde_json::json!("player100"));
+
+        let round_trip = JsonQueryOutput::from_slice(&serde_json::to_vec(&output)?)?;
+        assert_eq!(round_trip, output);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_with_error() -> Result<(), Box<dyn std::error::Error>> {
+        let bytes = br#"{
+            "errors": [{"code": -1004, "message": "SyntaxError: syntax error near `MATC'"}]
+        }"#;
+        let output = JsonQueryOutput::from_slice(bytes)?;
+
+        assert!(!output.is_succeed());
+        assert_eq!(output.get_error_code(), ErrorCode::E_SYNTAX_ERROR);
+        assert_eq!(
+            output.get_error_msg(),
+            Some("SyntaxError: syntax error near `MATC'".to_owned())
+        );
+        assert!(output.results.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,395 @@
+//! Choosing the graphd host of a new session or connection.
+//!
+//! `SingleConnSessionConf::get_connect_order` asks the `LoadBalancer` to order
+//! the hosts that aren't quarantined. A host is quarantined when it can't be
+//! reached, or after `max_query_failures` consecutive statements failed on it
+//! without a response. Quarantined hosts are tried last, and rejoin the
+//! rotation when their backoff expires.
+//! ## Example
+//! ```ignore
+//! use std::sync::Arc;
+//! use rust_nebula::graph::LeastOutstanding;
+//!
+//! conf.set_load_balancer(Arc::new(LeastOutstanding::default()));
+//! ```
+
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+use crate::HostAddress;
+
+/// A host that isn't quarantined, as seen by a `LoadBalancer`.
+#[derive(Debug, Clone)]
+pub struct HostState {
+    pub addr: HostAddress,
+    /// Sessions or pooled connections currently using the host
+    pub outstanding: usize,
+    /// Moving average of the recent outcomes on the host, from `0.0` if they
+    /// all failed to `1.0` if they all succeeded
+    pub health: f64,
+}
+
+pub trait LoadBalancer: Send + Sync + core::fmt::Debug {
+    /// Reorder `hosts`, the first one is tried first.
+    fn order(&self, hosts: &mut [HostState]);
+}
+
+//
+//
+//
+/// Rotates the hosts on every call.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+impl LoadBalancer for RoundRobin {
+    fn order(&self, hosts: &mut [HostState]) {
+        if hosts.is_empty() {
+            return;
+        }
+        let idx = self.next.fetch_add(1, Ordering::Relaxed) % hosts.len();
+        hosts.rotate_left(idx);
+    }
+}
+
+/// Shuffles the hosts on every call.
+#[derive(Debug, Default)]
+pub struct Random;
+
+impl LoadBalancer for Random {
+    fn order(&self, hosts: &mut [HostState]) {
+        for i in (1..hosts.len()).rev() {
+            hosts.swap(i, (random_u64() % (i as u64 + 1)) as usize);
+        }
+    }
+}
+
+/// Prefers the hosts with the fewest sessions, hosts with as many sessions are
+/// rotated.
+#[derive(Debug, Default)]
+pub struct LeastOutstanding {
+    round_robin: RoundRobin,
+}
+
+impl LoadBalancer for LeastOutstanding {
+    fn order(&self, hosts: &mut [HostState]) {
+        self.round_robin.order(hosts);
+        hosts.sort_by_key(|v| v.outstanding);
+    }
+}
+
+/// Picks the hosts at random, weighted by their health, so a host that keeps
+/// failing gets less and less traffic before it's quarantined.
+#[derive(Debug)]
+pub struct HealthWeighted {
+    min_weight: f64,
+}
+
+impl Default for HealthWeighted {
+    fn default() -> Self {
+        Self { min_weight: 0.01 }
+    }
+}
+
+impl HealthWeighted {
+    /// Hosts get at least `min_weight`, so they're still picked sometimes and
+    /// can recover.
+    pub fn new(min_weight: f64) -> Self {
+        Self { min_weight }
+    }
+}
+
+impl LoadBalancer for HealthWeighted {
+    fn order(&self, hosts: &mut [HostState]) {
+        // Weighted sampling without replacement, ordered by `u^(1/weight)`.
+        let mut keys = hosts
+            .iter()
+            .map(|v| random_f64().powf(1.0 / v.health.max(self.min_weight)))
+            .zip(hosts.iter().cloned())
+            .collect::<Vec<_>>();
+        keys.sort_by(|a, b| b.0.total_cmp(&a.0));
+        for (host, (_, state)) in hosts.iter_mut().zip(keys) {
+            *host = state;
+        }
+    }
+}
+
+fn random_u64() -> u64 {
+    std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish()
+}
+
+/// A random number in `(0, 1]`.
+fn random_f64() -> f64 {
+    ((random_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+}
+
+//
+//
+//
+/// When a host is quarantined and for how long.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct QuarantinePolicy {
+    pub(crate) backoff_ms: u32,
+    pub(crate) max_backoff_ms: u32,
+    pub(crate) max_query_failures: u32,
+}
+
+/// Weight of the latest outcome in `HostState::health`
+const HEALTH_DECAY: f64 = 0.2;
+
+#[derive(Debug, Clone)]
+struct HostEntry {
+    outstanding: usize,
+    health: f64,
+    /// Consecutive quarantines, the backoff doubles with each of them
+    quarantines: u32,
+    until: Option<Instant>,
+    /// Consecutive statements that failed without a response
+    query_failures: u32,
+}
+
+impl Default for HostEntry {
+    fn default() -> Self {
+        Self {
+            outstanding: 0,
+            health: 1.0,
+            quarantines: 0,
+            until: None,
+            query_failures: 0,
+        }
+    }
+}
+
+impl HostEntry {
+    fn record(&mut self, succeeded: bool) {
+        let outcome = if succeeded { 1.0 } else { 0.0 };
+        self.health += (outcome - self.health) * HEALTH_DECAY;
+    }
+}
+
+/// The state of every host, shared by the sessions opened from the same
+/// configuration.
+#[derive(Debug, Default)]
+pub(crate) struct HostRegistry {
+    hosts: Mutex<HashMap<HostAddress, HostEntry>>,
+}
+
+impl HostRegistry {
+    /// Split `addrs` into the hosts in rotation and the quarantined hosts sorted
+    /// by the end of their quarantine.
+    pub(crate) fn states(&self, addrs: &[HostAddress]) -> (Vec<HostState>, Vec<HostAddress>) {
+        let now = Instant::now();
+        let hosts = self.hosts.lock().unwrap();
+        let mut healthy = vec![];
+        let mut quarantined = vec![];
+        for addr in addrs {
+            let entry = hosts.get(addr).cloned().unwrap_or_default();
+            match entry.until {
+                Some(until) if until > now => quarantined.push((until, addr.clone())),
+                _ => healthy.push(HostState {
+                    addr: addr.clone(),
+                    outstanding: entry.outstanding,
+                    health: entry.health,
+                }),
+            }
+        }
+        quarantined.sort_by_key(|(until, _)| *until);
+        (
+            healthy,
+            quarantined.into_iter().map(|(_, addr)| addr).collect(),
+        )
+    }
+
+    pub(crate) fn quarantine(&self, addr: &HostAddress, policy: &QuarantinePolicy) {
+        let mut hosts = self.hosts.lock().unwrap();
+        let entry = hosts.entry(addr.clone()).or_default();
+        entry.record(false);
+        entry.quarantines += 1;
+        entry.query_failures = 0;
+        let backoff_ms = (policy.backoff_ms as u64)
+            .saturating_mul(1 << (entry.quarantines - 1).min(16))
+            .min(policy.max_backoff_ms as u64);
+        entry.until = Some(Instant::now() + Duration::from_millis(backoff_ms));
+    }
+
+    pub(crate) fn release(&self, addr: &HostAddress) {
+        let mut hosts = self.hosts.lock().unwrap();
+        let entry = hosts.entry(addr.clone()).or_default();
+        entry.record(true);
+        entry.quarantines = 0;
+        entry.until = None;
+    }
+
+    /// Record whether a statement got a response, and quarantine the host after
+    /// `max_query_failures` consecutive failures.
+    pub(crate) fn record_query(
+        &self,
+        addr: &HostAddress,
+        succeeded: bool,
+        policy: &QuarantinePolicy,
+    ) {
+        let mut hosts = self.hosts.lock().unwrap();
+        let entry = hosts.entry(addr.clone()).or_default();
+        if succeeded {
+            entry.record(true);
+            entry.query_failures = 0;
+            return;
+        }
+        entry.query_failures += 1;
+        if entry.query_failures < policy.max_query_failures {
+            entry.record(false);
+            return;
+        }
+        drop(hosts);
+        self.quarantine(addr, policy);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn until(&self, addr: &HostAddress) -> Option<Instant> {
+        self.hosts.lock().unwrap().get(addr).and_then(|v| v.until)
+    }
+}
+
+/// Counts a session or a connection as outstanding on its host until it's dropped.
+#[derive(Debug)]
+pub(crate) struct HostLease {
+    registry: Arc<HostRegistry>,
+    addr: HostAddress,
+    policy: QuarantinePolicy,
+}
+
+impl HostLease {
+    pub(crate) fn new(
+        registry: Arc<HostRegistry>,
+        addr: HostAddress,
+        policy: QuarantinePolicy,
+    ) -> Self {
+        registry
+            .hosts
+            .lock()
+            .unwrap()
+            .entry(addr.clone())
+            .or_default()
+            .outstanding += 1;
+        Self {
+            registry,
+            addr,
+            policy,
+        }
+    }
+
+    pub(crate) fn record_query(&self, succeeded: bool) {
+        self.registry
+            .record_query(&self.addr, succeeded, &self.policy);
+    }
+}
+
+impl Drop for HostLease {
+    fn drop(&mut self) {
+        if let Some(entry) = self.registry.hosts.lock().unwrap().get_mut(&self.addr) {
+            entry.outstanding = entry.outstanding.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn states(outstanding: &[usize]) -> Vec<HostState> {
+        outstanding
+            .iter()
+            .enumerate()
+            .map(|(i, v)| HostState {
+                addr: HostAddress::new(&format!("127.0.0.{}", i + 1), 9669),
+                outstanding: *v,
+                health: 1.0,
+            })
+            .collect()
+    }
+
+    fn names(states: &[HostState]) -> Vec<String> {
+        states.iter().map(|v| v.addr.host().to_owned()).collect()
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let balancer = RoundRobin::default();
+        let mut firsts = vec![];
+        for _ in 0..4 {
+            let mut hosts = states(&[0, 0, 0]);
+            balancer.order(&mut hosts);
+            firsts.push(hosts[0].addr.host().to_owned());
+        }
+        assert_eq!(firsts, ["127.0.0.1", "127.0.0.2", "127.0.0.3", "127.0.0.1"]);
+    }
+
+    #[test]
+    fn test_least_outstanding() {
+        let balancer = LeastOutstanding::default();
+        let mut hosts = states(&[3, 1, 2]);
+        balancer.order(&mut hosts);
+        assert_eq!(names(&hosts), ["127.0.0.2", "127.0.0.3", "127.0.0.1"]);
+    }
+
+    #[test]
+    fn test_random() {
+        let mut hosts = states(&[0, 0, 0, 0]);
+        Random.order(&mut hosts);
+        let mut sorted = names(&hosts);
+        sorted.sort();
+        assert_eq!(sorted, names(&states(&[0, 0, 0, 0])));
+    }
+
+    #[test]
+    fn test_health_weighted() {
+        let balancer = HealthWeighted::new(0.001);
+        let mut firsts = 0;
+        for _ in 0..100 {
+            let mut hosts = states(&[0, 0]);
+            hosts[1].health = 0.001;
+            balancer.order(&mut hosts);
+            if hosts[0].addr.host() == "127.0.0.1" {
+                firsts += 1;
+            }
+        }
+        assert!(firsts > 90, "{firsts}");
+    }
+
+    #[test]
+    fn test_record_query() {
+        let registry = Arc::new(HostRegistry::default());
+        let addr = HostAddress::new("127.0.0.1", 9669);
+        let policy = QuarantinePolicy {
+            backoff_ms: 1000,
+            max_backoff_ms: 1000,
+            max_query_failures: 2,
+        };
+
+        let lease = HostLease::new(registry.clone(), addr.clone(), policy);
+        lease.record_query(false);
+        lease.record_query(true);
+        lease.record_query(false);
+        let (healthy, _) = registry.states(std::slice::from_ref(&addr));
+        assert_eq!(healthy[0].outstanding, 1);
+        assert!(healthy[0].health < 1.0);
+
+        lease.record_query(false);
+        let (healthy, quarantined) = registry.states(std::slice::from_ref(&addr));
+        assert!(healthy.is_empty());
+        assert_eq!(quarantined, vec![addr.clone()]);
+
+        drop(lease);
+        registry.release(&addr);
+        let (healthy, _) = registry.states(&[addr]);
+        assert_eq!(healthy[0].outstanding, 0);
+    }
+}
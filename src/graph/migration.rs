@@ -0,0 +1,573 @@
+//! Versioned schema migrations.
+//!
+//! A migration is an nGQL script with a version. `Migrator` runs the scripts that
+//! haven't been applied to its space in the order of their versions, and records
+//! every applied one as a vertex of the `schema_migration` tag in the same space,
+//! so the space itself tells which changes it has.
+//!
+//! After a statement creates, alters or drops a tag or an edge type, the next
+//! statement waits until the change is visible, like the schema helpers of
+//! `GraphQuery`.
+//! ## Example
+//! ```ignore
+//! use rust_nebula::graph::Migrator;
+//!
+//! // V1__create_player.ngql, V2__add_player_age.ngql, ...
+//! let mut migrator = Migrator::new("basketball");
+//! migrator.load_dir("migrations")?;
+//! let applied = migrator.run(&mut session).await?;
+//! ```
+
+use std::{collections::HashMap, path::Path};
+
+use serde::Deserialize;
+
+use crate::graph::query::{GraphQuery, GraphQueryError};
+use crate::graph::schema::{self, Expected, SchemaError, SchemaWaitConf};
+use crate::graph::statement::{self, SchemaStatement};
+use crate::ngql::{identifier, model, FetchVertices, InsertVertex, PropDef, ToNgqlLiteral};
+
+/// Tag of the vertices recording the applied migrations
+pub const MIGRATION_TAG: &str = "schema_migration";
+
+const MIGRATION_PROPS: &[PropDef] = &[
+    PropDef {
+        name: "version",
+        data_type: "int64",
+        nullable: false,
+        column: "version",
+    },
+    PropDef {
+        name: "name",
+        data_type: "string",
+        nullable: false,
+        column: "name",
+    },
+    PropDef {
+        name: "checksum",
+        data_type: "string",
+        nullable: false,
+        column: "checksum",
+    },
+    PropDef {
+        name: "applied_at",
+        data_type: "datetime",
+        nullable: true,
+        column: "applied_at",
+    },
+];
+
+/// A script of nGQL statements with a version.
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub version: u64,
+    pub name: String,
+    pub script: String,
+}
+
+impl Migration {
+    pub fn new(version: u64, name: &str, script: &str) -> Self {
+        Self {
+            version,
+            name: name.to_owned(),
+            script: script.to_owned(),
+        }
+    }
+
+    /// Read a `V<version>__<name>.ngql` file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, MigrationLoadError> {
+        let path = path.as_ref();
+        let file_name = path
+            .file_name()
+            .map(|v| v.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let (version, name) = parse_file_name(&file_name)
+            .ok_or_else(|| MigrationLoadError::InvalidFileName(file_name.clone()))?;
+        let script = std::fs::read_to_string(path).map_err(MigrationLoadError::IoError)?;
+        Ok(Self {
+            version,
+            name: name.to_owned(),
+            script,
+        })
+    }
+
+    /// FNV-1a hash of the script, an applied migration must not be edited.
+    pub fn checksum(&self) -> String {
+        let hash = self.script.bytes().fold(0xcbf29ce484222325_u64, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+        format!("{hash:016x}")
+    }
+
+    fn record(&self, vid: &dyn ToNgqlLiteral) -> String {
+        let props = MIGRATION_PROPS.iter().map(|v| v.name).collect::<Vec<_>>();
+        let checksum = self.checksum();
+        InsertVertex::new(MIGRATION_TAG, &props)
+            .value(vid, &[&self.version, &self.name, &checksum, &Now])
+            .to_string()
+    }
+}
+
+/// `V<version>__<name>.ngql`
+fn parse_file_name(file_name: &str) -> Option<(u64, &str)> {
+    let stem = file_name.strip_suffix(".ngql")?;
+    let (version, name) = stem.strip_prefix('V')?.split_once("__")?;
+    Some((version.parse().ok()?, name))
+}
+
+/// `datetime()` of the server
+struct Now;
+
+impl ToNgqlLiteral for Now {
+    fn to_ngql_literal(&self) -> String {
+        "datetime()".to_owned()
+    }
+}
+
+/// A migration recorded in the space.
+#[derive(Deserialize, Debug, Clone)]
+pub struct AppliedMigration {
+    pub version: u64,
+    pub name: String,
+    pub checksum: String,
+}
+
+//
+//
+//
+/// Runs the migrations of a space.
+#[derive(Debug, Clone)]
+pub struct Migrator {
+    space: String,
+    migrations: Vec<Migration>,
+    wait: SchemaWaitConf,
+    dry_run: bool,
+}
+
+impl Migrator {
+    pub fn new(space: &str) -> Self {
+        Self {
+            space: space.to_owned(),
+            migrations: vec![],
+            wait: SchemaWaitConf::default(),
+            dry_run: false,
+        }
+    }
+
+    pub fn add_migration(&mut self, migration: Migration) {
+        self.migrations.push(migration);
+    }
+
+    /// Add every `V<version>__<name>.ngql` file of the directory, other files are
+    /// ignored.
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> Result<(), MigrationLoadError> {
+        let entries = std::fs::read_dir(dir).map_err(MigrationLoadError::IoError)?;
+        for entry in entries {
+            let path = entry.map_err(MigrationLoadError::IoError)?.path();
+            if path.is_file() && path.extension().is_some_and(|v| v == "ngql") {
+                self.migrations.push(Migration::from_file(path)?);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_wait(&mut self, wait: SchemaWaitConf) {
+        self.wait = wait;
+    }
+
+    /// Print the statements of the pending migrations instead of running them,
+    /// see `plan`.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Migrations recorded in the space, of the versions that are known.
+    /// ## Notice
+    /// The session is switched to the space.
+    pub async fn applied<Q>(
+        &self,
+        query: &mut Q,
+    ) -> Result<Vec<AppliedMigration>, MigrationError<Q::Error>>
+    where
+        Q: GraphQuery + Send + ?Sized,
+        Q::Error: Send,
+    {
+        query
+            .execute(&format!("USE {};", identifier(&self.space)))
+            .await
+            .map_err(MigrationError::QueryError)?;
+        self.fetch_applied(query)
+            .await
+            .map_err(MigrationError::QueryError)
+    }
+
+    async fn fetch_applied<Q>(&self, query: &mut Q) -> Result<Vec<AppliedMigration>, Q::Error>
+    where
+        Q: GraphQuery + Send + ?Sized,
+    {
+        let tags = query.query("SHOW TAGS;").await?;
+        let tags = tags
+            .scan::<TagName>()
+            .map_err(GraphQueryError::DataSetError)?;
+        if self.migrations.is_empty() || !tags.iter().any(|v| v.name == MIGRATION_TAG) {
+            return Ok(vec![]);
+        }
+
+        let vid_type = schema::vid_type(query, &self.space).await?;
+        let vids = self
+            .migrations
+            .iter()
+            .map(|v| migration_vid(v.version, &vid_type))
+            .collect::<Vec<_>>();
+        let vids = vids.iter().map(|v| &**v).collect::<Vec<_>>();
+        let stmt = MIGRATION_PROPS[..3].iter().fold(
+            FetchVertices::new(&[MIGRATION_TAG], &vids),
+            |stmt, v| {
+                stmt.yield_(&format!(
+                    "{}.{} AS {}",
+                    identifier(MIGRATION_TAG),
+                    identifier(v.name),
+                    identifier(v.column)
+                ))
+            },
+        );
+        let output = query.query(&stmt.to_string()).await?;
+        Ok(output
+            .scan::<AppliedMigration>()
+            .map_err(GraphQueryError::DataSetError)?)
+    }
+
+    /// Apply the pending migrations in the order of their versions, and return
+    /// their versions. In the dry-run mode the statements of `plan` are printed,
+    /// and the versions that would be applied are returned.
+    /// ## Notice
+    /// A failing migration isn't rolled back, the statements before the failing
+    /// one are kept and the migration isn't recorded.
+    pub async fn run<Q>(&self, query: &mut Q) -> Result<Vec<u64>, MigrationError<Q::Error>>
+    where
+        Q: GraphQuery + Send + ?Sized,
+        Q::Error: Send,
+    {
+        let (migrations, vid_type) = self.pending(query).await?;
+        if self.dry_run {
+            for line in self.plan_lines(&migrations, &vid_type) {
+                println!("{line}");
+            }
+            return Ok(migrations.iter().map(|v| v.version).collect());
+        }
+
+        if !migrations.is_empty() {
+            query
+                .create_tag(MIGRATION_TAG, MIGRATION_PROPS, &self.wait)
+                .await
+                .map_err(MigrationError::SchemaError)?;
+        }
+        let mut versions = vec![];
+        for migration in migrations {
+            self.apply(query, migration, &vid_type).await?;
+            versions.push(migration.version);
+        }
+        Ok(versions)
+    }
+
+    /// The statements `run` would execute, as a script that can be run as is.
+    /// The statements of every migration follow a `# V<version> <name>` comment.
+    /// ## Notice
+    /// The session is switched to the space.
+    pub async fn plan<Q>(&self, query: &mut Q) -> Result<Vec<String>, MigrationError<Q::Error>>
+    where
+        Q: GraphQuery + Send + ?Sized,
+        Q::Error: Send,
+    {
+        let (migrations, vid_type) = self.pending(query).await?;
+        Ok(self.plan_lines(&migrations, &vid_type))
+    }
+
+    /// The migrations that aren't applied yet, sorted, and the vid type of the
+    /// space.
+    async fn pending<Q>(
+        &self,
+        query: &mut Q,
+    ) -> Result<(Vec<&Migration>, String), MigrationError<Q::Error>>
+    where
+        Q: GraphQuery + Send + ?Sized,
+        Q::Error: Send,
+    {
+        let mut migrations = self.migrations.iter().collect::<Vec<_>>();
+        migrations.sort_by_key(|v| v.version);
+        if let Some(pair) = migrations.windows(2).find(|v| v[0].version == v[1].version) {
+            return Err(MigrationError::DuplicateVersion(pair[0].version));
+        }
+
+        let applied = self
+            .applied(query)
+            .await?
+            .into_iter()
+            .map(|v| (v.version, v.checksum))
+            .collect::<HashMap<_, _>>();
+        for migration in migrations.iter() {
+            match applied.get(&migration.version) {
+                Some(checksum) if checksum != &migration.checksum() => {
+                    return Err(MigrationError::ChecksumMismatch(migration.version));
+                }
+                _ => {}
+            }
+        }
+        migrations.retain(|v| !applied.contains_key(&v.version));
+
+        let vid_type = schema::vid_type(query, &self.space)
+            .await
+            .map_err(MigrationError::QueryError)?;
+        Ok((migrations, vid_type))
+    }
+
+    async fn apply<Q>(
+        &self,
+        query: &mut Q,
+        migration: &Migration,
+        vid_type: &str,
+    ) -> Result<(), MigrationError<Q::Error>>
+    where
+        Q: GraphQuery + Send + ?Sized,
+        Q::Error: Send,
+    {
+        let mut space_changed = false;
+        for stmt in statement::split_script(&migration.script) {
+            query
+                .execute(&stmt.text)
+                .await
+                .map_err(|err| MigrationError::StatementError(migration.version, stmt.line, err))?;
+            space_changed |= statement::used_space(&stmt.text).is_some();
+
+            let expected = match statement::schema_statement(&stmt.text) {
+                Some(SchemaStatement::Changed(keyword, name)) => Expected::Described(keyword, name),
+                Some(SchemaStatement::Dropped(keyword, name)) => {
+                    Expected::SchemaDropped(keyword, name)
+                }
+                None => continue,
+            };
+            schema::wait_for(query, &expected, &self.wait)
+                .await
+                .map_err(MigrationError::SchemaError)?;
+        }
+
+        if space_changed {
+            query
+                .execute(&format!("USE {};", identifier(&self.space)))
+                .await
+                .map_err(MigrationError::QueryError)?;
+        }
+        let vid = migration_vid(migration.version, vid_type);
+        query
+            .execute(&migration.record(&*vid))
+            .await
+            .map_err(MigrationError::QueryError)
+    }
+
+    fn plan_lines(&self, migrations: &[&Migration], vid_type: &str) -> Vec<String> {
+        if migrations.is_empty() {
+            return vec![];
+        }
+        let mut lines = vec![
+            format!("USE {};", identifier(&self.space)),
+            format!(
+                "{};",
+                model::create_ddl("TAG", MIGRATION_TAG, MIGRATION_PROPS)
+            ),
+        ];
+        for migration in migrations {
+            lines.push(format!("# V{} {}", migration.version, migration.name));
+            for stmt in statement::split_script(&migration.script) {
+                lines.push(format!("{};", stmt.text));
+            }
+            let vid = migration_vid(migration.version, vid_type);
+            lines.push(format!("{};", migration.record(&*vid)));
+        }
+        lines
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct TagName {
+    #[serde(rename(deserialize = "Name"))]
+    name: String,
+}
+
+/// The vid of the record of a migration, `"V<version>"` in a space of string vids
+/// and `i64::MIN + version` in a space of integer vids, to keep clear of the
+/// vids of the data.
+fn migration_vid(version: u64, vid_type: &str) -> Box<dyn ToNgqlLiteral + Send + Sync> {
+    if vid_type.to_ascii_uppercase().starts_with("INT") {
+        Box::new(i64::MIN.wrapping_add(version as i64))
+    } else {
+        Box::new(format!("V{version}"))
+    }
+}
+
+//
+//
+//
+#[derive(Debug)]
+pub enum MigrationLoadError {
+    IoError(std::io::Error),
+    InvalidFileName(String),
+}
+
+impl core::fmt::Display for MigrationLoadError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::IoError(err) => write!(f, "IoError {err}"),
+            Self::InvalidFileName(name) => {
+                write!(
+                    f,
+                    "InvalidFileName {name}, expected V<version>__<name>.ngql"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for MigrationLoadError {}
+
+#[derive(Debug)]
+pub enum MigrationError<E> {
+    QueryError(E),
+    SchemaError(SchemaError<E>),
+    /// A statement of the migration of the version failed, at the line of the
+    /// script.
+    StatementError(u64, usize, E),
+    DuplicateVersion(u64),
+    /// The applied migration of the version has been edited.
+    ChecksumMismatch(u64),
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for MigrationError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::QueryError(err) => write!(f, "QueryError {err}"),
+            Self::SchemaError(err) => write!(f, "SchemaError {err}"),
+            Self::StatementError(version, line, err) => {
+                write!(f, "StatementError V{version} at line {line} failed: {err}")
+            }
+            Self::DuplicateVersion(version) => write!(f, "DuplicateVersion V{version}"),
+            Self::ChecksumMismatch(version) => {
+                write!(
+                    f,
+                    "ChecksumMismatch V{version} was edited after it's applied"
+                )
+            }
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for MigrationError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::QueryError(err) | Self::StatementError(_, _, err) => Some(err),
+            Self::SchemaError(err) => Some(err),
+            Self::DuplicateVersion(_) | Self::ChecksumMismatch(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_file_name() {
+        assert_eq!(
+            parse_file_name("V12__add_player_age.ngql"),
+            Some((12, "add_player_age"))
+        );
+        assert_eq!(parse_file_name("V1_create.ngql"), None);
+        assert_eq!(parse_file_name("V1__create.sql"), None);
+        assert_eq!(parse_file_name("Vx__create.ngql"), None);
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir =
+            std::env::temp_dir().join(format!("rust-nebula-migration-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("V2__add_age.ngql"),
+            "ALTER TAG player ADD (age int);",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("V1__create_player.ngql"),
+            "CREATE TAG player(name string);",
+        )
+        .unwrap();
+        std::fs::write(dir.join("README.md"), "").unwrap();
+
+        let mut migrator = Migrator::new("test");
+        migrator.load_dir(&dir).unwrap();
+        let mut versions = migrator
+            .migrations
+            .iter()
+            .map(|v| (v.version, v.name.as_str()))
+            .collect::<Vec<_>>();
+        versions.sort();
+        assert_eq!(versions, vec![(1, "create_player"), (2, "add_age")]);
+
+        std::fs::write(dir.join("create.ngql"), "").unwrap();
+        assert!(matches!(
+            migrator.load_dir(&dir),
+            Err(MigrationLoadError::InvalidFileName(name)) if name == "create.ngql"
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_record() {
+        let migration = Migration::new(3, "create_player", "CREATE TAG player(name string);");
+        assert_eq!(migration.checksum(), migration.clone().checksum());
+        assert_ne!(
+            migration.checksum(),
+            Migration::new(
+                3,
+                "create_player",
+                "CREATE TAG player(name string, age int);"
+            )
+            .checksum()
+        );
+        assert_eq!(
+            migration.record(&*migration_vid(3, "FIXED_STRING(32)")),
+            format!(
+                r#"INSERT VERTEX `schema_migration`(`version`, `name`, `checksum`, `applied_at`) VALUES "V3":(3, "create_player", "{}", datetime())"#,
+                migration.checksum()
+            )
+        );
+        assert_eq!(
+            migration_vid(3, "INT64").to_ngql_literal(),
+            "-9223372036854775805"
+        );
+    }
+
+    #[test]
+    fn test_plan_lines() {
+        let migrator = Migrator::new("test");
+        let migration = Migration::new(
+            1,
+            "create_follow",
+            "CREATE EDGE follow(degree int);\nMATCH (a)-->(b) RETURN b;",
+        );
+        let lines = migrator.plan_lines(&[&migration], "INT64");
+        assert_eq!(lines[0], "USE `test`;");
+        assert_eq!(
+            lines[2..5],
+            [
+                "# V1 create_follow",
+                "CREATE EDGE follow(degree int);",
+                "MATCH (a)-->(b) RETURN b;"
+            ]
+        );
+
+        let statements = statement::split_script(&lines.join("\n"));
+        assert_eq!(statements.len(), lines.len() - 1);
+        assert_eq!(statements[3].text, "MATCH (a)-->(b) RETURN b");
+        assert!(migrator.plan_lines(&[], "INT64").is_empty());
+    }
+}
@@ -1,13 +1,42 @@
 pub mod connection;
 
 pub mod query;
-pub use query::{GraphQuery, GraphQueryError, GraphQueryOutput};
+pub use query::{GraphQuery, GraphQueryError, GraphQueryOutput, ScriptError};
+
+pub mod schema;
+pub use schema::{SchemaError, SchemaWaitConf};
+
+pub mod migration;
+pub use migration::{Migration, MigrationError, MigrationLoadError, Migrator};
+
+pub mod observer;
+pub use observer::{LogObserver, QueryEnd, QueryObserver, QueryStart, SlowQueryObserver};
+
+pub mod plan;
+pub use plan::ExecutionPlan;
+
+pub mod load_balancer;
+pub use load_balancer::{
+    HealthWeighted, HostState, LeastOutstanding, LoadBalancer, Random, RoundRobin,
+};
+
+pub mod connection_pool;
+pub use connection_pool::{ConnectionPool, ConnectionPoolConf, PooledConnection};
+
+pub mod session_pool;
+pub use session_pool::{PooledSession, SessionPool, SessionPoolManager};
+
+pub mod json_output;
+pub use json_output::JsonQueryOutput;
 
 pub mod transport_response_handler;
 pub use transport_response_handler::GraphTransportResponseHandler;
 
 pub mod single_conn_session;
+pub use single_conn_session::dsn::ConfParseError;
 pub use single_conn_session::single_conn_session_manager::{
-    SingleConnSessionConf, SingleConnSessionManager,
+    ReauthPolicy, SingleConnSessionConf, SingleConnSessionManager,
 };
 pub use single_conn_session::{SingleConnSession, SingleConnSessionError};
+
+pub(crate) mod statement;
@@ -0,0 +1,247 @@
+//! Hooks around the statements of `SingleConnSession`.
+//!
+//! Observers are registered with `SingleConnSessionConf::add_observer`, and
+//! every session opened by the manager calls them before and after each
+//! statement, including the JSON ones, the retries after a re-authentication
+//! and the health check pings. A statement that runs into the timeout of
+//! `query_with_timeout` ends with `QueryTimeout`.
+//! ## Example
+//! ```ignore
+//! use std::{sync::Arc, time::Duration};
+//! use rust_nebula::graph::{LogObserver, SlowQueryObserver};
+//!
+//! conf.add_observer(Arc::new(LogObserver::default()));
+//! conf.add_observer(Arc::new(SlowQueryObserver::new(Duration::from_millis(500))));
+//! ```
+
+use std::time::Duration;
+
+use log::Level;
+
+use crate::common::types::ErrorCode;
+use crate::graph::SingleConnSessionError;
+use crate::HostAddress;
+
+/// A statement about to be sent to graphd.
+#[derive(Debug, Clone, Copy)]
+pub struct QueryStart<'a> {
+    pub stmt: &'a str,
+    pub session_id: i64,
+    pub host: &'a HostAddress,
+}
+
+/// A statement that finished.
+#[derive(Debug, Clone, Copy)]
+pub struct QueryEnd<'a> {
+    pub stmt: &'a str,
+    /// The session after the statement, which is a new one if the session was
+    /// re-authenticated.
+    pub session_id: i64,
+    pub host: &'a HostAddress,
+    /// `None` if graphd didn't respond, e.g. the connection is broken.
+    pub error_code: Option<ErrorCode>,
+    /// Latency measured by graphd, only known if the statement succeeded.
+    pub latency_in_us: Option<i64>,
+    /// Wall time measured by the client
+    pub elapsed: Duration,
+    pub error: Option<&'a SingleConnSessionError>,
+}
+
+impl QueryEnd<'_> {
+    pub fn is_succeed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+pub trait QueryObserver: Send + Sync + core::fmt::Debug {
+    fn before_query(&self, _event: &QueryStart) {}
+
+    fn after_query(&self, _event: &QueryEnd) {}
+}
+
+/// Format the event as `key=value` pairs.
+fn format_event(event: &QueryEnd) -> String {
+    let mut record = format!(
+        "stmt={:?} session_id={} host={} elapsed_us={}",
+        event.stmt,
+        event.session_id,
+        event.host.to_string(),
+        event.elapsed.as_micros()
+    );
+    if let Some(code) = event.error_code {
+        record.push_str(&format!(" error_code={code}"));
+    }
+    if let Some(latency) = event.latency_in_us {
+        record.push_str(&format!(" latency_us={latency}"));
+    }
+    if let Some(err) = event.error {
+        record.push_str(&format!(" error={:?}", err.to_string()));
+    }
+    record
+}
+
+//
+//
+//
+/// Logs every finished statement with the `log` crate, under the
+/// `rust_nebula::query` target. Failed statements are logged at the `Warn` level.
+#[derive(Debug, Clone)]
+pub struct LogObserver {
+    level: Level,
+}
+
+impl Default for LogObserver {
+    fn default() -> Self {
+        Self { level: Level::Info }
+    }
+}
+
+impl LogObserver {
+    pub fn new(level: Level) -> Self {
+        Self { level }
+    }
+}
+
+impl QueryObserver for LogObserver {
+    fn after_query(&self, event: &QueryEnd) {
+        let level = if event.is_succeed() {
+            self.level
+        } else {
+            self.level.min(Level::Warn)
+        };
+        log::log!(target: "rust_nebula::query", level, "{}", format_event(event));
+    }
+}
+
+/// Logs the statements that take at least `threshold` of wall time at the `Warn`
+/// level, under the `rust_nebula::slow_query` target.
+#[derive(Debug, Clone)]
+pub struct SlowQueryObserver {
+    threshold: Duration,
+}
+
+impl SlowQueryObserver {
+    pub fn new(threshold: Duration) -> Self {
+        Self { threshold }
+    }
+
+    pub fn is_slow(&self, event: &QueryEnd) -> bool {
+        event.elapsed >= self.threshold
+    }
+}
+
+impl QueryObserver for SlowQueryObserver {
+    fn after_query(&self, event: &QueryEnd) {
+        if self.is_slow(event) {
+            log::warn!(
+                target: "rust_nebula::slow_query",
+                "threshold_us={} {}",
+                self.threshold.as_micros(),
+                format_event(event)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::graph::GraphQueryError;
+
+    #[test]
+    fn test_format_event() {
+        let host = HostAddress::new("127.0.0.1", 9669);
+        let err = SingleConnSessionError::GraphQueryError(GraphQueryError::ResponseError(
+            ErrorCode::E_SYNTAX_ERROR,
+            None,
+        ));
+        let event = QueryEnd {
+            stmt: "MATC (v) RETURN \"v\"",
+            session_id: 42,
+            host: &host,
+            error_code: Some(ErrorCode::E_SYNTAX_ERROR),
+            latency_in_us: None,
+            elapsed: Duration::from_millis(3),
+            error: Some(&err),
+        };
+        assert_eq!(
+            format_event(&event),
+            r#"stmt="MATC (v) RETURN \"v\"" session_id=42 host=127.0.0.1:9669 elapsed_us=3000 error_code=E_SYNTAX_ERROR error="GraphQueryError ResponseError err_code:E_SYNTAX_ERROR err_msg:None""#
+        );
+
+        let observer = SlowQueryObserver::new(Duration::from_millis(3));
+        assert!(observer.is_slow(&event));
+        assert!(!observer.is_slow(&QueryEnd {
+            elapsed: Duration::from_micros(2999),
+            ..event
+        }));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_observe_timeout_and_json() -> Result<(), Box<dyn std::error::Error>> {
+        use std::sync::{Arc, Mutex};
+
+        use crate::graph::GraphQuery as _;
+        use crate::testing::{graph, FakeGraphServer, Reply};
+        use crate::{SingleConnSessionConf, SingleConnSessionManager};
+
+        #[derive(Debug, Default)]
+        struct Events(Mutex<Vec<String>>);
+
+        impl QueryObserver for Events {
+            fn before_query(&self, event: &QueryStart) {
+                let record = format!("{} start {}", event.session_id, event.stmt);
+                self.0.lock().unwrap().push(record);
+            }
+
+            fn after_query(&self, event: &QueryEnd) {
+                let result = match event.error {
+                    Some(SingleConnSessionError::QueryTimeout(..)) => "timeout",
+                    Some(_) => "error",
+                    None => "ok",
+                };
+                let record = format!("{} end {} {result}", event.session_id, event.stmt);
+                self.0.lock().unwrap().push(record);
+            }
+        }
+
+        let server = FakeGraphServer::start().await?;
+        let events = Arc::new(Events::default());
+        let mut conf = SingleConnSessionConf::new(
+            vec![server.addr()],
+            "root".to_owned(),
+            "nebula".to_owned(),
+            None,
+        );
+        conf.add_observer(events.clone());
+        let mut session = SingleConnSessionManager::new(conf).get_session().await?;
+
+        session.query("YIELD 1;").await?;
+        server.push_execute(Reply::new(graph::response(None)).delay(Duration::from_millis(300)));
+        assert!(session
+            .query_with_timeout("YIELD 2;", Duration::from_millis(100))
+            .await
+            .is_err());
+        // The fake doesn't implement executeJson.
+        assert!(session.query_json("YIELD 3;").await.is_err());
+
+        let events = events.0.lock().unwrap().clone();
+        assert_eq!(
+            events
+                .into_iter()
+                .filter(|v| v.starts_with("1 "))
+                .collect::<Vec<_>>(),
+            [
+                "1 start YIELD 1;",
+                "1 end YIELD 1; ok",
+                "1 start YIELD 2;",
+                "1 end YIELD 2; timeout",
+                "1 start YIELD 3;",
+                "1 end YIELD 3; error",
+            ]
+        );
+        Ok(())
+    }
+}
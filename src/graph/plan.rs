@@ -0,0 +1,585 @@
+//! Execution plans of `EXPLAIN` and `PROFILE`.
+//!
+//! `ExecutionPlan` renders a plan the way nebula-console does for the `row`,
+//! `dot` and `dot:struct` formats, and as an indented tree.
+//! ## Example
+//! ```ignore
+//! let output = session.query("PROFILE FORMAT=\"row\" GO FROM \"a\" OVER follow").await?;
+//! if let Some(plan) = output.get_plan() {
+//!     println!("{}", plan.to_tree());
+//! }
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+
+use nebula_fbthrift_graph_v3::{PlanDescription, PlanNodeDescription, ProfilingStats};
+
+/// Columns of the `row` format
+const ROW_HEADER: [&str; 5] = [
+    "id",
+    "name",
+    "dependencies",
+    "profiling data",
+    "operator info",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionPlan {
+    pub nodes: Vec<PlanNode>,
+    /// Index in `nodes` of the node of an id
+    pub node_index_map: BTreeMap<i64, i64>,
+    /// `row`, `dot` or `dot:struct`
+    pub format: String,
+    pub optimize_time_in_us: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanNode {
+    pub id: i64,
+    pub name: String,
+    pub output_var: String,
+    pub description: Vec<(String, String)>,
+    /// One per execution, a node in a loop runs many times.
+    pub profiles: Vec<PlanNodeProfile>,
+    pub branch_info: Option<PlanNodeBranchInfo>,
+    pub dependencies: Vec<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanNodeProfile {
+    pub rows: i64,
+    pub exec_duration_in_us: i64,
+    pub total_duration_in_us: i64,
+    pub other_stats: Vec<(String, String)>,
+}
+
+/// The node ends the `do`/`then` branch (or the `else` branch) of a `Loop` or
+/// `Select` node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlanNodeBranchInfo {
+    pub is_do_branch: bool,
+    pub condition_node_id: i64,
+}
+
+fn utf8(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).to_string()
+}
+
+impl From<&ProfilingStats> for PlanNodeProfile {
+    fn from(stats: &ProfilingStats) -> Self {
+        Self {
+            rows: stats.rows,
+            exec_duration_in_us: stats.exec_duration_in_us,
+            total_duration_in_us: stats.total_duration_in_us,
+            other_stats: stats
+                .other_stats
+                .iter()
+                .flatten()
+                .map(|(k, v)| (utf8(k), utf8(v)))
+                .collect(),
+        }
+    }
+}
+
+impl From<&PlanNodeDescription> for PlanNode {
+    fn from(desc: &PlanNodeDescription) -> Self {
+        Self {
+            id: desc.id,
+            name: utf8(&desc.name),
+            output_var: utf8(&desc.output_var),
+            description: desc
+                .description
+                .iter()
+                .flatten()
+                .map(|v| (utf8(&v.key), utf8(&v.value)))
+                .collect(),
+            profiles: desc
+                .profiles
+                .iter()
+                .flatten()
+                .map(PlanNodeProfile::from)
+                .collect(),
+            branch_info: desc.branch_info.as_ref().map(|v| PlanNodeBranchInfo {
+                is_do_branch: v.is_do_branch,
+                condition_node_id: v.condition_node_id,
+            }),
+            dependencies: desc.dependencies.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&PlanDescription> for ExecutionPlan {
+    fn from(desc: &PlanDescription) -> Self {
+        Self {
+            nodes: desc.plan_node_descs.iter().map(PlanNode::from).collect(),
+            node_index_map: desc.node_index_map.clone(),
+            format: utf8(&desc.format),
+            optimize_time_in_us: desc.optimize_time_in_us,
+        }
+    }
+}
+
+impl PlanNode {
+    /// `<name>_<id>`
+    pub fn label(&self) -> String {
+        format!("{}_{}", self.name, self.id)
+    }
+
+    fn is_conditional(&self) -> bool {
+        matches!(self.name.to_ascii_lowercase().as_str(), "select" | "loop")
+    }
+
+    /// `ver: 0, rows: 1, execTime: 12us, totalTime: 30us` of every execution
+    pub fn profiling_data(&self) -> String {
+        let mut lines = vec![];
+        for (i, profile) in self.profiles.iter().enumerate() {
+            if !profile.other_stats.is_empty() {
+                lines.push("{".to_owned());
+            }
+            lines.push(format!(
+                "ver: {i}, rows: {}, execTime: {}us, totalTime: {}us",
+                profile.rows, profile.exec_duration_in_us, profile.total_duration_in_us
+            ));
+            for (k, v) in profile.other_stats.iter() {
+                lines.push(format!("{k}: {v}"));
+            }
+            if !profile.other_stats.is_empty() {
+                lines.push("}".to_owned());
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Branch info, output var and the description
+    pub fn operator_info(&self) -> String {
+        let mut lines = vec![];
+        if let Some(info) = self.branch_info {
+            lines.push(format!(
+                "branch: {}, nodeId: {}\n",
+                info.is_do_branch, info.condition_node_id
+            ));
+        }
+        lines.push(format!("outputVar: {}", pretty_json(&self.output_var)));
+        for (k, v) in self.description.iter() {
+            lines.push(format!("{k}: {}", pretty_json(v)));
+        }
+        lines.join("\n")
+    }
+
+    fn summary(&self) -> String {
+        let profiles = self
+            .profiles
+            .iter()
+            .map(|v| {
+                format!(
+                    "rows: {}, execTime: {}us, totalTime: {}us",
+                    v.rows, v.exec_duration_in_us, v.total_duration_in_us
+                )
+            })
+            .collect::<Vec<_>>();
+        match profiles.len() {
+            0 => self.label(),
+            1 => format!("{} ({})", self.label(), profiles[0]),
+            _ => format!("{} ({})", self.label(), profiles.join("; ")),
+        }
+    }
+}
+
+/// Pretty print a JSON value, other values are kept.
+fn pretty_json(s: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(s)
+        .ok()
+        .filter(|v| v.is_object() || v.is_array())
+        .and_then(|v| serde_json::to_string_pretty(&v).ok())
+        .unwrap_or_else(|| s.to_owned())
+}
+
+impl ExecutionPlan {
+    pub fn node(&self, id: i64) -> Option<&PlanNode> {
+        match self.node_index_map.get(&id) {
+            Some(idx) => self.nodes.get(*idx as usize),
+            None => self.nodes.iter().find(|v| v.id == id),
+        }
+    }
+
+    /// The node that no other node depends on, which yields the result.
+    pub fn root(&self) -> Option<&PlanNode> {
+        let deps = self
+            .nodes
+            .iter()
+            .flat_map(|v| v.dependencies.iter())
+            .collect::<HashSet<_>>();
+        self.nodes
+            .iter()
+            .find(|v| !deps.contains(&v.id) && v.branch_info.is_none())
+            .or_else(|| self.nodes.first())
+    }
+
+    /// Total time of the query in the plan, which is the total time of the root.
+    pub fn total_duration_in_us(&self) -> Option<i64> {
+        self.root()?
+            .profiles
+            .iter()
+            .map(|v| v.total_duration_in_us)
+            .max()
+    }
+
+    /// The rows of the `row` format, `id`, `name`, `dependencies`,
+    /// `profiling data` and `operator info`.
+    pub fn to_rows(&self) -> Vec<[String; 5]> {
+        self.nodes
+            .iter()
+            .map(|v| {
+                let deps = v
+                    .dependencies
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>();
+                [
+                    v.id.to_string(),
+                    v.name.clone(),
+                    deps.join(","),
+                    v.profiling_data(),
+                    v.operator_info(),
+                ]
+            })
+            .collect()
+    }
+
+    /// The `row` format, a table of `to_rows()`.
+    pub fn to_table(&self) -> String {
+        let rows = self.to_rows();
+        let mut widths = ROW_HEADER.map(str::len);
+        for row in rows.iter() {
+            for (width, cell) in widths.iter_mut().zip(row.iter()) {
+                let cell_width = cell.lines().map(|v| v.chars().count()).max();
+                *width = (*width).max(cell_width.unwrap_or_default());
+            }
+        }
+
+        let separator = widths
+            .iter()
+            .map(|v| "-".repeat(v + 2))
+            .collect::<Vec<_>>()
+            .join("+");
+        let separator = format!("+{separator}+\n");
+        let header = ROW_HEADER.map(str::to_owned);
+        let mut table = self.header();
+        table.push_str(&separator);
+        write_row(&mut table, &header, &widths);
+        table.push_str(&separator);
+        for row in rows.iter() {
+            write_row(&mut table, row, &widths);
+            table.push_str(&separator);
+        }
+        table
+    }
+
+    /// An indented tree from the root to the leaves, with the profiles of the
+    /// nodes.
+    pub fn to_tree(&self) -> String {
+        let mut tree = self.header();
+        if let Some(root) = self.root() {
+            tree.push_str(&root.summary());
+            tree.push('\n');
+            let mut visited = HashSet::from([root.id]);
+            self.write_children(&mut tree, root, "", &mut visited);
+        }
+        tree
+    }
+
+    fn write_children(
+        &self,
+        tree: &mut String,
+        node: &PlanNode,
+        prefix: &str,
+        visited: &mut HashSet<i64>,
+    ) {
+        let children = self.children(node);
+        for (i, (branch, child)) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            let branch = branch.map(|v| format!("[{v}] ")).unwrap_or_default();
+            let (connector, indent) = if last {
+                ("└─ ", "   ")
+            } else {
+                ("├─ ", "│  ")
+            };
+            if !visited.insert(child.id) {
+                tree.push_str(&format!(
+                    "{prefix}{connector}{branch}{} (see above)\n",
+                    child.label()
+                ));
+                continue;
+            }
+            tree.push_str(&format!("{prefix}{connector}{branch}{}\n", child.summary()));
+            self.write_children(tree, child, &format!("{prefix}{indent}"), visited);
+        }
+    }
+
+    /// The branches of a `Loop` or `Select` node and the dependencies.
+    fn children(&self, node: &PlanNode) -> Vec<(Option<&'static str>, &PlanNode)> {
+        let mut children = vec![];
+        if node.is_conditional() {
+            for is_do_branch in [true, false] {
+                if let Some(end) = self.branch_end(node.id, is_do_branch) {
+                    let branch = match (node.name.eq_ignore_ascii_case("loop"), is_do_branch) {
+                        (true, _) => "do",
+                        (false, true) => "then",
+                        (false, false) => "else",
+                    };
+                    children.push((Some(branch), end));
+                }
+            }
+        }
+        children.extend(
+            node.dependencies
+                .iter()
+                .filter_map(|v| self.node(*v))
+                .map(|v| (None, v)),
+        );
+        children
+    }
+
+    fn branch_end(&self, condition_node_id: i64, is_do_branch: bool) -> Option<&PlanNode> {
+        self.nodes.iter().find(|v| {
+            v.branch_info
+                == Some(PlanNodeBranchInfo {
+                    is_do_branch,
+                    condition_node_id,
+                })
+        })
+    }
+
+    /// The `dot` format, a Graphviz digraph with the input and output vars.
+    pub fn to_dot(&self) -> String {
+        self.make_dot(|node| {
+            let input_var = node
+                .description
+                .iter()
+                .find(|(k, _)| k == "inputVar")
+                .map(|(_, v)| graphviz_escape(v))
+                .unwrap_or_default();
+            format!(
+                "{}|outputVar: {}|inputVar: {}",
+                node.label(),
+                graphviz_escape(&node.output_var),
+                input_var
+            )
+        })
+    }
+
+    /// The `dot:struct` format, a Graphviz digraph with the whole description and
+    /// the profiles.
+    pub fn to_dot_struct(&self) -> String {
+        self.make_dot(|node| {
+            let mut fields = vec![
+                node.label(),
+                format!("outputVar: {}", graphviz_escape(&node.output_var)),
+            ];
+            fields.extend(
+                node.description
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", graphviz_escape(k), graphviz_escape(v))),
+            );
+            fields.extend(node.profiling_data().lines().map(graphviz_escape));
+            fields.join("|")
+        })
+    }
+
+    fn make_dot(&self, label: impl Fn(&PlanNode) -> String) -> String {
+        let mut dot = "digraph exec_plan {\n\trankdir=BT;\n".to_owned();
+        for node in self.nodes.iter() {
+            let name = node.label();
+            if node.is_conditional() {
+                dot.push_str(&format!("\t\"{name}\"[shape=diamond];\n"));
+                let Some(dep) = node.dependencies.first().and_then(|v| self.node(*v)) else {
+                    continue;
+                };
+                for is_do_branch in [true, false] {
+                    if let Some(end) = self.branch_end(node.id, is_do_branch) {
+                        dot.push_str(&dot_edge(&end.label(), &dep.label()));
+                    }
+                }
+                dot.push_str(&dot_edge(&dep.label(), &name));
+            } else {
+                dot.push_str(&format!(
+                    "\t\"{name}\"[label=\"{{{}}}\", shape=Mrecord];\n",
+                    label(node)
+                ));
+                for dep in node.dependencies.iter().filter_map(|v| self.node(*v)) {
+                    dot.push_str(&dot_edge(&dep.label(), &name));
+                }
+            }
+        }
+        dot.push('}');
+        dot
+    }
+
+    /// Render in the format the plan was requested in, the `row` format by
+    /// default.
+    pub fn render(&self) -> String {
+        match self.format.as_str() {
+            "dot" => self.to_dot(),
+            "dot:struct" => self.to_dot_struct(),
+            _ => self.to_table(),
+        }
+    }
+
+    fn header(&self) -> String {
+        let mut header = format!(
+            "Execution Plan (optimize time {} us",
+            self.optimize_time_in_us
+        );
+        if let Some(total) = self.total_duration_in_us() {
+            header.push_str(&format!(", total time {total} us"));
+        }
+        header.push_str(")\n");
+        header
+    }
+}
+
+impl core::fmt::Display for ExecutionPlan {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.render())
+    }
+}
+
+/// Write a row of multi-line cells.
+fn write_row(table: &mut String, row: &[String; 5], widths: &[usize; 5]) {
+    let lines = row.iter().map(|v| v.lines().count()).max().unwrap_or(1);
+    for i in 0..lines.max(1) {
+        table.push('|');
+        for (cell, width) in row.iter().zip(widths.iter()) {
+            let line = cell.lines().nth(i).unwrap_or_default();
+            let pad = width - line.chars().count();
+            table.push_str(&format!(" {line}{} |", " ".repeat(pad)));
+        }
+        table.push('\n');
+    }
+}
+
+fn dot_edge(start: &str, end: &str) -> String {
+    format!("\t\"{start}\"->\"{end}\";\n")
+}
+
+fn graphviz_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '{' | '}' | '"' | '[' | ']' | '|' | '<' | '>' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: i64, name: &str, deps: &[i64]) -> PlanNode {
+        PlanNode {
+            id,
+            name: name.to_owned(),
+            output_var: format!("__{name}_{id}"),
+            description: vec![],
+            profiles: vec![],
+            branch_info: None,
+            dependencies: deps.to_vec(),
+        }
+    }
+
+    fn plan() -> ExecutionPlan {
+        let mut project = node(3, "Project", &[2]);
+        project.description = vec![("columns".to_owned(), r#"["$-.name AS name"]"#.to_owned())];
+        project.profiles = vec![PlanNodeProfile {
+            rows: 1,
+            exec_duration_in_us: 12,
+            total_duration_in_us: 30,
+            other_stats: vec![],
+        }];
+        let mut loop_body = node(1, "GetNeighbors", &[0]);
+        loop_body.branch_info = Some(PlanNodeBranchInfo {
+            is_do_branch: true,
+            condition_node_id: 2,
+        });
+        ExecutionPlan {
+            nodes: vec![
+                project,
+                node(2, "Loop", &[0]),
+                loop_body,
+                node(0, "Start", &[]),
+            ],
+            node_index_map: BTreeMap::from([(3, 0), (2, 1), (1, 2), (0, 3)]),
+            format: "row".to_owned(),
+            optimize_time_in_us: 41,
+        }
+    }
+
+    #[test]
+    fn test_to_table() {
+        let plan = plan();
+        assert_eq!(plan.root().map(|v| v.id), Some(3));
+        assert_eq!(plan.total_duration_in_us(), Some(30));
+        assert_eq!(
+            plan.to_table(),
+            r#"Execution Plan (optimize time 41 us, total time 30 us)
++----+--------------+--------------+--------------------------------------------------+-----------------------------+
+| id | name         | dependencies | profiling data                                   | operator info               |
++----+--------------+--------------+--------------------------------------------------+-----------------------------+
+| 3  | Project      | 2            | ver: 0, rows: 1, execTime: 12us, totalTime: 30us | outputVar: __Project_3      |
+|    |              |              |                                                  | columns: [                  |
+|    |              |              |                                                  |   "$-.name AS name"         |
+|    |              |              |                                                  | ]                           |
++----+--------------+--------------+--------------------------------------------------+-----------------------------+
+| 2  | Loop         | 0            |                                                  | outputVar: __Loop_2         |
++----+--------------+--------------+--------------------------------------------------+-----------------------------+
+| 1  | GetNeighbors | 0            |                                                  | branch: true, nodeId: 2     |
+|    |              |              |                                                  |                             |
+|    |              |              |                                                  | outputVar: __GetNeighbors_1 |
++----+--------------+--------------+--------------------------------------------------+-----------------------------+
+| 0  | Start        |              |                                                  | outputVar: __Start_0        |
++----+--------------+--------------+--------------------------------------------------+-----------------------------+
+"#
+        );
+    }
+
+    #[test]
+    fn test_to_tree() {
+        assert_eq!(
+            plan().to_tree(),
+            "Execution Plan (optimize time 41 us, total time 30 us)
+Project_3 (rows: 1, execTime: 12us, totalTime: 30us)
+└─ Loop_2
+   ├─ [do] GetNeighbors_1
+   │  └─ Start_0
+   └─ Start_0 (see above)
+"
+        );
+    }
+
+    #[test]
+    fn test_to_dot() {
+        assert_eq!(
+            plan().to_dot(),
+            "digraph exec_plan {
+\trankdir=BT;
+\t\"Project_3\"[label=\"{Project_3|outputVar: __Project_3|inputVar: }\", shape=Mrecord];
+\t\"Loop_2\"->\"Project_3\";
+\t\"Loop_2\"[shape=diamond];
+\t\"GetNeighbors_1\"->\"Start_0\";
+\t\"Start_0\"->\"Loop_2\";
+\t\"GetNeighbors_1\"[label=\"{GetNeighbors_1|outputVar: __GetNeighbors_1|inputVar: }\", shape=Mrecord];
+\t\"Start_0\"->\"GetNeighbors_1\";
+\t\"Start_0\"[label=\"{Start_0|outputVar: __Start_0|inputVar: }\", shape=Mrecord];
+}"
+        );
+        assert!(plan().to_dot_struct().contains(
+            "\"Project_3\"[label=\"{Project_3|outputVar: __Project_3|columns: \\[\\\"$-.name AS name\\\"\\]|ver: 0, rows: 1, execTime: 12us, totalTime: 30us}\", shape=Mrecord];"
+        ));
+    }
+}
@@ -1,12 +1,18 @@
+use std::{collections::HashMap, time::Duration};
+
 use async_trait::async_trait;
 use nebula_fbthrift_graph_v3::{
     errors::graph_service::ExecuteError, types::ExecutionResponse, PlanDescription,
 };
 use serde::{de::DeserializeOwned, Deserialize};
 
-use crate::common::types::{ErrorCode, Row};
+use crate::common::types::{ErrorCode, Row, Value};
 use crate::dataset_wrapper::{DataSetError, DataSetWrapper, Record};
 use crate::dataset_wrapper_proxy;
+use crate::graph::plan::ExecutionPlan;
+use crate::graph::schema::{self, Expected, SchemaError, SchemaWaitConf};
+use crate::graph::statement;
+use crate::ngql::{identifier, AlterSchema, CreateSpace, PropDef};
 use crate::{value_wrapper::ValueWrapper, TimezoneInfo};
 
 #[async_trait]
@@ -26,6 +32,193 @@ pub trait GraphQuery {
         Ok(())
     }
 
+    /// Execute stmt with `$param` bindings and return the query output.
+    /// ## Example
+    /// `query_with_params("MATCH (v:player{name: $name}) RETURN v;", &params)`
+    /// with `params` mapping `b"name"` to `Value::sVal(b"Tim Duncan".to_vec())`.
+    async fn query_with_params(
+        &mut self,
+        stmt: &str,
+        params: &HashMap<Vec<u8>, Value>,
+    ) -> Result<GraphQueryOutput, Self::Error>;
+
+    /// Execute stmt with `$param` bindings and doesn't return the execution output.
+    async fn execute_with_params(
+        &mut self,
+        stmt: &str,
+        params: &HashMap<Vec<u8>, Value>,
+    ) -> Result<(), Self::Error> {
+        let _ = self.query_with_params(stmt, params).await?;
+        Ok(())
+    }
+
+    /// Execute stmt like `query(stmt)`, but give up once `timeout` elapses.
+    /// ## Notice
+    /// The connection can't be reused after a timeout, and the implementation should
+    /// make sure the server stops working on the statement.
+    async fn query_with_timeout(
+        &mut self,
+        stmt: &str,
+        timeout: Duration,
+    ) -> Result<GraphQueryOutput, Self::Error>;
+
+    /// Execute stmt with a deadline and doesn't return the execution output.
+    async fn execute_with_timeout(
+        &mut self,
+        stmt: &str,
+        timeout: Duration,
+    ) -> Result<(), Self::Error> {
+        let _ = self.query_with_timeout(stmt, timeout).await?;
+        Ok(())
+    }
+
+    /// Execute a script like the content of a `.ngql` file statement by statement,
+    /// and return the output of every statement.
+    ///
+    /// Statements are separated by `;`. Semicolons in quotes are kept, and `#`,
+    /// `//` and `/* ... */` comments are removed.
+    /// `USE space` affects the statements after it as usual, and the space a
+    /// statement runs in is reported if it fails.
+    /// ## Notice
+    /// The statements before the failing one aren't rolled back, their outputs
+    /// are kept in the error.
+    async fn execute_script(
+        &mut self,
+        script: &str,
+    ) -> Result<Vec<GraphQueryOutput>, ScriptError<Self::Error>> {
+        let mut outputs = vec![];
+        let mut space = None;
+        for (index, stmt) in statement::split_script(script).into_iter().enumerate() {
+            match self.query(&stmt.text).await {
+                Ok(output) => outputs.push(output),
+                Err(error) => {
+                    return Err(ScriptError {
+                        index,
+                        line: stmt.line,
+                        statement: stmt.text,
+                        space,
+                        error,
+                        outputs,
+                    })
+                }
+            }
+            if let Some(used_space) = statement::used_space(&stmt.text) {
+                space = Some(used_space.to_owned());
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// `CREATE SPACE IF NOT EXISTS`, and wait until the space can be used.
+    /// ## Notice
+    /// The space is checked with `USE`, as only `USE` fails until graphd knows
+    /// the space. The session switches back to its space afterwards, but a
+    /// session that had no space is left in the new space.
+    async fn create_space(
+        &mut self,
+        stmt: &CreateSpace,
+        wait: &SchemaWaitConf,
+    ) -> Result<(), SchemaError<Self::Error>>
+    where
+        Self::Error: Send,
+    {
+        self.execute(&stmt.to_string())
+            .await
+            .map_err(SchemaError::QueryError)?;
+        schema::wait_for(self, &Expected::Space(stmt.name()), wait).await
+    }
+
+    /// `DROP SPACE IF EXISTS`, and wait until it's gone from `SHOW SPACES`.
+    async fn drop_space(
+        &mut self,
+        name: &str,
+        wait: &SchemaWaitConf,
+    ) -> Result<(), SchemaError<Self::Error>>
+    where
+        Self::Error: Send,
+    {
+        self.execute(&format!("DROP SPACE IF EXISTS {};", identifier(name)))
+            .await
+            .map_err(SchemaError::QueryError)?;
+        schema::wait_for(self, &Expected::SpaceDropped(name), wait).await
+    }
+
+    /// `CREATE TAG IF NOT EXISTS` in the current space, and wait until vertices
+    /// of the tag can be written.
+    /// ## Example
+    /// `create_tag(Player::NAME, Player::PROPS, &wait)` with a `NebulaTag`.
+    async fn create_tag(
+        &mut self,
+        name: &str,
+        props: &[PropDef],
+        wait: &SchemaWaitConf,
+    ) -> Result<(), SchemaError<Self::Error>>
+    where
+        Self::Error: Send,
+    {
+        schema::create_schema(self, "TAG", name, props, wait).await
+    }
+
+    /// `CREATE EDGE IF NOT EXISTS` in the current space, and wait until edges
+    /// of the type can be written.
+    async fn create_edge(
+        &mut self,
+        name: &str,
+        props: &[PropDef],
+        wait: &SchemaWaitConf,
+    ) -> Result<(), SchemaError<Self::Error>>
+    where
+        Self::Error: Send,
+    {
+        schema::create_schema(self, "EDGE", name, props, wait).await
+    }
+
+    /// `ALTER TAG` or `ALTER EDGE`, and wait until the added and changed
+    /// properties can be written and the dropped ones are gone.
+    async fn alter_schema(
+        &mut self,
+        stmt: &AlterSchema,
+        wait: &SchemaWaitConf,
+    ) -> Result<(), SchemaError<Self::Error>>
+    where
+        Self::Error: Send,
+    {
+        self.execute(&stmt.to_string())
+            .await
+            .map_err(SchemaError::QueryError)?;
+        let expected = Expected::Schema {
+            keyword: stmt.keyword(),
+            name: stmt.name(),
+            props: stmt.props().map(|v| (v.name, v.data_type)).collect(),
+            dropped: stmt.dropped().iter().map(String::as_str).collect(),
+        };
+        schema::wait_for(self, &expected, wait).await
+    }
+
+    /// `DROP TAG IF EXISTS`, and wait until it's gone from `SHOW TAGS`.
+    async fn drop_tag(
+        &mut self,
+        name: &str,
+        wait: &SchemaWaitConf,
+    ) -> Result<(), SchemaError<Self::Error>>
+    where
+        Self::Error: Send,
+    {
+        schema::drop_schema(self, "TAG", name, wait).await
+    }
+
+    /// `DROP EDGE IF EXISTS`, and wait until it's gone from `SHOW EDGES`.
+    async fn drop_edge(
+        &mut self,
+        name: &str,
+        wait: &SchemaWaitConf,
+    ) -> Result<(), SchemaError<Self::Error>>
+    where
+        Self::Error: Send,
+    {
+        schema::drop_schema(self, "EDGE", name, wait).await
+    }
+
     async fn show_hosts(&mut self) -> Result<Vec<Host>, Self::Error> {
         let tmp = self.query(STMT_SHOW_HOSTS).await?;
         tmp.scan::<Host>()
@@ -90,6 +283,11 @@ impl GraphQueryOutput {
         &self.resp.plan_desc
     }
 
+    /// The plan of `EXPLAIN` or `PROFILE`, in a readable form.
+    pub fn get_plan(&self) -> Option<ExecutionPlan> {
+        self.resp.plan_desc.as_ref().map(ExecutionPlan::from)
+    }
+
     pub fn is_set_comment(&self) -> bool {
         self.resp.comment.is_some()
     }
@@ -121,6 +319,7 @@ pub enum GraphQueryError {
     ExecuteError(ExecuteError),
     ResponseError(ErrorCode, Option<Vec<u8>>),
     DataSetError(DataSetError),
+    JsonDecodeError(serde_json::Error),
 }
 
 impl core::fmt::Display for GraphQueryError {
@@ -131,12 +330,44 @@ impl core::fmt::Display for GraphQueryError {
                 write!(f, "ResponseError err_code:{err_code} err_msg:{err_msg:?}",)
             }
             Self::DataSetError(err) => write!(f, "DataSetError {err}"),
+            Self::JsonDecodeError(err) => write!(f, "JsonDecodeError {err}"),
         }
     }
 }
 
 impl std::error::Error for GraphQueryError {}
 
+/// A statement of a script passed to `GraphQuery::execute_script` failed.
+#[derive(Debug)]
+pub struct ScriptError<E> {
+    /// 0-based index of the failing statement in the script
+    pub index: usize,
+    /// 1-based line of the script the failing statement starts on
+    pub line: usize,
+    pub statement: String,
+    /// The space of the last successful `USE` in the script, if any
+    pub space: Option<String>,
+    pub error: E,
+    /// Outputs of the statements that ran before the failing one
+    pub outputs: Vec<GraphQueryOutput>,
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for ScriptError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "ScriptError statement #{} at line {} failed: {}",
+            self.index, self.line, self.error
+        )
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ScriptError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
 //
 //
 //
@@ -167,6 +398,17 @@ pub struct Space {
     pub name: String,
 }
 
+pub(crate) const STMT_SHOW_ALL_QUERIES: &str = "SHOW ALL QUERIES;";
+#[derive(Deserialize, Debug)]
+pub struct RunningQuery {
+    #[serde(rename(deserialize = "SessionID"))]
+    pub session_id: i64,
+    #[serde(rename(deserialize = "ExecutionPlanID"))]
+    pub plan_id: i64,
+    #[serde(rename(deserialize = "Status"))]
+    pub status: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -187,4 +429,20 @@ mod tests {
         );
         println!("{err}");
     }
+
+    #[test]
+    fn script_error_display() {
+        let err = ScriptError {
+            index: 2,
+            line: 7,
+            statement: "MATC (v) RETURN v".to_owned(),
+            space: Some("test".to_owned()),
+            error: GraphQueryError::ResponseError(ErrorCode::E_SYNTAX_ERROR, None),
+            outputs: vec![],
+        };
+        assert_eq!(
+            err.to_string(),
+            "ScriptError statement #2 at line 7 failed: ResponseError err_code:E_SYNTAX_ERROR err_msg:None"
+        );
+    }
 }
@@ -0,0 +1,421 @@
+//! Waiting for schema changes to be visible.
+//!
+//! metad accepts a DDL statement at once, but graphd and storaged learn the new
+//! schema on their next heartbeat, so a write right after `CREATE TAG` may fail.
+//! After a change is visible in `DESCRIBE`, it's probed with a `FETCH PROP` of
+//! a nonexistent vertex or edge, which fails until both graphd and storaged
+//! know the schema.
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::time::{sleep, Instant};
+
+use crate::graph::query::{GraphQuery, GraphQueryError};
+use crate::ngql::{identifier, model, PropDef};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the schema helpers of `GraphQuery` wait for a change.
+#[derive(Debug, Clone)]
+pub struct SchemaWaitConf {
+    pub timeout: Duration,
+    /// Interval between the checks
+    pub interval: Duration,
+}
+
+impl Default for SchemaWaitConf {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+}
+
+impl SchemaWaitConf {
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+}
+
+//
+//
+//
+#[derive(Debug)]
+pub enum SchemaError<E> {
+    /// The DDL statement or a check failed.
+    QueryError(E),
+    /// The change isn't visible after the timeout, with the error of the last
+    /// check, if any.
+    WaitTimeout(String, Duration, Option<E>),
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for SchemaError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::QueryError(err) => write!(f, "QueryError {err}"),
+            Self::WaitTimeout(name, timeout, None) => {
+                write!(f, "WaitTimeout {name} isn't visible after {timeout:?}")
+            }
+            Self::WaitTimeout(name, timeout, Some(err)) => write!(
+                f,
+                "WaitTimeout {name} isn't visible after {timeout:?}, last error: {err}"
+            ),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for SchemaError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::QueryError(err) | Self::WaitTimeout(_, _, Some(err)) => Some(err),
+            Self::WaitTimeout(_, _, None) => None,
+        }
+    }
+}
+
+//
+//
+//
+/// The state a schema change waits for.
+pub(crate) enum Expected<'a> {
+    Space(&'a str),
+    SpaceDropped(&'a str),
+    /// A tag or an edge type, with properties `(name, data_type)` and properties
+    /// that must be gone.
+    Schema {
+        keyword: &'static str,
+        name: &'a str,
+        props: Vec<(&'a str, &'a str)>,
+        dropped: Vec<&'a str>,
+    },
+    /// A tag or an edge type, with all properties it's described with
+    Described(&'static str, &'a str),
+    SchemaDropped(&'static str, &'a str),
+}
+
+impl Expected<'_> {
+    fn name(&self) -> String {
+        match self {
+            Self::Space(name) | Self::SpaceDropped(name) => format!("SPACE {name}"),
+            Self::Schema { keyword, name, .. }
+            | Self::Described(keyword, name)
+            | Self::SchemaDropped(keyword, name) => {
+                format!("{keyword} {name}")
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Named {
+    #[serde(rename(deserialize = "Name"))]
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SchemaField {
+    #[serde(rename(deserialize = "Field"))]
+    field: String,
+    #[serde(rename(deserialize = "Type"))]
+    data_type: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SpaceDesc {
+    #[serde(rename(deserialize = "Vid Type"))]
+    vid_type: String,
+}
+
+pub(crate) async fn create_schema<Q>(
+    query: &mut Q,
+    keyword: &'static str,
+    name: &str,
+    props: &[PropDef],
+    wait: &SchemaWaitConf,
+) -> Result<(), SchemaError<Q::Error>>
+where
+    Q: GraphQuery + Send + ?Sized,
+    Q::Error: Send,
+{
+    query
+        .execute(&model::create_ddl(keyword, name, props))
+        .await
+        .map_err(SchemaError::QueryError)?;
+    let expected = Expected::Schema {
+        keyword,
+        name,
+        props: props.iter().map(|v| (v.name, v.data_type)).collect(),
+        dropped: vec![],
+    };
+    wait_for(query, &expected, wait).await
+}
+
+pub(crate) async fn drop_schema<Q>(
+    query: &mut Q,
+    keyword: &'static str,
+    name: &str,
+    wait: &SchemaWaitConf,
+) -> Result<(), SchemaError<Q::Error>>
+where
+    Q: GraphQuery + Send + ?Sized,
+    Q::Error: Send,
+{
+    query
+        .execute(&format!("DROP {keyword} IF EXISTS {};", identifier(name)))
+        .await
+        .map_err(SchemaError::QueryError)?;
+    wait_for(query, &Expected::SchemaDropped(keyword, name), wait).await
+}
+
+/// Check `expected` until it holds or `conf.timeout` elapses.
+pub(crate) async fn wait_for<Q>(
+    query: &mut Q,
+    expected: &Expected<'_>,
+    conf: &SchemaWaitConf,
+) -> Result<(), SchemaError<Q::Error>>
+where
+    Q: GraphQuery + Send + ?Sized,
+    Q::Error: Send,
+{
+    let deadline = Instant::now() + conf.timeout;
+    loop {
+        let last_error = match is_visible(query, expected).await {
+            Ok(true) => return Ok(()),
+            Ok(false) => None,
+            Err(err) => Some(err),
+        };
+        if Instant::now() + conf.interval > deadline {
+            return Err(SchemaError::WaitTimeout(
+                expected.name(),
+                conf.timeout,
+                last_error,
+            ));
+        }
+        sleep(conf.interval).await;
+    }
+}
+
+async fn is_visible<Q>(query: &mut Q, expected: &Expected<'_>) -> Result<bool, Q::Error>
+where
+    Q: GraphQuery + Send + ?Sized,
+{
+    match expected {
+        Expected::Space(name) => {
+            let output = query.query("SHOW SPACES;").await?;
+            if !scan::<Q, Named>(&output)?.iter().any(|v| &v.name == name) {
+                return Ok(false);
+            }
+            // `USE` fails until graphd knows the space, then switch back. A
+            // session can't leave a space, so one without a space stays in it.
+            let current = output.get_space_name().filter(|v| !v.is_empty());
+            query.execute(&format!("USE {};", identifier(name))).await?;
+            if let Some(current) = current.filter(|v| v != name) {
+                query
+                    .execute(&format!("USE {};", identifier(&current)))
+                    .await?;
+            }
+            Ok(true)
+        }
+        Expected::SpaceDropped(name) => {
+            let output = query.query("SHOW SPACES;").await?;
+            Ok(!scan::<Q, Named>(&output)?.iter().any(|v| &v.name == name))
+        }
+        Expected::Schema {
+            keyword,
+            name,
+            props,
+            dropped,
+        } => {
+            let output = query
+                .query(&format!("DESCRIBE {keyword} {};", identifier(name)))
+                .await?;
+            let fields = scan::<Q, SchemaField>(&output)?;
+            let described = props.iter().all(|(prop, data_type)| {
+                fields
+                    .iter()
+                    .any(|v| &v.field == prop && same_type(&v.data_type, data_type))
+            }) && !fields.iter().any(|v| dropped.contains(&v.field.as_str()));
+            if !described {
+                return Ok(false);
+            }
+
+            let space = output.get_space_name().unwrap_or_default();
+            let vid_type = vid_type(query, &space).await?;
+            let props = props.iter().map(|(prop, _)| *prop).collect::<Vec<_>>();
+            query
+                .execute(&probe_stmt(keyword, name, &props, &vid_type))
+                .await?;
+            Ok(true)
+        }
+        Expected::Described(keyword, name) => {
+            let output = query
+                .query(&format!("DESCRIBE {keyword} {};", identifier(name)))
+                .await?;
+            let fields = scan::<Q, SchemaField>(&output)?;
+            let space = output.get_space_name().unwrap_or_default();
+            let vid_type = vid_type(query, &space).await?;
+            let props = fields.iter().map(|v| v.field.as_str()).collect::<Vec<_>>();
+            query
+                .execute(&probe_stmt(keyword, name, &props, &vid_type))
+                .await?;
+            Ok(true)
+        }
+        Expected::SchemaDropped(keyword, name) => {
+            let output = query.query(&format!("SHOW {keyword}S;")).await?;
+            Ok(!scan::<Q, Named>(&output)?.iter().any(|v| &v.name == name))
+        }
+    }
+}
+
+/// `Vid Type` of the space, e.g. `INT64` or `FIXED_STRING(32)`.
+pub(crate) async fn vid_type<Q>(query: &mut Q, space: &str) -> Result<String, Q::Error>
+where
+    Q: GraphQuery + Send + ?Sized,
+{
+    let output = query
+        .query(&format!("DESCRIBE SPACE {};", identifier(space)))
+        .await?;
+    Ok(scan::<Q, SpaceDesc>(&output)?
+        .pop()
+        .map(|v| v.vid_type)
+        .unwrap_or_default())
+}
+
+fn scan<Q, D>(output: &crate::graph::GraphQueryOutput) -> Result<Vec<D>, Q::Error>
+where
+    Q: GraphQuery + ?Sized,
+    D: serde::de::DeserializeOwned,
+{
+    output
+        .scan::<D>()
+        .map_err(|e| GraphQueryError::DataSetError(e).into())
+}
+
+/// Compare data types the way `DESCRIBE` prints them, e.g. `int` is `int64`.
+fn same_type(described: &str, expected: &str) -> bool {
+    let normalize = |v: &str| {
+        let v = v.replace(' ', "").to_ascii_lowercase();
+        if v == "int" {
+            "int64".to_owned()
+        } else {
+            v
+        }
+    };
+    normalize(described) == normalize(expected)
+}
+
+/// `FETCH PROP` of a vertex or an edge that doesn't exist, it fails unless
+/// graphd and storaged know the schema and the properties.
+fn probe_stmt(keyword: &str, name: &str, props: &[&str], vid_type: &str) -> String {
+    let vid = if vid_type.to_ascii_uppercase().starts_with("INT") {
+        "0"
+    } else {
+        r#""""#
+    };
+    let (key, mut columns) = if keyword == "TAG" {
+        (vid.to_owned(), vec!["id(vertex)".to_owned()])
+    } else {
+        (format!("{vid}->{vid}"), vec!["src(edge)".to_owned()])
+    };
+    columns.extend(
+        props
+            .iter()
+            .map(|v| format!("{}.{}", identifier(name), identifier(v))),
+    );
+    format!(
+        "FETCH PROP ON {} {key} YIELD {};",
+        identifier(name),
+        columns.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::common::types::ErrorCode;
+
+    #[test]
+    fn test_probe_stmt() {
+        assert_eq!(
+            probe_stmt("TAG", "player", &["name", "age"], "FIXED_STRING(32)"),
+            r#"FETCH PROP ON `player` "" YIELD id(vertex), `player`.`name`, `player`.`age`;"#
+        );
+        assert_eq!(
+            probe_stmt("EDGE", "follow", &[], "INT64"),
+            "FETCH PROP ON `follow` 0->0 YIELD src(edge);"
+        );
+    }
+
+    #[test]
+    fn test_same_type() {
+        assert!(same_type("int64", "INT"));
+        assert!(same_type("fixed_string(32)", "FIXED_STRING( 32 )"));
+        assert!(!same_type("int32", "int64"));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_create_space_switches_back() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::common::types::Value;
+        use crate::ngql::CreateSpace;
+        use crate::testing::{graph, FakeGraphServer, Reply};
+        use crate::{SingleConnSessionConf, SingleConnSessionManager};
+
+        let server = FakeGraphServer::start().await?;
+        server.on_statement(
+            "SHOW SPACES;",
+            Reply::new(graph::response(Some(graph::data_set(
+                &["Name"],
+                vec![
+                    vec![Value::sVal(b"test".to_vec())],
+                    vec![Value::sVal(b"other".to_vec())],
+                ],
+            )))),
+        );
+        let session = |space: Option<&str>| {
+            let conf = SingleConnSessionConf::new(
+                vec![server.addr()],
+                "root".to_owned(),
+                "nebula".to_owned(),
+                space.map(ToOwned::to_owned),
+            );
+            async move { SingleConnSessionManager::new(conf).get_session().await }
+        };
+        let stmt = CreateSpace::new("test", "INT64");
+        let wait = SchemaWaitConf::default();
+
+        let mut session_in_other = session(Some("other")).await?;
+        session_in_other.create_space(&stmt, &wait).await?;
+        let output = session_in_other.query("YIELD 1;").await?;
+        assert_eq!(output.get_space_name(), Some("other".to_owned()));
+
+        let mut session_without_space = session(None).await?;
+        session_without_space.create_space(&stmt, &wait).await?;
+        let output = session_without_space.query("YIELD 1;").await?;
+        assert_eq!(output.get_space_name(), Some("test".to_owned()));
+        Ok(())
+    }
+
+    #[test]
+    fn schema_error_display() {
+        let err = SchemaError::WaitTimeout(
+            "TAG player".to_owned(),
+            Duration::from_secs(60),
+            Some(GraphQueryError::ResponseError(
+                ErrorCode::E_SEMANTIC_ERROR,
+                None,
+            )),
+        );
+        assert_eq!(
+            err.to_string(),
+            "WaitTimeout TAG player isn't visible after 60s, last error: ResponseError err_code:E_SEMANTIC_ERROR err_msg:None"
+        );
+    }
+}
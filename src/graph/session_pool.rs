@@ -0,0 +1,262 @@
+//! Authenticated sessions sharing the connections of a `ConnectionPool`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use nebula_fbthrift_graph_v3::dependencies::common::types::{ErrorCode, Value};
+
+use crate::graph::{
+    connection_pool::ConnectionPool,
+    query::{GraphQuery, GraphQueryError, GraphQueryOutput},
+    single_conn_session::{single_conn_session_manager::kill_queries, STMT_PING},
+    statement, SingleConnSessionError,
+};
+use crate::ngql::identifier;
+
+/// A pool of sessions, see `SessionPoolManager`.
+pub type SessionPool = bb8::Pool<SessionPoolManager>;
+
+/// Opens the sessions of a `SessionPool`.
+///
+/// A session only holds its session id, and borrows a connection from the
+/// `ConnectionPool` for every statement, so the number of sessions isn't
+/// bounded by the number of sockets. The credentials, the default space and
+/// the health check settings are taken from `ConnectionPool::config`.
+#[derive(Clone)]
+pub struct SessionPoolManager {
+    connections: Arc<ConnectionPool>,
+}
+
+impl SessionPoolManager {
+    pub fn new(connections: Arc<ConnectionPool>) -> Self {
+        Self { connections }
+    }
+
+    pub fn connections(&self) -> &Arc<ConnectionPool> {
+        &self.connections
+    }
+
+    /// Authenticate a new session and switch to the configured space.
+    pub async fn get_session(&self) -> Result<PooledSession, SingleConnSessionError> {
+        let config = self.connections.config();
+        let mut conn = self.connections.get().await?;
+        let session_id = conn
+            .authenticate(&config.username, &config.password)
+            .await?;
+
+        if let Some(space) = &config.space {
+            conn.execute(session_id, &format!("USE {};", identifier(space)), None)
+                .await?;
+        }
+        Ok(PooledSession::new(self.connections.clone(), session_id))
+    }
+
+    /// Kill the running statements of the session `session_id` from a separate session.
+    pub async fn kill_session_queries(
+        &self,
+        session_id: i64,
+    ) -> Result<(), SingleConnSessionError> {
+        let mut session = self.get_session().await?;
+        let res = kill_queries(&mut session, session_id).await;
+        let _ = session.signout().await;
+        res
+    }
+
+    /// Ping the session if it has been idle long enough, and check that it's
+    /// still in the configured space.
+    pub async fn check_session(
+        &self,
+        session: &mut PooledSession,
+    ) -> Result<(), SingleConnSessionError> {
+        let config = self.connections.config();
+        if let Some(threshold_ms) = config.ping_idle_threshold {
+            if session.get_idle_duration() < Duration::from_millis(threshold_ms as u64) {
+                return Ok(());
+            }
+        }
+
+        let timeout = config.ping_timeout.map(|v| Duration::from_millis(v as u64));
+        let output = match session.execute_stmt(STMT_PING, None, timeout).await {
+            Err(SingleConnSessionError::QueryTimeout(timeout, _)) => {
+                return Err(SingleConnSessionError::PingTimeout(timeout))
+            }
+            res => res?,
+        };
+
+        if let Some(space) = &config.space {
+            let current_space = output.get_space_name();
+            if current_space.as_deref() != Some(space.as_str()) {
+                return Err(SingleConnSessionError::SpaceMismatch(
+                    space.clone(),
+                    current_space,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl bb8::ManageConnection for SessionPoolManager {
+    type Connection = PooledSession;
+    type Error = SingleConnSessionError;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        self.get_session().await
+    }
+
+    async fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        if !self.connections.config().health_check {
+            return Ok(());
+        }
+        self.check_session(conn).await
+    }
+
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        conn.is_close_required()
+    }
+}
+
+//
+//
+//
+/// An authenticated session of a `SessionPool`.
+///
+/// Read statements that fail on a broken connection are retried once on
+/// another connection. A session expired or invalidated by graphd is dropped
+/// by the pool.
+pub struct PooledSession {
+    connections: Arc<ConnectionPool>,
+    session_id: i64,
+    close_required: bool,
+    last_used: Instant,
+}
+
+impl PooledSession {
+    fn new(connections: Arc<ConnectionPool>, session_id: i64) -> Self {
+        Self {
+            connections,
+            session_id,
+            close_required: false,
+            last_used: Instant::now(),
+        }
+    }
+
+    pub fn session_id(&self) -> i64 {
+        self.session_id
+    }
+
+    pub fn is_close_required(&self) -> bool {
+        self.close_required
+    }
+
+    /// Returns how long the session has been idle since its last query.
+    pub fn get_idle_duration(&self) -> Duration {
+        self.last_used.elapsed()
+    }
+
+    /// Sign out of graphd, the pool drops the session afterwards.
+    pub async fn signout(&mut self) -> Result<(), SingleConnSessionError> {
+        self.close_required = true;
+        let mut conn = self.connections.get().await?;
+        conn.signout(self.session_id)
+            .await
+            .map_err(SingleConnSessionError::SignoutError)
+    }
+
+    /// Send a cheap `YIELD 1;` to check that the session is still alive.
+    pub async fn ping(&mut self) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        self.query(STMT_PING).await
+    }
+
+    /// Execute stmt on a connection borrowed from the pool.
+    ///
+    /// If the statement doesn't finish within `timeout`, the connection is
+    /// left broken and closed by the pool, since the response may still arrive
+    /// later.
+    async fn execute_stmt(
+        &mut self,
+        stmt: &str,
+        params: Option<&BTreeMap<Vec<u8>, Value>>,
+        timeout: Option<Duration>,
+    ) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        self.last_used = Instant::now();
+        let connections = self.connections.clone();
+        let mut retry = statement::is_read_only(stmt);
+        let res = loop {
+            let mut conn = connections.get().await?;
+            let res = match timeout {
+                Some(timeout) => {
+                    tokio::time::timeout(timeout, conn.execute(self.session_id, stmt, params))
+                        .await
+                        .map_err(|_| SingleConnSessionError::QueryTimeout(timeout, None))?
+                }
+                None => conn.execute(self.session_id, stmt, params).await,
+            };
+            // The pool replaces the broken connection.
+            if res.is_err() && conn.is_broken() && retry {
+                retry = false;
+                continue;
+            }
+            break res;
+        };
+
+        match res {
+            Err(
+                err @ SingleConnSessionError::GraphQueryError(GraphQueryError::ResponseError(
+                    ErrorCode::E_SESSION_INVALID | ErrorCode::E_SESSION_TIMEOUT,
+                    _,
+                )),
+            ) => {
+                self.close_required = true;
+                Err(err)
+            }
+            res => res,
+        }
+    }
+}
+
+//
+//
+//
+#[async_trait]
+impl GraphQuery for PooledSession {
+    type Error = SingleConnSessionError;
+
+    async fn query(&mut self, stmt: &str) -> Result<GraphQueryOutput, Self::Error> {
+        self.execute_stmt(stmt, None, None).await
+    }
+
+    async fn query_with_params(
+        &mut self,
+        stmt: &str,
+        params: &HashMap<Vec<u8>, Value>,
+    ) -> Result<GraphQueryOutput, Self::Error> {
+        let params = params
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<BTreeMap<_, _>>();
+        self.execute_stmt(stmt, Some(&params), None).await
+    }
+
+    async fn query_with_timeout(
+        &mut self,
+        stmt: &str,
+        timeout: Duration,
+    ) -> Result<GraphQueryOutput, Self::Error> {
+        match self.execute_stmt(stmt, None, Some(timeout)).await {
+            Err(SingleConnSessionError::QueryTimeout(timeout, None)) => {
+                let kill_error = SessionPoolManager::new(self.connections.clone())
+                    .kill_session_queries(self.session_id)
+                    .await
+                    .err()
+                    .map(Box::new);
+                Err(SingleConnSessionError::QueryTimeout(timeout, kill_error))
+            }
+            res => res,
+        }
+    }
+}
@@ -4,33 +4,43 @@ use fbthrift::{
     BinaryProtocol, BufMutExt, Framing, FramingDecoded, FramingEncodedFinal, ProtocolEncoded,
     Transport,
 };
-use fbthrift_transport::{
-    impl_tokio::{TokioSleep, TokioTcpStream},
-    AsyncTransport,
-};
+use fbthrift_transport::{impl_tokio::TokioSleep, AsyncTransport};
 use nebula_fbthrift_graph_v3::{
     client::GraphService as _,
-    dependencies::common::types::ErrorCode,
-    errors::graph_service::{ExecuteError, ExecuteJsonError, SignoutError},
+    dependencies::common::types::{ErrorCode, Value},
+    errors::graph_service::{
+        ExecuteError, ExecuteJsonError, SignoutError, VerifyClientVersionError,
+    },
     graph_service::AuthenticateError,
 };
+use std::collections::{BTreeMap, HashMap};
 use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::TimezoneInfo;
 use crate::{
-    graph::query::{GraphQueryError, GraphQueryOutput},
+    graph::{
+        json_output::JsonQueryOutput,
+        load_balancer::HostLease,
+        observer::{QueryEnd, QueryObserver, QueryStart},
+        query::{GraphQueryError, GraphQueryOutput},
+    },
+    ngql::identifier,
     GraphTransportResponseHandler,
 };
+use crate::{HostAddress, NebulaStream, TimezoneInfo};
 
-use super::{connection::GraphConnection, query::GraphQuery};
+use super::{connection::GraphConnection, query::GraphQuery, statement};
+use single_conn_session_manager::SingleConnSessionManager;
 
+pub mod dsn;
 pub mod single_conn_session_manager;
 
 //
 //
 //
 pub struct SingleConnSession<
-    T = AsyncTransport<TokioTcpStream, TokioSleep, GraphTransportResponseHandler>,
+    T = AsyncTransport<NebulaStream, TokioSleep, GraphTransportResponseHandler>,
 > where
     T: Transport + Framing<DecBuf = std::io::Cursor<Bytes>>,
     Bytes: Framing<DecBuf = FramingDecoded<T>>,
@@ -38,8 +48,20 @@ pub struct SingleConnSession<
 {
     connection: GraphConnection<T>,
     session_id: i64,
+    /// The graphd the session is connected to
+    host: HostAddress,
+    /// Counts the session on its host and reports the failed statements
+    host_lease: Option<HostLease>,
     timezone_info: TimezoneInfo,
     close_required: bool,
+    last_used: Instant,
+    /// The space the session is currently in, as reported by graphd
+    space_name: Option<String>,
+    /// `(username, password)` used to re-authenticate an expired session
+    reauth_credentials: Option<(String, String)>,
+    /// Opens a separate session to kill the statements that ran into a timeout
+    query_killer: Option<SingleConnSessionManager>,
+    observers: Vec<Arc<dyn QueryObserver>>,
 }
 
 impl<T> SingleConnSession<T>
@@ -48,70 +70,389 @@ where
     Bytes: Framing<DecBuf = FramingDecoded<T>>,
     ProtocolEncoded<BinaryProtocol>: BufMutExt<Final = FramingEncodedFinal<T>>,
 {
-    fn new(connection: GraphConnection<T>, session_id: i64) -> Self {
+    fn new(connection: GraphConnection<T>, session_id: i64, host: HostAddress) -> Self {
         Self {
             connection,
             session_id,
+            host,
+            host_lease: None,
             close_required: false,
             timezone_info: TimezoneInfo {},
+            last_used: Instant::now(),
+            space_name: None,
+            reauth_credentials: None,
+            query_killer: None,
+            observers: vec![],
         }
     }
 
+    /// Authenticate a session over `transport`, e.g. to record or replay the
+    /// session with the transports of the `testing` feature.
+    /// ## Notice
+    /// The session is on its own, re-authentication, query killing and host
+    /// health tracking of `SingleConnSessionManager` don't apply.
+    pub async fn new_with_transport(
+        addr: &HostAddress,
+        transport: T,
+        username: &str,
+        password: &str,
+    ) -> Result<Self, SingleConnSessionError> {
+        let connection = GraphConnection::new_with_transport(transport);
+        let session_id = connection
+            .authenticate(username, password)
+            .await
+            .map_err(SingleConnSessionError::AuthenticateError)?;
+        Ok(Self::new(connection, session_id, addr.clone()))
+    }
+
+    pub(super) fn set_reauth_credentials(&mut self, username: String, password: String) {
+        self.reauth_credentials = Some((username, password));
+    }
+
+    pub(super) fn set_query_killer(&mut self, manager: SingleConnSessionManager) {
+        self.query_killer = Some(manager);
+    }
+
+    pub(super) fn set_host_lease(&mut self, lease: HostLease) {
+        self.host_lease = Some(lease);
+    }
+
+    fn record_query(&self, succeeded: bool) {
+        if let Some(lease) = &self.host_lease {
+            lease.record_query(succeeded);
+        }
+    }
+
+    pub(super) fn set_observers(&mut self, observers: Vec<Arc<dyn QueryObserver>>) {
+        self.observers = observers;
+    }
+
     pub async fn signout(self) -> Result<(), SignoutError> {
         self.connection.service.signout(self.session_id).await
     }
 
-    #[allow(clippy::ptr_arg, unused)]
-    async fn execute_json(&mut self, stmt: &Vec<u8>) -> Result<Vec<u8>, ExecuteJsonError> {
+    /// Execute stmt and return graphd's raw JSON response.
+    /// ## Notice
+    /// Errors reported by graphd are part of the JSON rather than an `Err`,
+    /// see `query_json(stmt)` for a checked and typed variant.
+    pub async fn execute_json(&mut self, stmt: &str) -> Result<Vec<u8>, SingleConnSessionError> {
+        if self.observers.is_empty() {
+            return self.execute_json_stmt(stmt).await;
+        }
+
+        self.notify_start(stmt);
+        let start = Instant::now();
+        let res = self.execute_json_stmt(stmt).await;
+        let output = res
+            .as_ref()
+            .ok()
+            .and_then(|v| JsonQueryOutput::from_slice(v).ok())
+            .map(|v| (v.get_error_code(), v.get_latency()));
+        self.notify_end(stmt, start, output, res.as_ref().err());
+        res
+    }
+
+    async fn execute_json_stmt(&mut self, stmt: &str) -> Result<Vec<u8>, SingleConnSessionError> {
+        self.last_used = Instant::now();
+        let stmt = stmt.as_bytes().to_vec();
         let res = match self
             .connection
             .service
-            .executeJson(self.session_id, stmt)
+            .executeJson(self.session_id, &stmt)
             .await
         {
             Ok(res) => res,
             Err(ExecuteJsonError::ThriftError(err)) => {
+                self.record_query(false);
                 if let Some(io_err) = err.downcast_ref::<IoError>() {
                     // "ExecuteJsonError Broken pipe (os error 32)"
                     if io_err.kind() == IoErrorKind::BrokenPipe {
                         self.close_required = true;
                     }
                 }
-                return Err(ExecuteJsonError::ThriftError(err));
+                return Err(
+                    GraphQueryError::ExecuteError(ExecuteJsonError::ThriftError(err)).into(),
+                );
             }
-            Err(err) => return Err(err),
+            Err(err) => return Err(GraphQueryError::ExecuteError(err).into()),
         };
+        self.record_query(true);
 
         Ok(res)
     }
 
+    /// Execute stmt through `executeJson` and decode the response.
+    pub async fn query_json(
+        &mut self,
+        stmt: &str,
+    ) -> Result<JsonQueryOutput, SingleConnSessionError> {
+        if self.observers.is_empty() {
+            return self.query_json_stmt(stmt).await;
+        }
+
+        self.notify_start(stmt);
+        let start = Instant::now();
+        let res = self.query_json_stmt(stmt).await;
+        let output = res
+            .as_ref()
+            .ok()
+            .map(|v| (v.get_error_code(), v.get_latency()));
+        self.notify_end(stmt, start, output, res.as_ref().err());
+        res
+    }
+
+    async fn query_json_stmt(
+        &mut self,
+        stmt: &str,
+    ) -> Result<JsonQueryOutput, SingleConnSessionError> {
+        let res = self.execute_json_stmt(stmt).await?;
+        let output = JsonQueryOutput::from_slice(&res).map_err(GraphQueryError::JsonDecodeError)?;
+
+        match output.get_error_code() {
+            ErrorCode::SUCCEEDED => {}
+            err_code @ (ErrorCode::E_SESSION_INVALID | ErrorCode::E_SESSION_TIMEOUT) => {
+                self.close_required = true;
+                return Err(GraphQueryError::ResponseError(
+                    err_code,
+                    output.get_error_msg().map(String::into_bytes),
+                )
+                .into());
+            }
+            err_code => {
+                return Err(GraphQueryError::ResponseError(
+                    err_code,
+                    output.get_error_msg().map(String::into_bytes),
+                )
+                .into());
+            }
+        }
+
+        Ok(output)
+    }
+
+    fn notify_start(&self, stmt: &str) {
+        let event = QueryStart {
+            stmt,
+            session_id: self.session_id,
+            host: &self.host,
+        };
+        for observer in self.observers.iter() {
+            observer.before_query(&event);
+        }
+    }
+
+    /// `output` is the error code and the latency of the response, if any.
+    fn notify_end(
+        &self,
+        stmt: &str,
+        start: Instant,
+        output: Option<(ErrorCode, i64)>,
+        error: Option<&SingleConnSessionError>,
+    ) {
+        let (error_code, latency_in_us) = match (output, error) {
+            (Some((code, latency)), _) => (Some(code), Some(latency)),
+            (
+                None,
+                Some(SingleConnSessionError::GraphQueryError(GraphQueryError::ResponseError(
+                    code,
+                    _,
+                ))),
+            ) => (Some(*code), None),
+            _ => (None, None),
+        };
+        let event = QueryEnd {
+            stmt,
+            session_id: self.session_id,
+            host: &self.host,
+            error_code,
+            latency_in_us,
+            elapsed: start.elapsed(),
+            error,
+        };
+        for observer in self.observers.iter() {
+            observer.after_query(&event);
+        }
+    }
+
     pub fn is_close_required(&self) -> bool {
         self.close_required
     }
+
+    /// Returns how long the session has been idle since its last query.
+    pub fn get_idle_duration(&self) -> Duration {
+        self.last_used.elapsed()
+    }
 }
 
-//
-//
-//
-#[async_trait]
-impl<T> GraphQuery for SingleConnSession<T>
+impl<T> SingleConnSession<T>
 where
     T: Transport + Send + Sync + Framing<DecBuf = std::io::Cursor<Bytes>>,
     Bytes: Framing<DecBuf = FramingDecoded<T>>,
     ProtocolEncoded<BinaryProtocol>: BufMutExt<Final = FramingEncodedFinal<T>>,
 {
-    type Error = SingleConnSessionError;
+    /// Send a cheap `YIELD 1;` to check that the session is still alive.
+    ///
+    /// The output carries the current space of the session, which can be
+    /// compared against the expected one.
+    pub async fn ping(&mut self) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        self.query(STMT_PING).await
+    }
 
-    async fn query(&mut self, stmt: &str) -> Result<GraphQueryOutput, Self::Error> {
-        let stmt = stmt.as_bytes().to_vec();
-        let res = match self
+    /// Execute stmt like `query(stmt)`, but mark it as safe to retry after a
+    /// transparent re-authentication even if it modifies data.
+    pub async fn query_idempotent(
+        &mut self,
+        stmt: &str,
+    ) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        self.query_with_reauth(stmt, None, true, None).await
+    }
+
+    /// Execute stmt and call the observers around it.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "query",
+            level = "debug",
+            skip_all,
+            fields(
+                stmt = stmt,
+                host = %self.host.to_string(),
+                space = ?self.space_name,
+                session_id = self.session_id,
+                error_code = tracing::field::Empty,
+            ),
+            err(Display)
+        )
+    )]
+    async fn query_with_reauth(
+        &mut self,
+        stmt: &str,
+        params: Option<&BTreeMap<Vec<u8>, Value>>,
+        idempotent: bool,
+        timeout: Option<Duration>,
+    ) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        if self.observers.is_empty() {
+            return self
+                .execute_with_timeout(stmt, params, idempotent, timeout)
+                .await;
+        }
+
+        self.notify_start(stmt);
+        let start = Instant::now();
+        let res = self
+            .execute_with_timeout(stmt, params, idempotent, timeout)
+            .await;
+        let output = res
+            .as_ref()
+            .ok()
+            .map(|v| (v.get_error_code(), v.get_latency()));
+        self.notify_end(stmt, start, output, res.as_ref().err());
+        res
+    }
+
+    /// Execute stmt, and kill it on graphd if it doesn't finish within `timeout`.
+    async fn execute_with_timeout(
+        &mut self,
+        stmt: &str,
+        params: Option<&BTreeMap<Vec<u8>, Value>>,
+        idempotent: bool,
+        timeout: Option<Duration>,
+    ) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return self.execute_with_reauth(stmt, params, idempotent).await,
+        };
+        let execute = self.execute_with_reauth(stmt, params, idempotent);
+        if let Ok(res) = tokio::time::timeout(timeout, execute).await {
+            return res;
+        }
+
+        // The response may still arrive later, so the connection can't be reused.
+        self.close_required = true;
+        let kill_error = match &self.query_killer {
+            Some(manager) => manager
+                .kill_session_queries(self.session_id)
+                .await
+                .err()
+                .map(Box::new),
+            None => None,
+        };
+        Err(SingleConnSessionError::QueryTimeout(timeout, kill_error))
+    }
+
+    async fn execute_with_reauth(
+        &mut self,
+        stmt: &str,
+        params: Option<&BTreeMap<Vec<u8>, Value>>,
+        idempotent: bool,
+    ) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        match self.execute_stmt(stmt, params).await {
+            Err(
+                err @ SingleConnSessionError::GraphQueryError(GraphQueryError::ResponseError(
+                    ErrorCode::E_SESSION_INVALID | ErrorCode::E_SESSION_TIMEOUT,
+                    _,
+                )),
+            ) if self.reauth_credentials.is_some() => {
+                self.reauthenticate().await?;
+                // The session is usable again, but a statement that may modify data
+                // can't be retried blindly, so the caller gets the original error.
+                if idempotent {
+                    self.execute_stmt(stmt, params).await
+                } else {
+                    Err(err)
+                }
+            }
+            res => res,
+        }
+    }
+
+    /// Authenticate again on the same connection and switch back to the space
+    /// the expired session was in.
+    async fn reauthenticate(&mut self) -> Result<(), SingleConnSessionError> {
+        let (username, password) = match &self.reauth_credentials {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        self.session_id = self
             .connection
-            .service
-            .execute(self.session_id, &stmt)
+            .authenticate(username, password)
             .await
-        {
+            .map_err(SingleConnSessionError::AuthenticateError)?;
+
+        // The session stays marked for close until it's back in its space.
+        if let Some(space) = self.space_name.clone() {
+            self.execute_stmt(&format!("USE {};", identifier(&space)), None)
+                .await?;
+        }
+        self.close_required = false;
+        Ok(())
+    }
+
+    async fn execute_stmt(
+        &mut self,
+        stmt: &str,
+        params: Option<&BTreeMap<Vec<u8>, Value>>,
+    ) -> Result<GraphQueryOutput, SingleConnSessionError> {
+        self.last_used = Instant::now();
+        let stmt = stmt.as_bytes().to_vec();
+        let res = match params {
+            Some(params) => {
+                self.connection
+                    .service
+                    .executeWithParameter(self.session_id, &stmt, params)
+                    .await
+            }
+            None => {
+                self.connection
+                    .service
+                    .execute(self.session_id, &stmt)
+                    .await
+            }
+        };
+        let res = match res {
             Ok(res) => res,
             Err(ExecuteError::ThriftError(err)) => {
+                self.record_query(false);
                 if let Some(io_err) = err.downcast_ref::<IoError>() {
                     // "ExecuteError Broken pipe (os error 32)"
                     if io_err.kind() == IoErrorKind::BrokenPipe {
@@ -123,7 +464,10 @@ where
             }
             Err(err) => return Err(GraphQueryError::ExecuteError(err).into()),
         };
+        self.record_query(true);
 
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("error_code", tracing::field::display(res.error_code));
         match res.error_code {
             ErrorCode::SUCCEEDED => {}
             ErrorCode::E_SESSION_INVALID | ErrorCode::E_SESSION_TIMEOUT => {
@@ -135,23 +479,110 @@ where
             }
         }
 
+        if let Some(space_name) = &res.space_name {
+            if !space_name.is_empty() {
+                self.space_name = Some(String::from_utf8_lossy(space_name).to_string());
+            }
+        }
+
         Ok(GraphQueryOutput::new(res, self.timezone_info.clone()))
     }
 }
 
+pub(crate) const STMT_PING: &str = "YIELD 1;";
+
+//
+//
+//
+#[async_trait]
+impl<T> GraphQuery for SingleConnSession<T>
+where
+    T: Transport + Send + Sync + Framing<DecBuf = std::io::Cursor<Bytes>>,
+    Bytes: Framing<DecBuf = FramingDecoded<T>>,
+    ProtocolEncoded<BinaryProtocol>: BufMutExt<Final = FramingEncodedFinal<T>>,
+{
+    type Error = SingleConnSessionError;
+
+    async fn query(&mut self, stmt: &str) -> Result<GraphQueryOutput, Self::Error> {
+        self.query_with_reauth(stmt, None, statement::is_read_only(stmt), None)
+            .await
+    }
+
+    async fn query_with_params(
+        &mut self,
+        stmt: &str,
+        params: &HashMap<Vec<u8>, Value>,
+    ) -> Result<GraphQueryOutput, Self::Error> {
+        let params = params
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<BTreeMap<_, _>>();
+        self.query_with_reauth(stmt, Some(&params), statement::is_read_only(stmt), None)
+            .await
+    }
+
+    async fn query_with_timeout(
+        &mut self,
+        stmt: &str,
+        timeout: Duration,
+    ) -> Result<GraphQueryOutput, Self::Error> {
+        self.query_with_reauth(stmt, None, statement::is_read_only(stmt), Some(timeout))
+            .await
+    }
+}
+
 #[derive(Debug)]
 pub enum SingleConnSessionError {
     TransportBuildError(std::io::Error),
+    VerifyClientVersionError(VerifyClientVersionError),
+    /// graphd doesn't accept the client version, carries the version and the
+    /// message of graphd.
+    ClientVersionRejected(String, String),
     AuthenticateError(AuthenticateError),
     GraphQueryError(GraphQueryError),
+    PingTimeout(Duration),
+    /// The statement didn't finish in time. Carries the error of killing it on
+    /// the server, if that failed too.
+    QueryTimeout(Duration, Option<Box<SingleConnSessionError>>),
+    /// No connection of the `ConnectionPool` became free in time.
+    ConnectionPoolTimeout(Duration),
+    SignoutError(SignoutError),
+    SpaceMismatch(String, Option<String>),
+    NoAvailableHost(Vec<(HostAddress, SingleConnSessionError)>),
 }
 
 impl core::fmt::Display for SingleConnSessionError {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
             Self::TransportBuildError(err) => write!(f, "TransportBuildError {err}"),
+            Self::VerifyClientVersionError(err) => write!(f, "VerifyClientVersionError {err}"),
+            Self::ClientVersionRejected(version, msg) => {
+                write!(f, "ClientVersionRejected version:{version} err_msg:{msg}")
+            }
             Self::AuthenticateError(err) => write!(f, "AuthenticateError {err}"),
             Self::GraphQueryError(err) => write!(f, "GraphQueryError {err}"),
+            Self::PingTimeout(timeout) => write!(f, "PingTimeout after {timeout:?}"),
+            Self::QueryTimeout(timeout, kill_error) => {
+                write!(f, "QueryTimeout after {timeout:?}")?;
+                if let Some(err) = kill_error {
+                    write!(f, ", failed to kill the query: {err}")?;
+                }
+                Ok(())
+            }
+            Self::ConnectionPoolTimeout(timeout) => {
+                write!(f, "ConnectionPoolTimeout after {timeout:?}")
+            }
+            Self::SignoutError(err) => write!(f, "SignoutError {err}"),
+            Self::SpaceMismatch(expected, actual) => {
+                write!(f, "SpaceMismatch expected:{expected} actual:{actual:?}")
+            }
+            Self::NoAvailableHost(failures) => {
+                write!(f, "NoAvailableHost")?;
+                for (addr, err) in failures {
+                    write!(f, " [{}: {err}]", addr.to_string())?;
+                }
+                Ok(())
+            }
         }
     }
 }
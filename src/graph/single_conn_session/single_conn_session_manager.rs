@@ -1,14 +1,28 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::Duration;
 
 use async_trait::async_trait;
 use fbthrift_transport::{AsyncTransport, AsyncTransportConfiguration};
 use fbthrift_transport_response_handler::ResponseHandler;
+use nebula_fbthrift_graph_v3::{
+    dependencies::common::types::ErrorCode, errors::graph_service::VerifyClientVersionError,
+    graph_service::AuthenticateError,
+};
 
-use crate::HostAddress;
 use crate::{
-    graph::{connection::GraphConnection, GraphQuery},
+    graph::{
+        connection::GraphConnection,
+        load_balancer::{HostLease, HostRegistry, LoadBalancer, QuarantinePolicy, RoundRobin},
+        observer::QueryObserver,
+        query::{RunningQuery, STMT_SHOW_ALL_QUERIES},
+        GraphQuery, GraphQueryError,
+    },
     GraphTransportResponseHandler,
 };
+use crate::{stream, HostAddress, TlsConfig};
 
 use super::{SingleConnSession, SingleConnSessionError};
 
@@ -18,6 +32,11 @@ pub struct SingleConnSessionConf {
     pub host_addrs: Vec<HostAddress>,
     /// Index of the current host address being used
     host_idx: AtomicUsize,
+    /// Sessions, health and quarantine of every host, shared with the clones of
+    /// the configuration
+    hosts: Arc<HostRegistry>,
+    /// Orders the hosts that aren't quarantined
+    pub load_balancer: Arc<dyn LoadBalancer>,
     /// graphd's username
     pub username: String,
     /// graphd's password
@@ -37,13 +56,59 @@ pub struct SingleConnSessionConf {
     pub max_parse_response_bytes_count: Option<u8>,
     /// Set fbthrift read_timeout
     pub read_timeout: Option<u32>,
+    /// Validate pooled sessions with a `YIELD 1;` ping before handing them out.
+    /// A validated session must still be in `space` if it's set.
+    pub health_check: bool,
+    /// Set the deadline of the health check ping in milliseconds
+    pub ping_timeout: Option<u32>,
+    /// Only ping sessions that have been idle for longer than this many milliseconds
+    pub ping_idle_threshold: Option<u32>,
+    /// Initial quarantine of an unreachable host in milliseconds, doubled on every
+    /// consecutive failure
+    pub quarantine_backoff: u32,
+    /// Upper bound of the quarantine of an unreachable host in milliseconds
+    pub max_quarantine_backoff: u32,
+    /// Quarantine a host after this many consecutive statements failed on it
+    /// without a response
+    pub max_query_failures: u32,
+    /// What to do when graphd reports `E_SESSION_INVALID` or `E_SESSION_TIMEOUT`
+    pub reauth_policy: ReauthPolicy,
+    /// Connect to graphd with TLS, requires the `tls` feature
+    pub tls: Option<TlsConfig>,
+    /// The version sent with `verifyClientVersion` before authenticating.
+    /// graphd rejects versions missing from its `client_white_list`.
+    /// ## Notice
+    /// Set it `None` to skip the handshake, e.g. for graphd 2.x which doesn't
+    /// support it.
+    pub client_version: Option<String>,
+    /// Called before and after every statement of the sessions
+    pub observers: Vec<Arc<dyn QueryObserver>>,
+}
+
+/// How a session recovers from being expired or invalidated by graphd.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReauthPolicy {
+    /// Mark the session as broken, so the pool drops it.
+    #[default]
+    Never,
+    /// Re-authenticate on the same connection and switch back to the previous
+    /// space. The failed statement is retried once if it's a read, or if it's
+    /// executed with `SingleConnSession::query_idempotent`.
+    RetryIdempotent,
 }
 
+const DEFAULT_QUARANTINE_BACKOFF_MS: u32 = 1000;
+const DEFAULT_MAX_QUARANTINE_BACKOFF_MS: u32 = 60 * 1000;
+const DEFAULT_MAX_QUERY_FAILURES: u32 = 3;
+pub const DEFAULT_CLIENT_VERSION: &str = "3.0.0";
+
 impl Clone for SingleConnSessionConf {
     fn clone(&self) -> Self {
         Self {
             host_addrs: self.host_addrs.clone(),
             host_idx: AtomicUsize::new(self.host_idx.load(Ordering::Relaxed)),
+            hosts: self.hosts.clone(),
+            load_balancer: self.load_balancer.clone(),
             username: self.username.clone(),
             password: self.password.clone(),
             space: self.space.clone(),
@@ -51,6 +116,16 @@ impl Clone for SingleConnSessionConf {
             max_buf_size: self.max_buf_size.clone(),
             max_parse_response_bytes_count: self.max_parse_response_bytes_count.clone(),
             read_timeout: self.read_timeout.clone(),
+            health_check: self.health_check,
+            ping_timeout: self.ping_timeout,
+            ping_idle_threshold: self.ping_idle_threshold,
+            quarantine_backoff: self.quarantine_backoff,
+            max_quarantine_backoff: self.max_quarantine_backoff,
+            max_query_failures: self.max_query_failures,
+            reauth_policy: self.reauth_policy,
+            tls: self.tls.clone(),
+            client_version: self.client_version.clone(),
+            observers: self.observers.clone(),
         }
     }
 }
@@ -64,6 +139,8 @@ impl SingleConnSessionConf {
         Self {
             host_addrs,
             host_idx: AtomicUsize::new(0),
+            hosts: Arc::new(HostRegistry::default()),
+            load_balancer: Arc::new(RoundRobin::default()),
             username,
             password,
             space,
@@ -71,6 +148,16 @@ impl SingleConnSessionConf {
             max_buf_size: None,
             max_parse_response_bytes_count: None,
             read_timeout: None,
+            health_check: false,
+            ping_timeout: None,
+            ping_idle_threshold: None,
+            quarantine_backoff: DEFAULT_QUARANTINE_BACKOFF_MS,
+            max_quarantine_backoff: DEFAULT_MAX_QUARANTINE_BACKOFF_MS,
+            max_query_failures: DEFAULT_MAX_QUERY_FAILURES,
+            reauth_policy: ReauthPolicy::Never,
+            tls: None,
+            client_version: Some(DEFAULT_CLIENT_VERSION.to_owned()),
+            observers: vec![],
         }
     }
 
@@ -86,16 +173,82 @@ impl SingleConnSessionConf {
     pub fn set_read_timeout(&mut self, timeout_ms: u32) {
         self.read_timeout = Some(timeout_ms);
     }
+    pub fn set_health_check(&mut self, enabled: bool) {
+        self.health_check = enabled;
+    }
+    pub fn set_ping_timeout(&mut self, timeout_ms: u32) {
+        self.ping_timeout = Some(timeout_ms);
+    }
+    pub fn set_ping_idle_threshold(&mut self, threshold_ms: u32) {
+        self.ping_idle_threshold = Some(threshold_ms);
+    }
+    pub fn set_quarantine_backoff(&mut self, backoff_ms: u32, max_backoff_ms: u32) {
+        self.quarantine_backoff = backoff_ms;
+        self.max_quarantine_backoff = max_backoff_ms;
+    }
+    pub fn set_max_query_failures(&mut self, failures: u32) {
+        self.max_query_failures = failures;
+    }
+    pub fn set_load_balancer(&mut self, load_balancer: Arc<dyn LoadBalancer>) {
+        self.load_balancer = load_balancer;
+    }
+    pub fn set_reauth_policy(&mut self, policy: ReauthPolicy) {
+        self.reauth_policy = policy;
+    }
+    pub fn set_tls(&mut self, tls: TlsConfig) {
+        self.tls = Some(tls);
+    }
+    pub fn set_client_version(&mut self, version: Option<&str>) {
+        self.client_version = version.map(ToOwned::to_owned);
+    }
+    pub fn add_observer(&mut self, observer: Arc<dyn QueryObserver>) {
+        self.observers.push(observer);
+    }
 }
 
 impl SingleConnSessionConf {
     pub fn get_next_addr(&self) -> HostAddress {
-        if self.host_idx.load(Ordering::Relaxed) >= self.host_addrs.len() {
-            self.host_idx.store(0, Ordering::Relaxed)
+        let idx = self.host_idx.fetch_add(1, Ordering::Relaxed);
+        self.host_addrs[idx % self.host_addrs.len()].clone()
+    }
+
+    /// Returns every host once, in the order they should be tried.
+    /// Healthy hosts come first in the order of the load balancer, followed by
+    /// quarantined hosts sorted by the end of their quarantine.
+    pub fn get_connect_order(&self) -> Vec<HostAddress> {
+        let (mut healthy, quarantined) = self.hosts.states(&self.host_addrs);
+        self.load_balancer.order(&mut healthy);
+        healthy
+            .into_iter()
+            .map(|v| v.addr)
+            .chain(quarantined)
+            .collect()
+    }
+
+    fn quarantine_policy(&self) -> QuarantinePolicy {
+        QuarantinePolicy {
+            backoff_ms: self.quarantine_backoff,
+            max_backoff_ms: self.max_quarantine_backoff,
+            max_query_failures: self.max_query_failures,
         }
-        let host = self.host_addrs[self.host_idx.load(Ordering::Relaxed)].clone();
-        self.host_idx.fetch_add(1, Ordering::Relaxed);
-        host
+    }
+
+    pub(crate) fn quarantine_host(&self, addr: &HostAddress) {
+        self.hosts.quarantine(addr, &self.quarantine_policy());
+    }
+
+    pub(crate) fn release_host(&self, addr: &HostAddress) {
+        self.hosts.release(addr);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn quarantined_until(&self, addr: &HostAddress) -> Option<std::time::Instant> {
+        self.hosts.until(addr)
+    }
+
+    /// Count a session or a connection on `addr` until the lease is dropped.
+    pub(crate) fn lease_host(&self, addr: &HostAddress) -> HostLease {
+        HostLease::new(self.hosts.clone(), addr.clone(), self.quarantine_policy())
     }
 }
 
@@ -139,20 +292,73 @@ impl SingleConnSessionManager {
         Self::new_with_response_handler(config, GraphTransportResponseHandler)
     }
 
+    /// Open a session on the first reachable graphd.
+    ///
+    /// Every host in `host_addrs` is tried at most once. Hosts that can't be
+    /// reached are quarantined with an exponential backoff, so later calls try
+    /// them last.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                space = ?self.config.space,
+                host = tracing::field::Empty,
+                session_id = tracing::field::Empty,
+            ),
+            err(Display)
+        )
+    )]
     pub async fn get_session(&self) -> Result<SingleConnSession, SingleConnSessionError> {
-        let transport = AsyncTransport::with_tokio_tcp_connect(
-            self.config.get_next_addr().to_string(),
-            self.transport_config.clone(),
+        let mut failures = vec![];
+        for addr in self.config.get_connect_order() {
+            match self.open_session(&addr).await {
+                Ok(session) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::Span::current()
+                        .record("host", tracing::field::display(addr.to_string()))
+                        .record("session_id", session.session_id);
+                    self.config.release_host(&addr);
+                    return Ok(session);
+                }
+                Err(err) if is_connect_error(&err) => {
+                    self.config.quarantine_host(&addr);
+                    failures.push((addr, err));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Err(SingleConnSessionError::NoAvailableHost(failures))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(host = %addr.to_string()),
+            err(Display)
         )
-        .await
-        .map_err(SingleConnSessionError::TransportBuildError)?;
-        let conn = GraphConnection::new_with_transport(transport);
+    )]
+    async fn open_session(
+        &self,
+        addr: &HostAddress,
+    ) -> Result<SingleConnSession, SingleConnSessionError> {
+        let conn = self.connect(addr).await?;
         let session_id = conn
             .authenticate(&self.config.username, &self.config.password)
             .await
             .map_err(SingleConnSessionError::AuthenticateError)?;
 
-        let mut session = SingleConnSession::new(conn, session_id);
+        let mut session = SingleConnSession::new(conn, session_id, addr.clone());
+        session.set_host_lease(self.config.lease_host(addr));
+        session.set_observers(self.config.observers.clone());
+        session.set_query_killer(self.clone());
+        if self.config.reauth_policy == ReauthPolicy::RetryIdempotent {
+            session
+                .set_reauth_credentials(self.config.username.clone(), self.config.password.clone());
+        }
         if self.config.space.is_some() {
             session
                 .execute(&format!("Use {};", self.config.space.clone().unwrap()))
@@ -161,6 +367,120 @@ impl SingleConnSessionManager {
 
         Ok(session)
     }
+
+    /// Connect to graphd and verify the client version, without authenticating.
+    pub(in crate::graph) async fn connect(
+        &self,
+        addr: &HostAddress,
+    ) -> Result<GraphConnection, SingleConnSessionError> {
+        let stream = stream::connect(addr.host(), addr.port(), self.config.tls.as_ref())
+            .await
+            .map_err(SingleConnSessionError::TransportBuildError)?;
+        let transport = AsyncTransport::new(stream, self.transport_config.clone());
+        let conn = GraphConnection::new_with_transport(transport);
+        if let Some(version) = &self.config.client_version {
+            let res = conn
+                .verify_client_version(version)
+                .await
+                .map_err(SingleConnSessionError::VerifyClientVersionError)?;
+            if res.error_code != ErrorCode::SUCCEEDED {
+                return Err(SingleConnSessionError::ClientVersionRejected(
+                    version.clone(),
+                    res.error_msg
+                        .map(|v| String::from_utf8_lossy(&v).to_string())
+                        .unwrap_or_default(),
+                ));
+            }
+        }
+        Ok(conn)
+    }
+
+    /// Kill the running statements of the session `session_id` from a separate session,
+    /// so graphd stops working on them after the client gave up.
+    pub async fn kill_session_queries(
+        &self,
+        session_id: i64,
+    ) -> Result<(), SingleConnSessionError> {
+        let mut session = self.get_session().await?;
+        let res = kill_queries(&mut session, session_id).await;
+        let _ = session.signout().await;
+        res
+    }
+
+    /// Ping the session if it has been idle long enough, and check that it's
+    /// still in the configured space.
+    pub async fn check_session(
+        &self,
+        session: &mut SingleConnSession,
+    ) -> Result<(), SingleConnSessionError> {
+        if let Some(threshold_ms) = self.config.ping_idle_threshold {
+            if session.get_idle_duration() < Duration::from_millis(threshold_ms as u64) {
+                return Ok(());
+            }
+        }
+
+        let output = match self.config.ping_timeout {
+            Some(timeout_ms) => {
+                let timeout = Duration::from_millis(timeout_ms as u64);
+                match tokio::time::timeout(timeout, session.ping()).await {
+                    Ok(res) => res?,
+                    Err(_) => {
+                        // The response may still arrive later, so the connection can't be reused.
+                        session.close_required = true;
+                        return Err(SingleConnSessionError::PingTimeout(timeout));
+                    }
+                }
+            }
+            None => session.ping().await?,
+        };
+
+        if let Some(space) = &self.config.space {
+            let current_space = output.get_space_name();
+            if current_space.as_deref() != Some(space.as_str()) {
+                return Err(SingleConnSessionError::SpaceMismatch(
+                    space.clone(),
+                    current_space,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether the host couldn't be reached, so it's worth trying another one.
+pub(crate) fn is_connect_error(err: &SingleConnSessionError) -> bool {
+    matches!(
+        err,
+        SingleConnSessionError::TransportBuildError(_)
+            | SingleConnSessionError::VerifyClientVersionError(
+                VerifyClientVersionError::ThriftError(_),
+            )
+            | SingleConnSessionError::AuthenticateError(AuthenticateError::ThriftError(_))
+    )
+}
+
+/// Kill the running statements of the session `session_id` through `session`.
+pub(crate) async fn kill_queries<Q>(
+    session: &mut Q,
+    session_id: i64,
+) -> Result<(), SingleConnSessionError>
+where
+    Q: GraphQuery<Error = SingleConnSessionError> + Send,
+{
+    let output = session.query(STMT_SHOW_ALL_QUERIES).await?;
+    let queries = output
+        .scan::<RunningQuery>()
+        .map_err(GraphQueryError::DataSetError)?;
+    for query in queries.iter().filter(|v| v.session_id == session_id) {
+        session
+            .execute(&format!(
+                "KILL QUERY (session={}, plan={});",
+                query.session_id, query.plan_id
+            ))
+            .await?;
+    }
+    Ok(())
 }
 
 #[async_trait]
@@ -172,11 +492,88 @@ impl bb8::ManageConnection for SingleConnSessionManager {
         self.get_session().await
     }
 
-    async fn is_valid(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
-        Ok(())
+    async fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        if !self.config.health_check {
+            return Ok(());
+        }
+        self.check_session(conn).await
     }
 
     fn has_broken(&self, conn: &mut Self::Connection) -> bool {
         conn.is_close_required()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    fn conf() -> SingleConnSessionConf {
+        SingleConnSessionConf::new(
+            vec![
+                HostAddress::new("127.0.0.1", 9669),
+                HostAddress::new("127.0.0.2", 9669),
+                HostAddress::new("127.0.0.3", 9669),
+            ],
+            "root".to_owned(),
+            "nebula".to_owned(),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_get_connect_order() {
+        let conf = conf();
+        let order = conf.get_connect_order();
+        assert_eq!(order, conf.host_addrs);
+
+        conf.quarantine_host(&conf.host_addrs[0]);
+        let order = conf.get_connect_order();
+        assert_eq!(
+            order,
+            vec![
+                conf.host_addrs[2].clone(),
+                conf.host_addrs[1].clone(),
+                conf.host_addrs[0].clone()
+            ]
+        );
+
+        conf.release_host(&conf.host_addrs[0]);
+        let order = conf.get_connect_order();
+        assert_eq!(
+            order,
+            vec![
+                conf.host_addrs[2].clone(),
+                conf.host_addrs[0].clone(),
+                conf.host_addrs[1].clone()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_next_addr() {
+        let conf = conf();
+        let addrs = (0..4).map(|_| conf.get_next_addr()).collect::<Vec<_>>();
+        assert_eq!(addrs[0], conf.host_addrs[0]);
+        assert_eq!(addrs[2], conf.host_addrs[2]);
+        assert_eq!(addrs[3], conf.host_addrs[0]);
+    }
+
+    #[test]
+    fn test_quarantine_backoff() {
+        let mut conf = conf();
+        conf.set_quarantine_backoff(100, 250);
+        let addr = conf.host_addrs[0].clone();
+
+        let mut backoffs = vec![];
+        for _ in 0..4 {
+            let now = Instant::now();
+            conf.quarantine_host(&addr);
+            let until = conf.hosts.until(&addr).unwrap();
+            backoffs.push((until - now).as_millis() / 50 * 50);
+        }
+        assert_eq!(backoffs, vec![100, 200, 250, 250]);
+    }
+}
@@ -0,0 +1,357 @@
+//! Lightweight inspection of nGQL statements.
+
+/// Keywords that start a statement which doesn't modify any data.
+const READ_ONLY_KEYWORDS: &[&str] = &[
+    "MATCH", "OPTIONAL", "GO", "FETCH", "LOOKUP", "FIND", "GET", "YIELD", "RETURN", "UNWIND",
+    "WITH", "SHOW", "DESCRIBE", "DESC", "EXPLAIN", "USE",
+];
+
+/// Returns whether the statement is known to only read data, so that it's safe
+/// to execute it again.
+///
+/// The statement is split on `;` and `|` outside of quotes, and every part has to
+/// start with a read-only keyword. Anything that can't be recognized is treated as
+/// a write.
+pub(crate) fn is_read_only(stmt: &str) -> bool {
+    let parts = split_outside_quotes(stmt, &[';', '|']);
+    let mut has_clause = false;
+    for part in parts {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        let keyword = part
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        if !READ_ONLY_KEYWORDS.contains(&keyword.as_str()) {
+            return false;
+        }
+        has_clause = true;
+    }
+    has_clause
+}
+
+fn split_outside_quotes<'a>(stmt: &'a str, separators: &[char]) -> Vec<&'a str> {
+    let mut parts = vec![];
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    let mut chars = stmt.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' | '`' => quote = Some(c),
+            // `||` is the logical OR operator, not a pipe.
+            '|' if chars.peek().map(|(_, c)| *c) == Some('|') => {
+                chars.next();
+            }
+            c if separators.contains(&c) => {
+                parts.push(&stmt[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&stmt[start..]);
+    parts
+}
+
+/// A statement of a script, without its comments and the trailing `;`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ScriptStatement {
+    pub(crate) text: String,
+    /// 1-based line of the first token of the statement
+    pub(crate) line: usize,
+}
+
+/// Split a script like the content of a `.ngql` file into statements.
+///
+/// Statements are separated by `;` outside of quotes. Comments (`#` and `//` to
+/// the end of the line, and `/* ... */`) are removed, and statements that are
+/// empty afterwards are skipped. `--` isn't a comment, it's part of the edge
+/// patterns like `-->`.
+pub(crate) fn split_script(script: &str) -> Vec<ScriptStatement> {
+    let mut statements = vec![];
+    let mut text = String::new();
+    let mut line = 1;
+    let mut start_line = None;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut chars = script.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if let Some(q) = quote {
+            text.push(c);
+            if c == '\n' {
+                line += 1;
+            }
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+
+        let next = chars.peek().copied();
+        match c {
+            '#' => skip_line(&mut chars),
+            '/' if next == Some('/') => skip_line(&mut chars),
+            '/' if next == Some('*') => {
+                chars.next();
+                let mut prev = None;
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    if prev == Some('*') && c == '/' {
+                        break;
+                    }
+                    prev = Some(c);
+                }
+                text.push(' ');
+            }
+            ';' => {
+                if let Some(line) = start_line.take() {
+                    statements.push(ScriptStatement {
+                        text: text.trim().to_owned(),
+                        line,
+                    });
+                }
+                text.clear();
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                } else if !c.is_whitespace() && start_line.is_none() {
+                    start_line = Some(line);
+                }
+                if matches!(c, '"' | '\'' | '`') {
+                    quote = Some(c);
+                }
+                text.push(c);
+            }
+        }
+    }
+    if let Some(line) = start_line {
+        statements.push(ScriptStatement {
+            text: text.trim().to_owned(),
+            line,
+        });
+    }
+    statements
+}
+
+/// Skip to the end of the line, keeping the newline itself.
+fn skip_line(chars: &mut core::iter::Peekable<core::str::Chars<'_>>) {
+    while chars.next_if(|c| *c != '\n').is_some() {}
+}
+
+/// Returns the space of a `USE space` statement.
+pub(crate) fn used_space(stmt: &str) -> Option<&str> {
+    let stmt = stmt.trim();
+    let keyword = stmt.get(..3)?;
+    let rest = stmt[3..].trim();
+    if !keyword.eq_ignore_ascii_case("USE") || !stmt[3..].starts_with(char::is_whitespace) {
+        return None;
+    }
+    let space = rest.trim_end_matches(';').trim();
+    let space = space
+        .strip_prefix('`')
+        .and_then(|v| v.strip_suffix('`'))
+        .unwrap_or(space);
+    (!space.is_empty()).then_some(space)
+}
+
+/// A tag or an edge type changed by a DDL statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SchemaStatement<'a> {
+    /// `CREATE` or `ALTER` of `TAG` or `EDGE`, and the name
+    Changed(&'static str, &'a str),
+    /// `DROP` of `TAG` or `EDGE`, and the name
+    Dropped(&'static str, &'a str),
+}
+
+/// Returns the tag or the edge type a `CREATE`, `ALTER` or `DROP` statement
+/// changes. Indexes and anything else are `None`.
+pub(crate) fn schema_statement(stmt: &str) -> Option<SchemaStatement<'_>> {
+    let (verb, rest) = next_word(stmt);
+    let (kind, mut rest) = next_word(rest);
+    let keyword = match kind.to_ascii_uppercase().as_str() {
+        "TAG" => "TAG",
+        "EDGE" => "EDGE",
+        _ => return None,
+    };
+    let verb = verb.to_ascii_uppercase();
+    let guard: &[&str] = match verb.as_str() {
+        "CREATE" => &["IF", "NOT", "EXISTS"],
+        "DROP" => &["IF", "EXISTS"],
+        "ALTER" => &[],
+        _ => return None,
+    };
+    if matches!(guard.first(), Some(v) if next_word(rest).0.eq_ignore_ascii_case(v)) {
+        for _ in guard {
+            rest = next_word(rest).1;
+        }
+    }
+
+    let rest = rest.trim_start();
+    let name = match rest.strip_prefix('`') {
+        Some(quoted) => &quoted[..quoted.find('`')?],
+        None => match next_word(rest).0 {
+            name if name.eq_ignore_ascii_case("INDEX") => return None,
+            name => name,
+        },
+    };
+    if name.is_empty() {
+        return None;
+    }
+    Some(match verb.as_str() {
+        "DROP" => SchemaStatement::Dropped(keyword, name),
+        _ => SchemaStatement::Changed(keyword, name),
+    })
+}
+
+/// Split off the leading word of identifier characters.
+fn next_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    let end = s
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(s.len());
+    s.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_read_only() {
+        assert!(is_read_only("MATCH (v:player) RETURN v LIMIT 10;"));
+        assert!(is_read_only(
+            "  go FROM \"player100\" OVER follow YIELD dst(edge)"
+        ));
+        assert!(is_read_only(
+            "GO FROM \"a\" OVER follow YIELD dst(edge) AS id | FETCH PROP ON player $-.id YIELD properties(vertex)"
+        ));
+        assert!(is_read_only("USE test; SHOW TAGS;"));
+        assert!(is_read_only(
+            "LOOKUP ON player WHERE player.name == \"a;DELETE\" || player.age > 3 YIELD id(vertex)"
+        ));
+
+        assert!(!is_read_only(""));
+        assert!(!is_read_only(
+            "INSERT VERTEX player(name) VALUES \"a\":(\"b\");"
+        ));
+        assert!(!is_read_only("USE test; DELETE VERTEX \"a\";"));
+        assert!(!is_read_only(
+            "GO FROM \"a\" OVER follow YIELD dst(edge) AS id | DELETE VERTEX $-.id"
+        ));
+        assert!(!is_read_only(
+            "PROFILE INSERT VERTEX player(name) VALUES \"a\":(\"b\")"
+        ));
+    }
+
+    #[test]
+    fn test_split_script() {
+        let script = r#"
+# Schema of the test space
+CREATE SPACE IF NOT EXISTS test(vid_type=FIXED_STRING(32)); // trailing comment
+USE test;
+
+/* multi-line
+   comment; with a semicolon */
+CREATE TAG player(name string, age int);
+INSERT VERTEX player(name, age) VALUES "a;b":("it's; \"quoted\"", 1);
+MATCH (a)-->(b)<--(c) RETURN b; YIELD 1;
+;
+MATCH (v:player) RETURN v
+"#;
+        let statements = split_script(script);
+        assert_eq!(
+            statements,
+            vec![
+                ScriptStatement {
+                    text: "CREATE SPACE IF NOT EXISTS test(vid_type=FIXED_STRING(32))".to_owned(),
+                    line: 3
+                },
+                ScriptStatement {
+                    text: "USE test".to_owned(),
+                    line: 4
+                },
+                ScriptStatement {
+                    text: "CREATE TAG player(name string, age int)".to_owned(),
+                    line: 8
+                },
+                ScriptStatement {
+                    text: r#"INSERT VERTEX player(name, age) VALUES "a;b":("it's; \"quoted\"", 1)"#
+                        .to_owned(),
+                    line: 9
+                },
+                ScriptStatement {
+                    text: "MATCH (a)-->(b)<--(c) RETURN b".to_owned(),
+                    line: 10
+                },
+                ScriptStatement {
+                    text: "YIELD 1".to_owned(),
+                    line: 10
+                },
+                ScriptStatement {
+                    text: "MATCH (v:player) RETURN v".to_owned(),
+                    line: 12
+                },
+            ]
+        );
+
+        assert!(split_script("").is_empty());
+        assert!(split_script(" # comment\n// comment\n;").is_empty());
+    }
+
+    #[test]
+    fn test_used_space() {
+        assert_eq!(used_space("USE test"), Some("test"));
+        assert_eq!(used_space(" use `my space`;"), Some("my space"));
+        assert_eq!(used_space("USER"), None);
+        assert_eq!(used_space("USE"), None);
+        assert_eq!(used_space("SHOW SPACES"), None);
+    }
+
+    #[test]
+    fn test_schema_statement() {
+        assert_eq!(
+            schema_statement("CREATE TAG IF NOT EXISTS player(name string)"),
+            Some(SchemaStatement::Changed("TAG", "player"))
+        );
+        assert_eq!(
+            schema_statement("create edge `follow me` (degree int)"),
+            Some(SchemaStatement::Changed("EDGE", "follow me"))
+        );
+        assert_eq!(
+            schema_statement("ALTER TAG player ADD (age int)"),
+            Some(SchemaStatement::Changed("TAG", "player"))
+        );
+        assert_eq!(
+            schema_statement("DROP EDGE IF EXISTS follow"),
+            Some(SchemaStatement::Dropped("EDGE", "follow"))
+        );
+        assert_eq!(
+            schema_statement("CREATE TAG INDEX player_index ON player()"),
+            None
+        );
+        assert_eq!(schema_statement("CREATE SPACE test(vid_type=INT64)"), None);
+        assert_eq!(schema_statement("DROP TAG"), None);
+    }
+}
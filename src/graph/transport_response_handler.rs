@@ -7,7 +7,8 @@ use fbthrift::{
 };
 use fbthrift_transport_response_handler::ResponseHandler;
 use nebula_fbthrift_graph_v3::services::graph_service::{
-    AuthenticateExn, ExecuteExn, ExecuteJsonExn, SignoutExn,
+    AuthenticateExn, ExecuteExn, ExecuteJsonExn, ExecuteWithParameterExn, SignoutExn,
+    VerifyClientVersionExn,
 };
 
 #[derive(Clone)]
@@ -55,7 +56,9 @@ impl ResponseHandler for GraphTransportResponseHandler {
                 Ok(Some(res_buf))
             }
             b"GraphService.execute" => Ok(None),
+            b"GraphService.executeWithParameter" => Ok(None),
             b"GraphService.executeJson" => Ok(None),
+            b"GraphService.verifyClientVersion" => Ok(None),
             _ => Err(IoError::new(
                 IoErrorKind::Other,
                 format!("Unknown method {}", String::from_utf8_lossy(fn_name)),
@@ -74,7 +77,9 @@ impl ResponseHandler for GraphTransportResponseHandler {
             b"authenticate" => {}
             b"signout" => unreachable!(),
             b"execute" => {}
+            b"executeWithParameter" => {}
             b"executeJson" => {}
+            b"verifyClientVersion" => {}
             _ => return Ok(None),
         };
 
@@ -93,12 +98,24 @@ impl ResponseHandler for GraphTransportResponseHandler {
                             Err(_) => return Ok(None),
                         };
                     }
+                    b"executeWithParameter" => {
+                        let _: ExecuteWithParameterExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
                     b"executeJson" => {
                         let _: ExecuteJsonExn = match Deserialize::read(&mut des) {
                             Ok(v) => v,
                             Err(_) => return Ok(None),
                         };
                     }
+                    b"verifyClientVersion" => {
+                        let _: VerifyClientVersionExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
                     _ => unreachable!(),
                 };
             }
@@ -144,6 +161,14 @@ mod tests {
             )?,
             None
         );
+        assert_eq!(
+            handler.try_make_static_response_bytes(
+                b"GraphService",
+                b"GraphService.executeWithParameter",
+                b"FOO"
+            )?,
+            None
+        );
         assert_eq!(
             handler.try_make_static_response_bytes(
                 b"GraphService",
@@ -152,6 +177,14 @@ mod tests {
             )?,
             None
         );
+        assert_eq!(
+            handler.try_make_static_response_bytes(
+                b"GraphService",
+                b"GraphService.verifyClientVersion",
+                b"FOO"
+            )?,
+            None
+        );
         match handler.try_make_static_response_bytes(b"GraphService", b"GraphService.foo", b"FOO") {
             Ok(_) => panic!(),
             Err(err) => {
@@ -3,7 +3,8 @@ pub mod graph;
 
 #[cfg(feature = "graph")]
 pub use graph::{
-    GraphTransportResponseHandler, SingleConnSession, SingleConnSessionConf,
+    ConfParseError, ConnectionPool, ConnectionPoolConf, GraphTransportResponseHandler,
+    ReauthPolicy, SessionPool, SessionPoolManager, SingleConnSession, SingleConnSessionConf,
     SingleConnSessionError, SingleConnSessionManager,
 };
 
@@ -17,13 +18,31 @@ pub mod storage;
 #[cfg(feature = "storage")]
 pub use storage::{StorageClient, StorageClientError, StorageTransportResponseHandler};
 
+pub mod ngql;
+
+// Lets the code generated by `rust-nebula-derive` refer to `::rust_nebula` in tests.
+#[cfg(all(test, feature = "derive"))]
+extern crate self as rust_nebula;
+
+pub mod stream;
+pub use stream::NebulaStream;
+
+pub mod tls;
+pub use tls::TlsConfig;
+
 pub(crate) mod data_deserializer;
 pub(crate) mod dataset_wrapper;
 pub(crate) mod value_wrapper;
 
 pub use dataset_wrapper::DataSetError;
 
-use nebula_fbthrift_graph_v3::dependencies::common;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+pub use nebula_fbthrift_graph_v3::dependencies::common;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct HostAddress {
@@ -39,6 +58,14 @@ impl HostAddress {
         }
     }
 
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
     pub fn to_string(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
@@ -8,10 +8,7 @@ use fbthrift::{
     BinaryProtocol, BufMutExt, Framing, FramingDecoded, FramingEncodedFinal,
     NonthrowingFunctionError, ProtocolEncoded, Transport,
 };
-use fbthrift_transport::{
-    impl_tokio::{TokioSleep, TokioTcpStream},
-    AsyncTransport, AsyncTransportConfiguration,
-};
+use fbthrift_transport::{impl_tokio::TokioSleep, AsyncTransport, AsyncTransportConfiguration};
 use nebula_fbthrift_meta_v3::{
     client::{MetaService, MetaServiceImpl},
     errors::meta_service::{
@@ -26,18 +23,18 @@ use nebula_fbthrift_meta_v3::{
     EdgeItem, HostItem, IdName, PartItem, Schema, TagItem, ID,
 };
 
-use crate::MetaTransportResponseHandler;
 use crate::{
-    common::{HostAddr, PartitionID},
+    common::{ErrorCode, HostAddr, PartitionID},
     HostAddress,
 };
+use crate::{stream, MetaTransportResponseHandler, NebulaStream, TlsConfig};
 
 use super::metacache::{MetaCache, SpaceCache};
 
 //
 //
 //
-struct MetaConnection<T = AsyncTransport<TokioTcpStream, TokioSleep, MetaTransportResponseHandler>>
+struct MetaConnection<T = AsyncTransport<NebulaStream, TokioSleep, MetaTransportResponseHandler>>
 where
     T: Transport + Framing<DecBuf = Cursor<Bytes>>,
     Bytes: Framing<DecBuf = FramingDecoded<T>>,
@@ -124,13 +121,14 @@ where
 }
 
 impl MetaConnection {
-    async fn new(addr: &str) -> Result<Self, MetaClientError> {
-        let transport = AsyncTransport::with_tokio_tcp_connect(
-            addr,
+    async fn new(addr: &HostAddress, tls: Option<&TlsConfig>) -> Result<Self, MetaClientError> {
+        let stream = stream::connect(addr.host(), addr.port(), tls)
+            .await
+            .map_err(MetaClientError::CreateTransportError)?;
+        let transport = AsyncTransport::new(
+            stream,
             AsyncTransportConfiguration::new(MetaTransportResponseHandler),
-        )
-        .await
-        .map_err(MetaClientError::CreateTransportError)?;
+        );
         Ok(Self {
             service: MetaServiceImpl::<BinaryProtocol, _>::new(transport),
         })
@@ -140,7 +138,7 @@ impl MetaConnection {
 //
 //
 //
-pub struct MetaClient<T = AsyncTransport<TokioTcpStream, TokioSleep, MetaTransportResponseHandler>>
+pub struct MetaClient<T = AsyncTransport<NebulaStream, TokioSleep, MetaTransportResponseHandler>>
 where
     T: Transport + Framing<DecBuf = std::io::Cursor<Bytes>>,
     Bytes: Framing<DecBuf = FramingDecoded<T>>,
@@ -158,26 +156,32 @@ where
     Bytes: Framing<DecBuf = FramingDecoded<T>>,
     ProtocolEncoded<BinaryProtocol>: BufMutExt<Final = FramingEncodedFinal<T>>,
 {
-    pub fn new_with_transport(maddr: &Vec<HostAddress>, transport: T) -> Self {
+    pub fn new_with_transport(maddr: &[HostAddress], transport: T) -> Self {
         Self {
-            maddr: maddr.clone(),
+            maddr: maddr.to_vec(),
             meta_cache: MetaCache::new(),
             connection: MetaConnection::new_with_transport(transport),
         }
     }
 
-    async fn list_spaces(&self) -> Result<Vec<IdName>, ListSpacesError> {
-        match self.connection.list_spaces().await {
-            Ok(resp) => Ok(resp.spaces),
-            Err(err) => Err(err),
-        }
+    async fn list_spaces(&self) -> Result<Vec<IdName>, MetaClientError> {
+        let resp = self
+            .connection
+            .list_spaces()
+            .await
+            .map_err(MetaClientError::LoadError)?;
+        check_code(resp.code, &resp.leader)?;
+        Ok(resp.spaces)
     }
 
-    async fn list_hosts(&self) -> Result<Vec<HostItem>, ListHostsError> {
-        match self.connection.list_hosts().await {
-            Ok(resp) => Ok(resp.hosts),
-            Err(err) => Err(err),
-        }
+    async fn list_hosts(&self) -> Result<Vec<HostItem>, MetaClientError> {
+        let resp = self
+            .connection
+            .list_hosts()
+            .await
+            .map_err(MetaClientError::LoadError)?;
+        check_code(resp.code, &resp.leader)?;
+        Ok(resp.hosts)
     }
 
     #[allow(unused)]
@@ -185,35 +189,57 @@ where
         &self,
         space_id: i32,
         part_ids: Vec<i32>,
-    ) -> Result<Vec<PartItem>, ListPartsError> {
-        match self.connection.list_parts(space_id, part_ids).await {
-            Ok(resp) => Ok(resp.parts),
-            Err(err) => Err(err),
-        }
+    ) -> Result<Vec<PartItem>, MetaClientError> {
+        let resp = self
+            .connection
+            .list_parts(space_id, part_ids)
+            .await
+            .map_err(MetaClientError::LoadError)?;
+        check_code(resp.code, &resp.leader)?;
+        Ok(resp.parts)
     }
 
-    async fn list_tags(&self, space_id: i32) -> Result<Vec<TagItem>, ListTagsError> {
-        match self.connection.list_tags(space_id).await {
-            Ok(resp) => Ok(resp.tags),
-            Err(err) => Err(err),
-        }
+    async fn list_tags(&self, space_id: i32) -> Result<Vec<TagItem>, MetaClientError> {
+        let resp = self
+            .connection
+            .list_tags(space_id)
+            .await
+            .map_err(MetaClientError::LoadError)?;
+        check_code(resp.code, &resp.leader)?;
+        Ok(resp.tags)
     }
 
-    async fn list_edges(&self, space_id: i32) -> Result<Vec<EdgeItem>, ListEdgesError> {
-        match self.connection.list_edges(space_id).await {
-            Ok(resp) => Ok(resp.edges),
-            Err(err) => Err(err),
-        }
+    async fn list_edges(&self, space_id: i32) -> Result<Vec<EdgeItem>, MetaClientError> {
+        let resp = self
+            .connection
+            .list_edges(space_id)
+            .await
+            .map_err(MetaClientError::LoadError)?;
+        check_code(resp.code, &resp.leader)?;
+        Ok(resp.edges)
     }
 
     async fn get_parts_alloc(
         &self,
         space_id: i32,
-    ) -> Result<BTreeMap<PartitionID, Vec<HostAddr>>, GetPartsAllocError> {
-        match self.connection.get_parts_alloc(space_id).await {
-            Ok(resp) => Ok(resp.parts),
-            Err(err) => Err(err),
-        }
+    ) -> Result<BTreeMap<PartitionID, Vec<HostAddr>>, MetaClientError> {
+        let resp = self
+            .connection
+            .get_parts_alloc(space_id)
+            .await
+            .map_err(MetaClientError::LoadError)?;
+        check_code(resp.code, &resp.leader)?;
+        Ok(resp.parts)
+    }
+}
+
+/// metad answers with an error code instead of failing the call, e.g.
+/// `E_LEADER_CHANGED` from a follower.
+fn check_code(code: ErrorCode, leader: &HostAddr) -> Result<(), MetaClientError> {
+    if code == ErrorCode::SUCCEEDED {
+        Ok(())
+    } else {
+        Err(MetaClientError::ResponseError(code, leader.clone()))
     }
 }
 
@@ -223,11 +249,17 @@ where
     Bytes: Framing<DecBuf = FramingDecoded<T>>,
     ProtocolEncoded<BinaryProtocol>: BufMutExt<Final = FramingEncodedFinal<T>>,
 {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(hosts = ?self.maddr, spaces = tracing::field::Empty),
+            err(Display)
+        )
+    )]
     async fn load_all(&mut self) -> Result<(), MetaClientError> {
-        let spaces = self
-            .list_spaces()
-            .await
-            .map_err(MetaClientError::LoadError)?;
+        let spaces = self.list_spaces().await?;
         let mut space_id_names = HashMap::new();
         let mut space_caches = HashMap::new();
 
@@ -237,25 +269,18 @@ where
             } else {
                 0
             };
+            #[cfg(feature = "tracing")]
+            tracing::trace!(space = %String::from_utf8_lossy(&space.name), space_id, "load space");
             let mut space_cache = SpaceCache {
                 space_id,
                 space_name: space.name,
                 tag_items: HashMap::new(),
                 edge_items: HashMap::new(),
-                parts_alloc: self
-                    .get_parts_alloc(space_id)
-                    .await
-                    .map_err(MetaClientError::LoadError)?,
+                parts_alloc: self.get_parts_alloc(space_id).await?,
             };
 
-            let tags = self
-                .list_tags(space_id)
-                .await
-                .map_err(MetaClientError::LoadError)?;
-            let edges = self
-                .list_edges(space_id)
-                .await
-                .map_err(MetaClientError::LoadError)?;
+            let tags = self.list_tags(space_id).await?;
+            let edges = self.list_edges(space_id).await?;
 
             for tag in tags {
                 let tag_name = tag.tag_name.to_vec();
@@ -279,10 +304,7 @@ where
             space_caches.insert(space_cache.space_name.clone(), space_cache);
         }
 
-        let hosts = self
-            .list_hosts()
-            .await
-            .map_err(MetaClientError::LoadError)?;
+        let hosts = self.list_hosts().await?;
         let mut storage_addrs = vec![];
         for host_item in hosts {
             storage_addrs.push(host_item.hostAddr);
@@ -297,6 +319,8 @@ where
             storage_leader.insert(space_name.clone(), host_addr_map);
         }
 
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("spaces", space_caches.len());
         self.meta_cache.space_id_names = space_id_names;
         self.meta_cache.space_caches = space_caches;
         self.meta_cache.storage_addrs = Some(storage_addrs);
@@ -457,11 +481,23 @@ where
 }
 
 impl MetaClient {
-    pub async fn new(maddr: &Vec<HostAddress>) -> Result<Self, MetaClientError> {
+    pub async fn new(maddr: &[HostAddress]) -> Result<Self, MetaClientError> {
+        Ok(Self {
+            connection: MetaConnection::new(&maddr[0], None).await?,
+            meta_cache: MetaCache::new(),
+            maddr: maddr.to_vec(),
+        })
+    }
+
+    /// Connect to the metad servers with TLS.
+    pub async fn new_with_tls(
+        maddr: &[HostAddress],
+        tls: TlsConfig,
+    ) -> Result<Self, MetaClientError> {
         Ok(Self {
-            connection: MetaConnection::new(&maddr[0].to_string()).await?,
+            connection: MetaConnection::new(&maddr[0], Some(&tls)).await?,
             meta_cache: MetaCache::new(),
-            maddr: maddr.clone(),
+            maddr: maddr.to_vec(),
         })
     }
 }
@@ -473,6 +509,8 @@ use std::fmt;
 pub enum MetaClientError {
     CreateTransportError(std::io::Error),
     LoadError(NonthrowingFunctionError),
+    /// metad answered with an error code, and the address of its leader
+    ResponseError(ErrorCode, HostAddr),
     SpaceNotFoundError(Vec<u8>),
     TagNotFoundError(Vec<u8>),
     EdgeNotFoundError(Vec<u8>),
@@ -487,6 +525,13 @@ impl fmt::Display for MetaClientError {
             Self::LoadError(error) => {
                 write!(f, "Space not found: {:?}", error)
             }
+            Self::ResponseError(code, leader) => {
+                write!(
+                    f,
+                    "ResponseError {:?} leader: {}:{}",
+                    code, leader.host, leader.port
+                )
+            }
             Self::SpaceNotFoundError(space_id) => {
                 write!(f, "Space not found: {:?}", space_id)
             }
@@ -0,0 +1,1206 @@
+//! Builders of nGQL statements.
+//!
+//! Names of tags, edge types and properties are quoted with `identifier`, and
+//! values are rendered with `ToNgqlLiteral`, so they can't break the statement.
+//! Conditions and `YIELD`/`RETURN` columns are nGQL expressions and are passed
+//! through as is.
+//!
+//! Every builder implements `Display`, so a statement is run with
+//! `session.query(&stmt.to_string())`.
+//! ## Example
+//! ```
+//! use rust_nebula::ngql::InsertVertex;
+//!
+//! let stmt = InsertVertex::new("player", &["name", "age"])
+//!     .value("player100", &[&"Tim Duncan", &42])
+//!     .to_string();
+//! assert_eq!(
+//!     stmt,
+//!     r#"INSERT VERTEX `player`(`name`, `age`) VALUES "player100":("Tim Duncan", 42)"#
+//! );
+//! ```
+
+use core::fmt;
+
+use super::{
+    literal::{identifier, ToNgqlLiteral},
+    model::PropDef,
+};
+
+fn identifiers(names: &[&str]) -> String {
+    names
+        .iter()
+        .map(|v| identifier(v))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn literals(values: &[&dyn ToNgqlLiteral]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_ngql_literal())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn edge_key(src: &dyn ToNgqlLiteral, dst: &dyn ToNgqlLiteral, rank: Option<i64>) -> String {
+    let mut key = format!("{}->{}", src.to_ngql_literal(), dst.to_ngql_literal());
+    if let Some(rank) = rank {
+        key.push_str(&format!("@{rank}"));
+    }
+    key
+}
+
+fn write_yield(f: &mut fmt::Formatter, yields: &[String], default: &str) -> fmt::Result {
+    if yields.is_empty() {
+        write!(f, " YIELD {default}")
+    } else {
+        write!(f, " YIELD {}", yields.join(", "))
+    }
+}
+
+//
+//
+//
+/// `INSERT VERTEX`, with one or more rows.
+/// ## Panics
+/// Formatting panics if no row was added, as `VALUES` can't be empty.
+#[derive(Debug, Clone)]
+pub struct InsertVertex {
+    if_not_exists: bool,
+    ignore_existed_index: bool,
+    tags: Vec<String>,
+    rows: Vec<String>,
+}
+
+impl InsertVertex {
+    pub fn new(tag: &str, props: &[&str]) -> Self {
+        Self {
+            if_not_exists: false,
+            ignore_existed_index: false,
+            tags: vec![],
+            rows: vec![],
+        }
+        .tag(tag, props)
+    }
+
+    /// Insert another tag of the same vertices. The values of a row are the
+    /// properties of all tags in order.
+    pub fn tag(mut self, tag: &str, props: &[&str]) -> Self {
+        self.tags
+            .push(format!("{}({})", identifier(tag), identifiers(props)));
+        self
+    }
+
+    pub fn if_not_exists(mut self) -> Self {
+        self.if_not_exists = true;
+        self
+    }
+
+    pub fn ignore_existed_index(mut self) -> Self {
+        self.ignore_existed_index = true;
+        self
+    }
+
+    pub fn value(mut self, vid: impl ToNgqlLiteral, values: &[&dyn ToNgqlLiteral]) -> Self {
+        self.rows
+            .push(format!("{}:({})", vid.to_ngql_literal(), literals(values)));
+        self
+    }
+
+    /// Returns whether no row was added yet.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+impl fmt::Display for InsertVertex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        assert!(!self.is_empty(), "InsertVertex needs at least one row");
+        write!(f, "INSERT VERTEX ")?;
+        if self.if_not_exists {
+            write!(f, "IF NOT EXISTS ")?;
+        }
+        if self.ignore_existed_index {
+            write!(f, "IGNORE_EXISTED_INDEX ")?;
+        }
+        write!(
+            f,
+            "{} VALUES {}",
+            self.tags.join(", "),
+            self.rows.join(", ")
+        )
+    }
+}
+
+/// `INSERT EDGE`, with one or more rows.
+/// ## Panics
+/// Formatting panics if no row was added, as `VALUES` can't be empty.
+#[derive(Debug, Clone)]
+pub struct InsertEdge {
+    if_not_exists: bool,
+    ignore_existed_index: bool,
+    edge: String,
+    rows: Vec<String>,
+}
+
+impl InsertEdge {
+    pub fn new(edge: &str, props: &[&str]) -> Self {
+        Self {
+            if_not_exists: false,
+            ignore_existed_index: false,
+            edge: format!("{}({})", identifier(edge), identifiers(props)),
+            rows: vec![],
+        }
+    }
+
+    pub fn if_not_exists(mut self) -> Self {
+        self.if_not_exists = true;
+        self
+    }
+
+    pub fn ignore_existed_index(mut self) -> Self {
+        self.ignore_existed_index = true;
+        self
+    }
+
+    /// Add an edge with the default rank 0.
+    pub fn value(
+        self,
+        src: impl ToNgqlLiteral,
+        dst: impl ToNgqlLiteral,
+        values: &[&dyn ToNgqlLiteral],
+    ) -> Self {
+        self.push_row(edge_key(&src, &dst, None), values)
+    }
+
+    pub fn value_with_rank(
+        self,
+        src: impl ToNgqlLiteral,
+        dst: impl ToNgqlLiteral,
+        rank: i64,
+        values: &[&dyn ToNgqlLiteral],
+    ) -> Self {
+        self.push_row(edge_key(&src, &dst, Some(rank)), values)
+    }
+
+    /// Returns whether no row was added yet.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    fn push_row(mut self, key: String, values: &[&dyn ToNgqlLiteral]) -> Self {
+        self.rows.push(format!("{key}:({})", literals(values)));
+        self
+    }
+}
+
+impl fmt::Display for InsertEdge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        assert!(!self.is_empty(), "InsertEdge needs at least one row");
+        write!(f, "INSERT EDGE ")?;
+        if self.if_not_exists {
+            write!(f, "IF NOT EXISTS ")?;
+        }
+        if self.ignore_existed_index {
+            write!(f, "IGNORE_EXISTED_INDEX ")?;
+        }
+        write!(f, "{} VALUES {}", self.edge, self.rows.join(", "))
+    }
+}
+
+//
+//
+//
+/// `UPDATE` or `UPSERT` of a vertex or an edge.
+#[derive(Debug, Clone)]
+pub struct Update {
+    upsert: bool,
+    target: String,
+    sets: Vec<String>,
+    when: Option<String>,
+    yields: Vec<String>,
+}
+
+impl Update {
+    pub fn vertex(tag: &str, vid: impl ToNgqlLiteral) -> Self {
+        Self::new(
+            false,
+            format!("VERTEX ON {} {}", identifier(tag), vid.to_ngql_literal()),
+        )
+    }
+
+    pub fn edge(
+        edge: &str,
+        src: impl ToNgqlLiteral,
+        dst: impl ToNgqlLiteral,
+        rank: Option<i64>,
+    ) -> Self {
+        Self::new(
+            false,
+            format!(
+                "EDGE ON {} {}",
+                identifier(edge),
+                edge_key(&src, &dst, rank)
+            ),
+        )
+    }
+
+    /// Like `vertex`, but the vertex is inserted if it doesn't exist.
+    pub fn upsert_vertex(tag: &str, vid: impl ToNgqlLiteral) -> Self {
+        Self {
+            upsert: true,
+            ..Self::vertex(tag, vid)
+        }
+    }
+
+    /// Like `edge`, but the edge is inserted if it doesn't exist.
+    pub fn upsert_edge(
+        edge: &str,
+        src: impl ToNgqlLiteral,
+        dst: impl ToNgqlLiteral,
+        rank: Option<i64>,
+    ) -> Self {
+        Self {
+            upsert: true,
+            ..Self::edge(edge, src, dst, rank)
+        }
+    }
+
+    fn new(upsert: bool, target: String) -> Self {
+        Self {
+            upsert,
+            target,
+            sets: vec![],
+            when: None,
+            yields: vec![],
+        }
+    }
+
+    pub fn set(mut self, prop: &str, value: impl ToNgqlLiteral) -> Self {
+        self.sets.push(format!(
+            "{} = {}",
+            identifier(prop),
+            value.to_ngql_literal()
+        ));
+        self
+    }
+
+    /// Set a property to an expression, e.g. `set_expr("age", "age + 1")`.
+    pub fn set_expr(mut self, prop: &str, expr: &str) -> Self {
+        self.sets.push(format!("{} = {expr}", identifier(prop)));
+        self
+    }
+
+    pub fn when(mut self, cond: &str) -> Self {
+        self.when = Some(cond.to_owned());
+        self
+    }
+
+    pub fn yield_(mut self, expr: &str) -> Self {
+        self.yields.push(expr.to_owned());
+        self
+    }
+}
+
+impl fmt::Display for Update {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keyword = if self.upsert { "UPSERT" } else { "UPDATE" };
+        write!(f, "{keyword} {} SET {}", self.target, self.sets.join(", "))?;
+        if let Some(cond) = &self.when {
+            write!(f, " WHEN {cond}")?;
+        }
+        if !self.yields.is_empty() {
+            write!(f, " YIELD {}", self.yields.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+//
+//
+//
+/// `DELETE VERTEX`
+#[derive(Debug, Clone)]
+pub struct DeleteVertex {
+    vids: Vec<String>,
+    with_edge: bool,
+}
+
+impl DeleteVertex {
+    pub fn new<V: ToNgqlLiteral>(vids: &[V]) -> Self {
+        Self {
+            vids: vids.iter().map(|v| v.to_ngql_literal()).collect(),
+            with_edge: false,
+        }
+    }
+
+    /// Delete the edges of the vertices too.
+    pub fn with_edge(mut self) -> Self {
+        self.with_edge = true;
+        self
+    }
+}
+
+impl fmt::Display for DeleteVertex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DELETE VERTEX {}", self.vids.join(", "))?;
+        if self.with_edge {
+            write!(f, " WITH EDGE")?;
+        }
+        Ok(())
+    }
+}
+
+/// `DELETE EDGE`
+#[derive(Debug, Clone)]
+pub struct DeleteEdge {
+    edge: String,
+    keys: Vec<String>,
+}
+
+impl DeleteEdge {
+    pub fn new(edge: &str) -> Self {
+        Self {
+            edge: identifier(edge),
+            keys: vec![],
+        }
+    }
+
+    pub fn edge(
+        mut self,
+        src: impl ToNgqlLiteral,
+        dst: impl ToNgqlLiteral,
+        rank: Option<i64>,
+    ) -> Self {
+        self.keys.push(edge_key(&src, &dst, rank));
+        self
+    }
+}
+
+impl fmt::Display for DeleteEdge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DELETE EDGE {} {}", self.edge, self.keys.join(", "))
+    }
+}
+
+/// `DELETE TAG`, all tags are deleted if `tags` is empty.
+#[derive(Debug, Clone)]
+pub struct DeleteTag {
+    tags: String,
+    vids: Vec<String>,
+}
+
+impl DeleteTag {
+    pub fn new<V: ToNgqlLiteral>(tags: &[&str], vids: &[V]) -> Self {
+        Self {
+            tags: match tags {
+                [] => "*".to_owned(),
+                tags => identifiers(tags),
+            },
+            vids: vids.iter().map(|v| v.to_ngql_literal()).collect(),
+        }
+    }
+}
+
+impl fmt::Display for DeleteTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DELETE TAG {} FROM {}", self.tags, self.vids.join(", "))
+    }
+}
+
+//
+//
+//
+/// `FETCH PROP ON` vertices, all tags are fetched if `tags` is empty.
+/// Yields `properties(vertex)` by default.
+#[derive(Debug, Clone)]
+pub struct FetchVertices {
+    tags: String,
+    vids: Vec<String>,
+    yields: Vec<String>,
+}
+
+impl FetchVertices {
+    pub fn new<V: ToNgqlLiteral>(tags: &[&str], vids: &[V]) -> Self {
+        Self {
+            tags: match tags {
+                [] => "*".to_owned(),
+                tags => identifiers(tags),
+            },
+            vids: vids.iter().map(|v| v.to_ngql_literal()).collect(),
+            yields: vec![],
+        }
+    }
+
+    pub fn yield_(mut self, expr: &str) -> Self {
+        self.yields.push(expr.to_owned());
+        self
+    }
+}
+
+impl fmt::Display for FetchVertices {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FETCH PROP ON {} {}", self.tags, self.vids.join(", "))?;
+        write_yield(f, &self.yields, "properties(vertex)")
+    }
+}
+
+/// `FETCH PROP ON` edges, yields `properties(edge)` by default.
+#[derive(Debug, Clone)]
+pub struct FetchEdges {
+    edge: String,
+    keys: Vec<String>,
+    yields: Vec<String>,
+}
+
+impl FetchEdges {
+    pub fn new(edge: &str) -> Self {
+        Self {
+            edge: identifier(edge),
+            keys: vec![],
+            yields: vec![],
+        }
+    }
+
+    pub fn edge(
+        mut self,
+        src: impl ToNgqlLiteral,
+        dst: impl ToNgqlLiteral,
+        rank: Option<i64>,
+    ) -> Self {
+        self.keys.push(edge_key(&src, &dst, rank));
+        self
+    }
+
+    pub fn yield_(mut self, expr: &str) -> Self {
+        self.yields.push(expr.to_owned());
+        self
+    }
+}
+
+impl fmt::Display for FetchEdges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FETCH PROP ON {} {}", self.edge, self.keys.join(", "))?;
+        write_yield(f, &self.yields, "properties(edge)")
+    }
+}
+
+//
+//
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Out,
+    In,
+    Both,
+}
+
+/// `GO`, over all edge types if none is given. Yields `dst(edge)` by default.
+#[derive(Debug, Clone)]
+pub struct Go {
+    steps: Option<(Option<u32>, u32)>,
+    vids: Vec<String>,
+    edges: Vec<String>,
+    direction: Direction,
+    cond: Option<String>,
+    distinct: bool,
+    yields: Vec<String>,
+}
+
+impl Go {
+    pub fn new<V: ToNgqlLiteral>(vids: &[V]) -> Self {
+        Self {
+            steps: None,
+            vids: vids.iter().map(|v| v.to_ngql_literal()).collect(),
+            edges: vec![],
+            direction: Direction::Out,
+            cond: None,
+            distinct: false,
+            yields: vec![],
+        }
+    }
+
+    /// `GO n STEPS`
+    pub fn steps(mut self, n: u32) -> Self {
+        self.steps = Some((None, n));
+        self
+    }
+
+    /// `GO m TO n STEPS`
+    pub fn steps_between(mut self, m: u32, n: u32) -> Self {
+        self.steps = Some((Some(m), n));
+        self
+    }
+
+    pub fn over(mut self, edge: &str) -> Self {
+        self.edges.push(identifier(edge));
+        self
+    }
+
+    pub fn reversely(mut self) -> Self {
+        self.direction = Direction::In;
+        self
+    }
+
+    pub fn bidirect(mut self) -> Self {
+        self.direction = Direction::Both;
+        self
+    }
+
+    pub fn where_(mut self, cond: &str) -> Self {
+        self.cond = Some(cond.to_owned());
+        self
+    }
+
+    pub fn distinct(mut self) -> Self {
+        self.distinct = true;
+        self
+    }
+
+    pub fn yield_(mut self, expr: &str) -> Self {
+        self.yields.push(expr.to_owned());
+        self
+    }
+}
+
+impl fmt::Display for Go {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GO ")?;
+        match self.steps {
+            Some((Some(m), n)) => write!(f, "{m} TO {n} STEPS ")?,
+            Some((None, n)) => write!(f, "{n} STEPS ")?,
+            None => {}
+        }
+        write!(f, "FROM {} OVER ", self.vids.join(", "))?;
+        if self.edges.is_empty() {
+            write!(f, "*")?;
+        } else {
+            write!(f, "{}", self.edges.join(", "))?;
+        }
+        match self.direction {
+            Direction::Out => {}
+            Direction::In => write!(f, " REVERSELY")?,
+            Direction::Both => write!(f, " BIDIRECT")?,
+        }
+        if let Some(cond) = &self.cond {
+            write!(f, " WHERE {cond}")?;
+        }
+        if self.distinct {
+            write!(f, " YIELD DISTINCT ")?;
+        } else {
+            write!(f, " YIELD ")?;
+        }
+        if self.yields.is_empty() {
+            write!(f, "dst(edge)")
+        } else {
+            write!(f, "{}", self.yields.join(", "))
+        }
+    }
+}
+
+//
+//
+//
+/// `LOOKUP ON` a tag or an edge type. Yields `id(vertex)` for tags and
+/// `src(edge), dst(edge), rank(edge)` for edges by default.
+#[derive(Debug, Clone)]
+pub struct Lookup {
+    name: String,
+    default_yield: &'static str,
+    cond: Option<String>,
+    yields: Vec<String>,
+}
+
+impl Lookup {
+    pub fn tag(tag: &str) -> Self {
+        Self::new(tag, "id(vertex)")
+    }
+
+    pub fn edge(edge: &str) -> Self {
+        Self::new(edge, "src(edge), dst(edge), rank(edge)")
+    }
+
+    fn new(name: &str, default_yield: &'static str) -> Self {
+        Self {
+            name: identifier(name),
+            default_yield,
+            cond: None,
+            yields: vec![],
+        }
+    }
+
+    pub fn where_(mut self, cond: &str) -> Self {
+        self.cond = Some(cond.to_owned());
+        self
+    }
+
+    pub fn yield_(mut self, expr: &str) -> Self {
+        self.yields.push(expr.to_owned());
+        self
+    }
+}
+
+impl fmt::Display for Lookup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LOOKUP ON {}", self.name)?;
+        if let Some(cond) = &self.cond {
+            write!(f, " WHERE {cond}")?;
+        }
+        write_yield(f, &self.yields, self.default_yield)
+    }
+}
+
+//
+//
+//
+/// A node of a `MATCH` pattern, e.g. `(v:player{name: "Tim Duncan"})`.
+#[derive(Debug, Clone, Default)]
+pub struct Node {
+    var: String,
+    labels: Vec<String>,
+    props: Vec<String>,
+}
+
+impl Node {
+    pub fn new(var: &str) -> Self {
+        Self {
+            var: var.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    pub fn label(mut self, tag: &str) -> Self {
+        self.labels.push(identifier(tag));
+        self
+    }
+
+    pub fn prop(mut self, name: &str, value: impl ToNgqlLiteral) -> Self {
+        self.props
+            .push(format!("{}: {}", identifier(name), value.to_ngql_literal()));
+        self
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}", self.var)?;
+        for label in &self.labels {
+            write!(f, ":{label}")?;
+        }
+        if !self.props.is_empty() {
+            write!(f, "{{{}}}", self.props.join(", "))?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// A relationship of a `MATCH` pattern, e.g. `[e:follow*1..3]`.
+#[derive(Debug, Clone, Default)]
+pub struct Rel {
+    var: String,
+    types: Vec<String>,
+    hops: Option<(u32, u32)>,
+    props: Vec<String>,
+}
+
+impl Rel {
+    pub fn new(var: &str) -> Self {
+        Self {
+            var: var.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// Match any of the edge types given.
+    pub fn edge_type(mut self, edge: &str) -> Self {
+        self.types.push(identifier(edge));
+        self
+    }
+
+    /// Variable length relationship `*min..max`
+    pub fn hops(mut self, min: u32, max: u32) -> Self {
+        self.hops = Some((min, max));
+        self
+    }
+
+    pub fn prop(mut self, name: &str, value: impl ToNgqlLiteral) -> Self {
+        self.props
+            .push(format!("{}: {}", identifier(name), value.to_ngql_literal()));
+        self
+    }
+}
+
+impl fmt::Display for Rel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}", self.var)?;
+        if !self.types.is_empty() {
+            write!(f, ":{}", self.types.join("|"))?;
+        }
+        if let Some((min, max)) = self.hops {
+            write!(f, "*{min}..{max}")?;
+        }
+        if !self.props.is_empty() {
+            write!(f, "{{{}}}", self.props.join(", "))?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// A path pattern of `MATCH`, e.g. `(v:player)-[e:follow]->(v2)`.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    path: String,
+}
+
+impl Pattern {
+    pub fn new(node: Node) -> Self {
+        Self {
+            path: node.to_string(),
+        }
+    }
+
+    /// `-[rel]->(node)`
+    pub fn out(self, rel: Rel, node: Node) -> Self {
+        self.push(Direction::Out, rel, node)
+    }
+
+    /// `<-[rel]-(node)`
+    pub fn in_(self, rel: Rel, node: Node) -> Self {
+        self.push(Direction::In, rel, node)
+    }
+
+    /// `-[rel]-(node)`
+    pub fn both(self, rel: Rel, node: Node) -> Self {
+        self.push(Direction::Both, rel, node)
+    }
+
+    fn push(mut self, direction: Direction, rel: Rel, node: Node) -> Self {
+        let (left, right) = match direction {
+            Direction::Out => ("-", "->"),
+            Direction::In => ("<-", "-"),
+            Direction::Both => ("-", "-"),
+        };
+        self.path.push_str(&format!("{left}{rel}{right}{node}"));
+        self
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path)
+    }
+}
+
+/// `MATCH`, returns `*` by default.
+#[derive(Debug, Clone)]
+pub struct Match {
+    optional: bool,
+    patterns: Vec<Pattern>,
+    cond: Option<String>,
+    returns: Vec<String>,
+    order_by: Vec<String>,
+    skip: Option<u64>,
+    limit: Option<u64>,
+}
+
+impl Match {
+    pub fn new(pattern: Pattern) -> Self {
+        Self {
+            optional: false,
+            patterns: vec![pattern],
+            cond: None,
+            returns: vec![],
+            order_by: vec![],
+            skip: None,
+            limit: None,
+        }
+    }
+
+    /// `OPTIONAL MATCH`
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    pub fn pattern(mut self, pattern: Pattern) -> Self {
+        self.patterns.push(pattern);
+        self
+    }
+
+    pub fn where_(mut self, cond: &str) -> Self {
+        self.cond = Some(cond.to_owned());
+        self
+    }
+
+    pub fn return_(mut self, expr: &str) -> Self {
+        self.returns.push(expr.to_owned());
+        self
+    }
+
+    /// e.g. `order_by("v.player.age DESC")`
+    pub fn order_by(mut self, expr: &str) -> Self {
+        self.order_by.push(expr.to_owned());
+        self
+    }
+
+    pub fn skip(mut self, n: u64) -> Self {
+        self.skip = Some(n);
+        self
+    }
+
+    pub fn limit(mut self, n: u64) -> Self {
+        self.limit = Some(n);
+        self
+    }
+}
+
+impl fmt::Display for Match {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.optional {
+            write!(f, "OPTIONAL ")?;
+        }
+        let patterns = self
+            .patterns
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "MATCH {}", patterns.join(", "))?;
+        if let Some(cond) = &self.cond {
+            write!(f, " WHERE {cond}")?;
+        }
+        if self.returns.is_empty() {
+            write!(f, " RETURN *")?;
+        } else {
+            write!(f, " RETURN {}", self.returns.join(", "))?;
+        }
+        if !self.order_by.is_empty() {
+            write!(f, " ORDER BY {}", self.order_by.join(", "))?;
+        }
+        if let Some(n) = self.skip {
+            write!(f, " SKIP {n}")?;
+        }
+        if let Some(n) = self.limit {
+            write!(f, " LIMIT {n}")?;
+        }
+        Ok(())
+    }
+}
+
+//
+//
+//
+/// `CREATE SPACE IF NOT EXISTS`
+#[derive(Debug, Clone)]
+pub struct CreateSpace {
+    name: String,
+    vid_type: String,
+    partition_num: Option<u32>,
+    replica_factor: Option<u32>,
+    comment: Option<String>,
+}
+
+impl CreateSpace {
+    /// `vid_type` is `INT64` or `FIXED_STRING(<N>)`.
+    pub fn new(name: &str, vid_type: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            vid_type: vid_type.to_owned(),
+            partition_num: None,
+            replica_factor: None,
+            comment: None,
+        }
+    }
+
+    pub fn partition_num(mut self, n: u32) -> Self {
+        self.partition_num = Some(n);
+        self
+    }
+
+    pub fn replica_factor(mut self, n: u32) -> Self {
+        self.replica_factor = Some(n);
+        self
+    }
+
+    pub fn comment(mut self, comment: &str) -> Self {
+        self.comment = Some(comment.to_owned());
+        self
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for CreateSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CREATE SPACE IF NOT EXISTS {}(", identifier(&self.name))?;
+        if let Some(n) = self.partition_num {
+            write!(f, "partition_num = {n}, ")?;
+        }
+        if let Some(n) = self.replica_factor {
+            write!(f, "replica_factor = {n}, ")?;
+        }
+        write!(f, "vid_type = {})", self.vid_type)?;
+        if let Some(comment) = &self.comment {
+            write!(f, " COMMENT = {}", comment.to_ngql_literal())?;
+        }
+        Ok(())
+    }
+}
+
+/// `ALTER TAG` or `ALTER EDGE`
+#[derive(Debug, Clone)]
+pub struct AlterSchema {
+    keyword: &'static str,
+    name: String,
+    added: Vec<PropDef>,
+    changed: Vec<PropDef>,
+    dropped: Vec<String>,
+}
+
+impl AlterSchema {
+    pub fn tag(name: &str) -> Self {
+        Self::new("TAG", name)
+    }
+
+    pub fn edge(name: &str) -> Self {
+        Self::new("EDGE", name)
+    }
+
+    fn new(keyword: &'static str, name: &str) -> Self {
+        Self {
+            keyword,
+            name: name.to_owned(),
+            added: vec![],
+            changed: vec![],
+            dropped: vec![],
+        }
+    }
+
+    pub fn add_prop(mut self, prop: PropDef) -> Self {
+        self.added.push(prop);
+        self
+    }
+
+    /// Change the data type or the nullability of an existing property.
+    pub fn change_prop(mut self, prop: PropDef) -> Self {
+        self.changed.push(prop);
+        self
+    }
+
+    pub fn drop_prop(mut self, prop: &str) -> Self {
+        self.dropped.push(prop.to_owned());
+        self
+    }
+
+    pub(crate) fn keyword(&self) -> &'static str {
+        self.keyword
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The added and the changed properties
+    pub(crate) fn props(&self) -> impl Iterator<Item = &PropDef> {
+        self.added.iter().chain(self.changed.iter())
+    }
+
+    pub(crate) fn dropped(&self) -> &[String] {
+        &self.dropped
+    }
+}
+
+impl fmt::Display for AlterSchema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut clauses = vec![];
+        for (op, props) in [("ADD", &self.added), ("CHANGE", &self.changed)] {
+            if !props.is_empty() {
+                let props = props.iter().map(PropDef::ddl).collect::<Vec<_>>();
+                clauses.push(format!("{op} ({})", props.join(", ")));
+            }
+        }
+        if !self.dropped.is_empty() {
+            let props = self.dropped.iter().map(String::as_str).collect::<Vec<_>>();
+            clauses.push(format!("DROP ({})", identifiers(&props)));
+        }
+        write!(
+            f,
+            "ALTER {} {} {}",
+            self.keyword,
+            identifier(&self.name),
+            clauses.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert() {
+        let stmt = InsertVertex::new("player", &["name", "age"])
+            .tag("team member", &[])
+            .if_not_exists()
+            .value("a", &[&"Tim \"The Big\" Duncan", &42])
+            .value("b", &[&"Tony Parker", &36]);
+        assert_eq!(
+            stmt.to_string(),
+            r#"INSERT VERTEX IF NOT EXISTS `player`(`name`, `age`), `team member`() VALUES "a":("Tim \"The Big\" Duncan", 42), "b":("Tony Parker", 36)"#
+        );
+
+        let stmt = InsertEdge::new("follow", &["degree"])
+            .value("a", "b", &[&95])
+            .value_with_rank(1, 2, 3, &[&90]);
+        assert_eq!(
+            stmt.to_string(),
+            r#"INSERT EDGE `follow`(`degree`) VALUES "a"->"b":(95), 1->2@3:(90)"#
+        );
+        assert!(!stmt.is_empty());
+        assert!(InsertEdge::new("follow", &["degree"]).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "InsertVertex needs at least one row")]
+    fn test_insert_vertex_without_rows() {
+        let _ = InsertVertex::new("player", &["name"]).to_string();
+    }
+
+    #[test]
+    #[should_panic(expected = "InsertEdge needs at least one row")]
+    fn test_insert_edge_without_rows() {
+        let _ = InsertEdge::new("follow", &["degree"]).to_string();
+    }
+
+    #[test]
+    fn test_update() {
+        let stmt = Update::vertex("player", "a")
+            .set("name", "Tim")
+            .set_expr("age", "age + 1")
+            .when("age > 40")
+            .yield_("age AS Age");
+        assert_eq!(
+            stmt.to_string(),
+            r#"UPDATE VERTEX ON `player` "a" SET `name` = "Tim", `age` = age + 1 WHEN age > 40 YIELD age AS Age"#
+        );
+
+        let stmt = Update::upsert_edge("follow", "a", "b", Some(0)).set("degree", 100);
+        assert_eq!(
+            stmt.to_string(),
+            r#"UPSERT EDGE ON `follow` "a"->"b"@0 SET `degree` = 100"#
+        );
+    }
+
+    #[test]
+    fn test_delete() {
+        assert_eq!(
+            DeleteVertex::new(&["a", "b"]).with_edge().to_string(),
+            r#"DELETE VERTEX "a", "b" WITH EDGE"#
+        );
+        assert_eq!(
+            DeleteEdge::new("follow")
+                .edge("a", "b", None)
+                .edge("a", "c", Some(1))
+                .to_string(),
+            r#"DELETE EDGE `follow` "a"->"b", "a"->"c"@1"#
+        );
+        assert_eq!(
+            DeleteTag::new(&[], &[1, 2]).to_string(),
+            "DELETE TAG * FROM 1, 2"
+        );
+    }
+
+    #[test]
+    fn test_fetch() {
+        assert_eq!(
+            FetchVertices::new(&["player"], &["a"]).to_string(),
+            r#"FETCH PROP ON `player` "a" YIELD properties(vertex)"#
+        );
+        assert_eq!(
+            FetchEdges::new("follow")
+                .edge("a", "b", Some(0))
+                .yield_("follow.degree")
+                .to_string(),
+            r#"FETCH PROP ON `follow` "a"->"b"@0 YIELD follow.degree"#
+        );
+    }
+
+    #[test]
+    fn test_go_and_lookup() {
+        let stmt = Go::new(&["a"])
+            .steps_between(1, 2)
+            .over("follow")
+            .reversely()
+            .where_("properties(edge).degree > 90")
+            .distinct()
+            .yield_("src(edge) AS src")
+            .yield_("dst(edge) AS dst");
+        assert_eq!(
+            stmt.to_string(),
+            r#"GO 1 TO 2 STEPS FROM "a" OVER `follow` REVERSELY WHERE properties(edge).degree > 90 YIELD DISTINCT src(edge) AS src, dst(edge) AS dst"#
+        );
+        assert_eq!(
+            Go::new(&[1]).to_string(),
+            "GO FROM 1 OVER * YIELD dst(edge)"
+        );
+
+        assert_eq!(
+            Lookup::tag("player")
+                .where_(r#"player.name == "Tim""#)
+                .to_string(),
+            r#"LOOKUP ON `player` WHERE player.name == "Tim" YIELD id(vertex)"#
+        );
+    }
+
+    #[test]
+    fn test_match() {
+        let pattern = Pattern::new(Node::new("v").label("player").prop("name", "Tim"))
+            .out(
+                Rel::new("e")
+                    .edge_type("follow")
+                    .edge_type("serve")
+                    .hops(1, 3),
+                Node::new("v2"),
+            )
+            .in_(Rel::new(""), Node::new(""));
+        let stmt = Match::new(pattern)
+            .where_("v2.player.age > 30")
+            .return_("v2")
+            .order_by("v2.player.age DESC")
+            .skip(1)
+            .limit(10);
+        assert_eq!(
+            stmt.to_string(),
+            r#"MATCH (v:`player`{`name`: "Tim"})-[e:`follow`|`serve`*1..3]->(v2)<-[]-() WHERE v2.player.age > 30 RETURN v2 ORDER BY v2.player.age DESC SKIP 1 LIMIT 10"#
+        );
+    }
+
+    #[test]
+    fn test_ddl() {
+        let stmt = CreateSpace::new("basket ball", "FIXED_STRING(32)")
+            .partition_num(10)
+            .comment("NBA");
+        assert_eq!(
+            stmt.to_string(),
+            r#"CREATE SPACE IF NOT EXISTS `basket ball`(partition_num = 10, vid_type = FIXED_STRING(32)) COMMENT = "NBA""#
+        );
+
+        let prop = |name, data_type| PropDef {
+            name,
+            data_type,
+            nullable: true,
+            column: name,
+        };
+        let stmt = AlterSchema::tag("player")
+            .add_prop(prop("height", "double"))
+            .drop_prop("weight")
+            .change_prop(prop("age", "int64"))
+            .drop_prop("team");
+        assert_eq!(
+            stmt.to_string(),
+            "ALTER TAG `player` ADD (`height` double NULL), CHANGE (`age` int64 NULL), DROP (`weight`, `team`)"
+        );
+    }
+}
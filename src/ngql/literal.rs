@@ -0,0 +1,454 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::common::types::{
+    Date, DateTime, Duration, Edge, Geography, NullType, Path, Time, Value, Vertex,
+};
+
+/// Render a value as an nGQL literal, e.g. a string with its quotes and escapes.
+///
+/// This is the counterpart of `ValueWrapper::to_string`: the output can be put
+/// into a statement and evaluates to the same value, e.g. a `Value::dVal` is
+/// rendered as `date("2023-01-02")`.
+/// ## Notice
+/// Vertices, edges, paths and data sets have no literal. A vertex is rendered
+/// as its vid, an edge as `src->dst@rank`, a path as the list of the vids on it
+/// and a data set as the list of its rows.
+pub trait ToNgqlLiteral {
+    fn to_ngql_literal(&self) -> String;
+}
+
+/// Quote a name of a space, tag, edge type or property with backticks, so
+/// reserved keywords and special characters can be used.
+pub fn identifier(name: &str) -> String {
+    format!("`{}`", name.replace('\\', "\\\\").replace('`', "\\`"))
+}
+
+impl<T: ToNgqlLiteral + ?Sized> ToNgqlLiteral for &T {
+    fn to_ngql_literal(&self) -> String {
+        (**self).to_ngql_literal()
+    }
+}
+
+impl ToNgqlLiteral for str {
+    fn to_ngql_literal(&self) -> String {
+        let mut s = String::with_capacity(self.len() + 2);
+        s.push('"');
+        for c in self.chars() {
+            match c {
+                '"' => s.push_str("\\\""),
+                '\\' => s.push_str("\\\\"),
+                '\n' => s.push_str("\\n"),
+                '\r' => s.push_str("\\r"),
+                '\t' => s.push_str("\\t"),
+                c => s.push(c),
+            }
+        }
+        s.push('"');
+        s
+    }
+}
+
+impl ToNgqlLiteral for String {
+    fn to_ngql_literal(&self) -> String {
+        self.as_str().to_ngql_literal()
+    }
+}
+
+impl ToNgqlLiteral for bool {
+    fn to_ngql_literal(&self) -> String {
+        self.to_string()
+    }
+}
+
+macro_rules! impl_to_ngql_literal_for_integer {
+    ($($t:ty),*) => {
+        $(
+            impl ToNgqlLiteral for $t {
+                fn to_ngql_literal(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+// graphd rejects `u64` and `usize` values above `i64::MAX`, `ngql::ser` reports
+// them as `NgqlSerializeError::Unsupported`.
+impl_to_ngql_literal_for_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl ToNgqlLiteral for char {
+    fn to_ngql_literal(&self) -> String {
+        self.to_string().to_ngql_literal()
+    }
+}
+
+/// NaN and the infinities have no literal, they are converted from strings,
+/// e.g. `toFloat("inf")`.
+impl ToNgqlLiteral for f64 {
+    fn to_ngql_literal(&self) -> String {
+        if self.is_nan() {
+            r#"toFloat("nan")"#.to_owned()
+        } else if self.is_infinite() {
+            let sign = if *self < 0.0 { "-" } else { "" };
+            format!(r#"toFloat("{sign}inf")"#)
+        } else {
+            // `{:?}` keeps the decimal point, e.g. `1.0`, so it's parsed as a float.
+            format!("{self:?}")
+        }
+    }
+}
+
+impl ToNgqlLiteral for f32 {
+    fn to_ngql_literal(&self) -> String {
+        if self.is_finite() {
+            format!("{self:?}")
+        } else {
+            (*self as f64).to_ngql_literal()
+        }
+    }
+}
+
+/// `NULL` for `None`
+impl<T: ToNgqlLiteral> ToNgqlLiteral for Option<T> {
+    fn to_ngql_literal(&self) -> String {
+        match self {
+            Some(v) => v.to_ngql_literal(),
+            None => "NULL".to_owned(),
+        }
+    }
+}
+
+fn list<'a, T: ToNgqlLiteral + 'a>(values: impl IntoIterator<Item = &'a T>) -> String {
+    let values = values
+        .into_iter()
+        .map(|v| v.to_ngql_literal())
+        .collect::<Vec<_>>();
+    format!("[{}]", values.join(", "))
+}
+
+fn set<'a, T: ToNgqlLiteral + 'a>(values: impl IntoIterator<Item = &'a T>) -> String {
+    let values = values
+        .into_iter()
+        .map(|v| v.to_ngql_literal())
+        .collect::<Vec<_>>();
+    format!("{{{}}}", values.join(", "))
+}
+
+fn map<'a, K: AsRef<str> + 'a, V: ToNgqlLiteral + 'a>(
+    kvs: impl IntoIterator<Item = (&'a K, &'a V)>,
+) -> String {
+    let kvs = kvs
+        .into_iter()
+        .map(|(k, v)| format!("{}: {}", identifier(k.as_ref()), v.to_ngql_literal()))
+        .collect::<Vec<_>>();
+    format!("{{{}}}", kvs.join(", "))
+}
+
+impl<T: ToNgqlLiteral> ToNgqlLiteral for [T] {
+    fn to_ngql_literal(&self) -> String {
+        list(self)
+    }
+}
+
+impl<T: ToNgqlLiteral> ToNgqlLiteral for Vec<T> {
+    fn to_ngql_literal(&self) -> String {
+        list(self)
+    }
+}
+
+impl<T: ToNgqlLiteral, S> ToNgqlLiteral for HashSet<T, S> {
+    fn to_ngql_literal(&self) -> String {
+        set(self)
+    }
+}
+
+impl<T: ToNgqlLiteral> ToNgqlLiteral for BTreeSet<T> {
+    fn to_ngql_literal(&self) -> String {
+        set(self)
+    }
+}
+
+impl<K: AsRef<str>, V: ToNgqlLiteral, S> ToNgqlLiteral for HashMap<K, V, S> {
+    fn to_ngql_literal(&self) -> String {
+        map(self)
+    }
+}
+
+impl<K: AsRef<str>, V: ToNgqlLiteral> ToNgqlLiteral for BTreeMap<K, V> {
+    fn to_ngql_literal(&self) -> String {
+        map(self)
+    }
+}
+
+//
+//
+//
+impl ToNgqlLiteral for Date {
+    fn to_ngql_literal(&self) -> String {
+        format!(
+            "date(\"{:04}-{:02}-{:02}\")",
+            self.year, self.month, self.day
+        )
+    }
+}
+
+/// Nebula keeps times in UTC, and graphd reads the literal in its
+/// `timezone_name`, which is UTC by default.
+impl ToNgqlLiteral for Time {
+    fn to_ngql_literal(&self) -> String {
+        format!(
+            "time(\"{:02}:{:02}:{:02}.{:06}\")",
+            self.hour, self.minute, self.sec, self.microsec
+        )
+    }
+}
+
+/// Nebula keeps datetimes in UTC, and graphd reads the literal in its
+/// `timezone_name`, which is UTC by default.
+impl ToNgqlLiteral for DateTime {
+    fn to_ngql_literal(&self) -> String {
+        format!(
+            "datetime(\"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}\")",
+            self.year, self.month, self.day, self.hour, self.minute, self.sec, self.microsec
+        )
+    }
+}
+
+impl ToNgqlLiteral for Duration {
+    fn to_ngql_literal(&self) -> String {
+        format!(
+            "duration({{months: {}, seconds: {}, microseconds: {}}})",
+            self.months, self.seconds, self.microseconds
+        )
+    }
+}
+
+impl ToNgqlLiteral for std::time::Duration {
+    fn to_ngql_literal(&self) -> String {
+        format!(
+            "duration({{seconds: {}, microseconds: {}}})",
+            self.as_secs(),
+            self.subsec_micros()
+        )
+    }
+}
+
+impl ToNgqlLiteral for Geography {
+    fn to_ngql_literal(&self) -> String {
+        let coords = |coords: &[crate::common::types::Coordinate]| {
+            coords
+                .iter()
+                .map(|v| format!("{} {}", v.x.0, v.y.0))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let wkt = match self {
+            Geography::ptVal(v) => format!("POINT({})", coords(core::slice::from_ref(&v.coord))),
+            Geography::lsVal(v) => format!("LINESTRING({})", coords(&v.coordList)),
+            Geography::pgVal(v) => {
+                let rings = v
+                    .coordListList
+                    .iter()
+                    .map(|v| format!("({})", coords(v)))
+                    .collect::<Vec<_>>();
+                format!("POLYGON({})", rings.join(", "))
+            }
+            Geography::UnknownField(_) => return "NULL".to_owned(),
+        };
+        format!("ST_GeogFromText({})", wkt.to_ngql_literal())
+    }
+}
+
+/// The vid
+impl ToNgqlLiteral for Vertex {
+    fn to_ngql_literal(&self) -> String {
+        self.vid.to_ngql_literal()
+    }
+}
+
+/// `src->dst@rank`, as used by `FETCH PROP ON` or `DELETE EDGE`
+impl ToNgqlLiteral for Edge {
+    fn to_ngql_literal(&self) -> String {
+        format!(
+            "{}->{}@{}",
+            self.src.to_ngql_literal(),
+            self.dst.to_ngql_literal(),
+            self.ranking
+        )
+    }
+}
+
+/// The list of the vids on the path
+impl ToNgqlLiteral for Path {
+    fn to_ngql_literal(&self) -> String {
+        list(core::iter::once(&self.src).chain(self.steps.iter().map(|v| &v.dst)))
+    }
+}
+
+impl ToNgqlLiteral for Value {
+    fn to_ngql_literal(&self) -> String {
+        match self {
+            Value::nVal(NullType(_)) => "NULL".to_owned(),
+            Value::bVal(v) => v.to_ngql_literal(),
+            Value::iVal(v) => v.to_ngql_literal(),
+            Value::fVal(v) => v.0.to_ngql_literal(),
+            Value::sVal(v) => String::from_utf8_lossy(v).to_ngql_literal(),
+            Value::dVal(v) => v.to_ngql_literal(),
+            Value::tVal(v) => v.to_ngql_literal(),
+            Value::dtVal(v) => v.to_ngql_literal(),
+            Value::vVal(v) => v.to_ngql_literal(),
+            Value::eVal(v) => v.to_ngql_literal(),
+            Value::pVal(v) => v.to_ngql_literal(),
+            Value::lVal(v) => list(&v.values),
+            Value::mVal(v) => {
+                let kvs = v
+                    .kvs
+                    .iter()
+                    .map(|(k, v)| (String::from_utf8_lossy(k).to_string(), v))
+                    .collect::<Vec<_>>();
+                map(kvs.iter().map(|(k, v)| (k, *v)))
+            }
+            Value::uVal(v) => set(&v.values),
+            Value::gVal(v) => list(v.rows.iter().map(|v| &v.values)),
+            Value::ggVal(v) => v.to_ngql_literal(),
+            Value::duVal(v) => v.to_ngql_literal(),
+            Value::UnknownField(_) => "NULL".to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_ngql_literal() {
+        assert_eq!("Tim".to_ngql_literal(), r#""Tim""#);
+        assert_eq!(
+            "a \"b\"\\\n".to_owned().to_ngql_literal(),
+            r#""a \"b\"\\\n""#
+        );
+        assert_eq!(true.to_ngql_literal(), "true");
+        assert_eq!((-42_i64).to_ngql_literal(), "-42");
+        assert_eq!(1.0_f64.to_ngql_literal(), "1.0");
+        assert_eq!(0.5_f32.to_ngql_literal(), "0.5");
+        assert_eq!(f64::NAN.to_ngql_literal(), r#"toFloat("nan")"#);
+        assert_eq!(f64::INFINITY.to_ngql_literal(), r#"toFloat("inf")"#);
+        assert_eq!(f32::NEG_INFINITY.to_ngql_literal(), r#"toFloat("-inf")"#);
+        assert_eq!(
+            std::time::Duration::from_micros(1_500_002).to_ngql_literal(),
+            "duration({seconds: 1, microseconds: 500002})"
+        );
+        assert_eq!(identifier("player"), "`player`");
+        assert_eq!(identifier("a`b"), "`a\\`b`");
+    }
+
+    #[test]
+    fn test_containers_to_ngql_literal() {
+        assert_eq!(Some(1).to_ngql_literal(), "1");
+        assert_eq!(None::<&str>.to_ngql_literal(), "NULL");
+        assert_eq!(vec!["a", "b"].to_ngql_literal(), r#"["a", "b"]"#);
+        assert_eq!(BTreeSet::from([2, 1]).to_ngql_literal(), "{1, 2}");
+        assert_eq!(
+            BTreeMap::from([("name", "Tim"), ("team", "Spurs")]).to_ngql_literal(),
+            r#"{`name`: "Tim", `team`: "Spurs"}"#
+        );
+        assert_eq!(
+            HashMap::from([("age".to_owned(), Some(42))]).to_ngql_literal(),
+            "{`age`: 42}"
+        );
+    }
+
+    #[test]
+    fn test_value_to_ngql_literal() {
+        use crate::common::double::Double;
+        use crate::common::types::{Coordinate, NList, Point, Polygon};
+
+        assert_eq!(Value::nVal(NullType::__NULL__).to_ngql_literal(), "NULL");
+        assert_eq!(Value::fVal(Double(1.5)).to_ngql_literal(), "1.5");
+        assert_eq!(
+            Value::fVal(Double(f64::NAN)).to_ngql_literal(),
+            r#"toFloat("nan")"#
+        );
+        assert_eq!(Value::sVal(b"a\"b".to_vec()).to_ngql_literal(), r#""a\"b""#);
+        assert_eq!(
+            Value::dVal(Date {
+                year: 2023,
+                month: 1,
+                day: 2,
+                ..Default::default()
+            })
+            .to_ngql_literal(),
+            r#"date("2023-01-02")"#
+        );
+        assert_eq!(
+            Value::tVal(Time {
+                hour: 3,
+                minute: 4,
+                sec: 5,
+                microsec: 6,
+                ..Default::default()
+            })
+            .to_ngql_literal(),
+            r#"time("03:04:05.000006")"#
+        );
+        assert_eq!(
+            Value::dtVal(DateTime {
+                year: 2023,
+                month: 1,
+                day: 2,
+                hour: 3,
+                minute: 4,
+                sec: 5,
+                microsec: 6,
+                ..Default::default()
+            })
+            .to_ngql_literal(),
+            r#"datetime("2023-01-02T03:04:05.000006")"#
+        );
+        assert_eq!(
+            Value::duVal(Duration {
+                seconds: 10,
+                microseconds: 20,
+                months: 1,
+                ..Default::default()
+            })
+            .to_ngql_literal(),
+            "duration({months: 1, seconds: 10, microseconds: 20})"
+        );
+        assert_eq!(
+            Value::lVal(NList {
+                values: vec![Value::iVal(1), Value::bVal(false)],
+                ..Default::default()
+            })
+            .to_ngql_literal(),
+            "[1, false]"
+        );
+
+        let coord = |x, y| Coordinate {
+            x: Double(x),
+            y: Double(y),
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::ggVal(Geography::ptVal(Point {
+                coord: coord(1.0, 2.5),
+                ..Default::default()
+            }))
+            .to_ngql_literal(),
+            r#"ST_GeogFromText("POINT(1 2.5)")"#
+        );
+        assert_eq!(
+            Geography::pgVal(Polygon {
+                coordListList: vec![vec![
+                    coord(0.0, 0.0),
+                    coord(1.0, 0.0),
+                    coord(1.0, 1.0),
+                    coord(0.0, 0.0)
+                ]],
+                ..Default::default()
+            })
+            .to_ngql_literal(),
+            r#"ST_GeogFromText("POLYGON((0 0, 1 0, 1 1, 0 0))")"#
+        );
+    }
+}
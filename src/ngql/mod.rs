@@ -0,0 +1,19 @@
+//! Building nGQL statements without formatting strings by hand.
+
+pub mod builder;
+pub mod literal;
+pub mod model;
+#[cfg(feature = "serde")]
+pub mod ser;
+
+pub use builder::{
+    AlterSchema, CreateSpace, DeleteEdge, DeleteTag, DeleteVertex, FetchEdges, FetchVertices, Go,
+    InsertEdge, InsertVertex, Lookup, Match, Node, Pattern, Rel, Update,
+};
+pub use literal::{identifier, ToNgqlLiteral};
+pub use model::{NebulaEdge, NebulaTag, PropDef};
+#[cfg(feature = "serde")]
+pub use ser::{to_insert_edge, to_insert_vertex, NgqlSerializeError};
+
+#[cfg(feature = "derive")]
+pub use rust_nebula_derive::{NebulaEdge, NebulaTag};
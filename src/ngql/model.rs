@@ -0,0 +1,345 @@
+//! Structs mapped to a tag or an edge type.
+//!
+//! Implement them with `#[derive(NebulaTag)]` and `#[derive(NebulaEdge)]` of the
+//! `derive` feature, so the struct is the single source of the DDL, the insert
+//! and fetch statements, and the columns read back with `scan`.
+//! ## Example
+//! ```ignore
+//! use rust_nebula::ngql::{NebulaTag, ToNgqlLiteral};
+//!
+//! #[derive(NebulaTag, serde::Deserialize)]
+//! #[nebula(name = "player")]
+//! struct Player {
+//!     #[nebula(vid)]
+//!     id: String,
+//!     name: String,
+//!     age: Option<i64>,
+//! }
+//!
+//! session.execute(&Player::create_tag()).await?;
+//! session.execute(&Player::insert(&players).to_string()).await?;
+//! let players = session.query(&Player::fetch(&["player100"]).to_string()).await?.scan::<Player>()?;
+//! ```
+
+use super::{
+    builder::{FetchEdges, FetchVertices, InsertEdge, InsertVertex},
+    literal::{identifier, ToNgqlLiteral},
+};
+
+/// A property of a tag or an edge type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PropDef {
+    /// Name of the property
+    pub name: &'static str,
+    /// nGQL data type, e.g. `string` or `fixed_string(32)`
+    pub data_type: &'static str,
+    pub nullable: bool,
+    /// Name of the column the property is yielded as, which is the name the
+    /// field is deserialized from.
+    pub column: &'static str,
+}
+
+impl PropDef {
+    pub(crate) fn ddl(&self) -> String {
+        format!(
+            "{} {} {}",
+            identifier(self.name),
+            self.data_type,
+            if self.nullable { "NULL" } else { "NOT NULL" }
+        )
+    }
+
+    fn yield_column(&self, schema: &str) -> String {
+        format!(
+            "{}.{} AS {}",
+            identifier(schema),
+            identifier(self.name),
+            identifier(self.column)
+        )
+    }
+}
+
+fn prop_names(props: &[PropDef]) -> Vec<&'static str> {
+    props.iter().map(|v| v.name).collect()
+}
+
+pub(crate) fn create_ddl(keyword: &str, name: &str, props: &[PropDef]) -> String {
+    let props = props.iter().map(PropDef::ddl).collect::<Vec<_>>();
+    format!(
+        "CREATE {keyword} IF NOT EXISTS {}({})",
+        identifier(name),
+        props.join(", ")
+    )
+}
+
+//
+//
+//
+pub trait NebulaTag: Sized {
+    /// Name of the tag
+    const NAME: &'static str;
+    const PROPS: &'static [PropDef];
+    /// Name of the column the vid is yielded as
+    const VID_COLUMN: &'static str;
+
+    fn vid(&self) -> &dyn ToNgqlLiteral;
+
+    /// Values of the properties in the order of `PROPS`
+    fn values(&self) -> Vec<&dyn ToNgqlLiteral>;
+
+    /// `CREATE TAG IF NOT EXISTS ...`
+    fn create_tag() -> String {
+        create_ddl("TAG", Self::NAME, Self::PROPS)
+    }
+
+    /// `INSERT VERTEX` of all rows, `rows` must not be empty.
+    fn insert(rows: &[Self]) -> InsertVertex {
+        rows.iter().fold(
+            InsertVertex::new(Self::NAME, &prop_names(Self::PROPS)),
+            |stmt, row| stmt.value(row.vid(), &row.values()),
+        )
+    }
+
+    /// The `YIELD` columns of the vid and the properties, named like the fields
+    /// are deserialized.
+    fn yield_columns() -> Vec<String> {
+        let mut columns = vec![format!("id(vertex) AS {}", identifier(Self::VID_COLUMN))];
+        columns.extend(Self::PROPS.iter().map(|v| v.yield_column(Self::NAME)));
+        columns
+    }
+
+    /// `FETCH PROP ON` the vertices, the output can be read with `scan::<Self>()`.
+    fn fetch<V: ToNgqlLiteral>(vids: &[V]) -> FetchVertices {
+        Self::yield_columns()
+            .iter()
+            .fold(FetchVertices::new(&[Self::NAME], vids), |stmt, v| {
+                stmt.yield_(v)
+            })
+    }
+}
+
+pub trait NebulaEdge: Sized {
+    /// Name of the edge type
+    const NAME: &'static str;
+    const PROPS: &'static [PropDef];
+    /// Names of the columns the src, dst and rank are yielded as
+    const SRC_COLUMN: &'static str;
+    const DST_COLUMN: &'static str;
+    const RANK_COLUMN: Option<&'static str>;
+
+    fn src(&self) -> &dyn ToNgqlLiteral;
+    fn dst(&self) -> &dyn ToNgqlLiteral;
+    fn rank(&self) -> Option<i64>;
+
+    /// Values of the properties in the order of `PROPS`
+    fn values(&self) -> Vec<&dyn ToNgqlLiteral>;
+
+    /// `CREATE EDGE IF NOT EXISTS ...`
+    fn create_edge() -> String {
+        create_ddl("EDGE", Self::NAME, Self::PROPS)
+    }
+
+    /// `INSERT EDGE` of all rows, `rows` must not be empty.
+    fn insert(rows: &[Self]) -> InsertEdge {
+        rows.iter().fold(
+            InsertEdge::new(Self::NAME, &prop_names(Self::PROPS)),
+            |stmt, row| match row.rank() {
+                Some(rank) => stmt.value_with_rank(row.src(), row.dst(), rank, &row.values()),
+                None => stmt.value(row.src(), row.dst(), &row.values()),
+            },
+        )
+    }
+
+    /// The `YIELD` columns of the src, dst, rank and the properties, named like
+    /// the fields are deserialized.
+    fn yield_columns() -> Vec<String> {
+        let mut columns = vec![
+            format!("src(edge) AS {}", identifier(Self::SRC_COLUMN)),
+            format!("dst(edge) AS {}", identifier(Self::DST_COLUMN)),
+        ];
+        if let Some(rank) = Self::RANK_COLUMN {
+            columns.push(format!("rank(edge) AS {}", identifier(rank)));
+        }
+        columns.extend(Self::PROPS.iter().map(|v| v.yield_column(Self::NAME)));
+        columns
+    }
+
+    /// `FETCH PROP ON` the edges `(src, dst, rank)`, the output can be read
+    /// with `scan::<Self>()`.
+    fn fetch<V: ToNgqlLiteral>(keys: &[(V, V, Option<i64>)]) -> FetchEdges {
+        let stmt = keys
+            .iter()
+            .fold(FetchEdges::new(Self::NAME), |stmt, (src, dst, rank)| {
+                stmt.edge(src, dst, *rank)
+            });
+        Self::yield_columns()
+            .iter()
+            .fold(stmt, |stmt, v| stmt.yield_(v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Follow {
+        src: String,
+        dst: String,
+        degree: Option<i64>,
+    }
+
+    impl NebulaEdge for Follow {
+        const NAME: &'static str = "follow";
+        const PROPS: &'static [PropDef] = &[PropDef {
+            name: "degree",
+            data_type: "int64",
+            nullable: true,
+            column: "degree",
+        }];
+        const SRC_COLUMN: &'static str = "src";
+        const DST_COLUMN: &'static str = "dst";
+        const RANK_COLUMN: Option<&'static str> = None;
+
+        fn src(&self) -> &dyn ToNgqlLiteral {
+            &self.src
+        }
+        fn dst(&self) -> &dyn ToNgqlLiteral {
+            &self.dst
+        }
+        fn rank(&self) -> Option<i64> {
+            None
+        }
+        fn values(&self) -> Vec<&dyn ToNgqlLiteral> {
+            vec![&self.degree]
+        }
+    }
+
+    #[test]
+    fn test_nebula_edge() {
+        assert_eq!(
+            Follow::create_edge(),
+            "CREATE EDGE IF NOT EXISTS `follow`(`degree` int64 NULL)"
+        );
+        let rows = vec![Follow {
+            src: "a".to_owned(),
+            dst: "b".to_owned(),
+            degree: None,
+        }];
+        assert_eq!(
+            Follow::insert(&rows).to_string(),
+            r#"INSERT EDGE `follow`(`degree`) VALUES "a"->"b":(NULL)"#
+        );
+        assert_eq!(
+            Follow::fetch(&[("a", "b", None)]).to_string(),
+            r#"FETCH PROP ON `follow` "a"->"b" YIELD src(edge) AS `src`, dst(edge) AS `dst`, `follow`.`degree` AS `degree`"#
+        );
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive() {
+        use serde::Deserialize;
+
+        #[derive(crate::ngql::NebulaTag, Deserialize)]
+        #[nebula(name = "player")]
+        #[allow(dead_code)]
+        struct Player {
+            #[nebula(vid)]
+            id: String,
+            #[serde(rename = "Name")]
+            name: String,
+            #[nebula(rename = "age", data_type = "int8")]
+            player_age: Option<i8>,
+            #[nebula(skip)]
+            #[serde(default)]
+            cached: Vec<String>,
+        }
+
+        #[derive(crate::ngql::NebulaEdge)]
+        struct FollowEdge {
+            #[nebula(src)]
+            src: i64,
+            #[nebula(dst)]
+            dst: i64,
+            #[nebula(rank)]
+            rank: i32,
+            degree: f64,
+        }
+
+        assert_eq!(
+            Player::create_tag(),
+            "CREATE TAG IF NOT EXISTS `player`(`name` string NOT NULL, `age` int8 NULL)"
+        );
+        let rows = vec![Player {
+            id: "player100".to_owned(),
+            name: "Tim Duncan".to_owned(),
+            player_age: Some(42),
+            cached: vec![],
+        }];
+        assert_eq!(
+            Player::insert(&rows).to_string(),
+            r#"INSERT VERTEX `player`(`name`, `age`) VALUES "player100":("Tim Duncan", 42)"#
+        );
+        assert_eq!(
+            Player::fetch(&["player100"]).to_string(),
+            r#"FETCH PROP ON `player` "player100" YIELD id(vertex) AS `id`, `player`.`name` AS `Name`, `player`.`age` AS `player_age`"#
+        );
+
+        assert_eq!(
+            FollowEdge::create_edge(),
+            "CREATE EDGE IF NOT EXISTS `follow_edge`(`degree` double NOT NULL)"
+        );
+        let rows = vec![FollowEdge {
+            src: 1,
+            dst: 2,
+            rank: 3,
+            degree: 0.5,
+        }];
+        assert_eq!(
+            FollowEdge::insert(&rows).to_string(),
+            "INSERT EDGE `follow_edge`(`degree`) VALUES 1->2@3:(0.5)"
+        );
+        assert_eq!(
+            FollowEdge::yield_columns(),
+            vec![
+                "src(edge) AS `src`",
+                "dst(edge) AS `dst`",
+                "rank(edge) AS `rank`",
+                "`follow_edge`.`degree` AS `degree`"
+            ]
+        );
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive_time_types() {
+        use crate::common::types::DateTime;
+
+        #[derive(crate::ngql::NebulaTag)]
+        struct Login {
+            #[nebula(vid)]
+            id: i64,
+            at: DateTime,
+            took: std::time::Duration,
+        }
+
+        assert_eq!(
+            Login::create_tag(),
+            "CREATE TAG IF NOT EXISTS `login`(`at` datetime NOT NULL, `took` duration NOT NULL)"
+        );
+        let rows = vec![Login {
+            id: 1,
+            at: DateTime {
+                year: 2023,
+                month: 1,
+                day: 2,
+                ..Default::default()
+            },
+            took: std::time::Duration::from_millis(1500),
+        }];
+        assert_eq!(
+            Login::insert(&rows).to_string(),
+            r#"INSERT VERTEX `login`(`at`, `took`) VALUES 1:(datetime("2023-01-02T00:00:00.000000"), duration({seconds: 1, microseconds: 500000}))"#
+        );
+    }
+}
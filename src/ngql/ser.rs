@@ -0,0 +1,810 @@
+//! Serialize structs into `INSERT VERTEX` and `INSERT EDGE` statements.
+//!
+//! Every field of a struct is a property, named like serde names it, so
+//! `#[serde(rename(serialize = "..."))]` maps a field to a property the same
+//! way `#[serde(rename(deserialize = "..."))]` maps a column to a field when
+//! reading rows. The fields holding the vid, or the src, dst and rank of an
+//! edge, are designated by their serialized names and aren't inserted as
+//! properties.
+//! ## Example
+//! ```
+//! use rust_nebula::ngql::to_insert_vertex;
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Player {
+//!     #[serde(rename = "vid")]
+//!     id: String,
+//!     name: String,
+//!     age: i64,
+//! }
+//!
+//! let players = vec![Player {
+//!     id: "player100".to_owned(),
+//!     name: "Tim Duncan".to_owned(),
+//!     age: 42,
+//! }];
+//! let stmt = to_insert_vertex("player", "vid", &players).unwrap();
+//! assert_eq!(
+//!     stmt.to_string(),
+//!     r#"INSERT VERTEX `player`(`name`, `age`) VALUES "player100":("Tim Duncan", 42)"#
+//! );
+//! ```
+
+use core::fmt;
+
+use serde::ser::{self, Impossible, Serialize};
+
+use super::{
+    builder::{InsertEdge, InsertVertex},
+    literal::{identifier, ToNgqlLiteral},
+};
+
+/// Build a multi-row `INSERT VERTEX` of `tag`, `vid_field` holds the vid.
+pub fn to_insert_vertex<T: Serialize>(
+    tag: &str,
+    vid_field: &str,
+    rows: &[T],
+) -> Result<InsertVertex, NgqlSerializeError> {
+    let (names, rows) = serialize_rows(rows)?;
+    let vid_idx = field_index(&names, vid_field)?;
+
+    let props = prop_names(&names, &[vid_idx]);
+    let mut stmt = InsertVertex::new(tag, &props);
+    for row in rows {
+        let values = prop_values(&row, &[vid_idx]);
+        let values = values
+            .iter()
+            .map(|v| v as &dyn ToNgqlLiteral)
+            .collect::<Vec<_>>();
+        stmt = stmt.value(Literal(row[vid_idx].clone()), &values);
+    }
+    Ok(stmt)
+}
+
+/// Build a multi-row `INSERT EDGE` of `edge`. `src_field` and `dst_field` hold
+/// the vids of the ends, and the optional `rank_field` holds an integer rank.
+pub fn to_insert_edge<T: Serialize>(
+    edge: &str,
+    src_field: &str,
+    dst_field: &str,
+    rank_field: Option<&str>,
+    rows: &[T],
+) -> Result<InsertEdge, NgqlSerializeError> {
+    let (names, rows) = serialize_rows(rows)?;
+    let src_idx = field_index(&names, src_field)?;
+    let dst_idx = field_index(&names, dst_field)?;
+    let rank_idx = rank_field.map(|v| field_index(&names, v)).transpose()?;
+
+    let mut key_idxs = vec![src_idx, dst_idx];
+    key_idxs.extend(rank_idx);
+    let props = prop_names(&names, &key_idxs);
+    let mut stmt = InsertEdge::new(edge, &props);
+    for row in rows {
+        let values = prop_values(&row, &key_idxs);
+        let values = values
+            .iter()
+            .map(|v| v as &dyn ToNgqlLiteral)
+            .collect::<Vec<_>>();
+        let (src, dst) = (Literal(row[src_idx].clone()), Literal(row[dst_idx].clone()));
+        stmt = match rank_idx {
+            Some(idx) => {
+                let rank = row[idx]
+                    .parse::<i64>()
+                    .map_err(|_| NgqlSerializeError::InvalidRank(row[idx].clone()))?;
+                stmt.value_with_rank(src, dst, rank, &values)
+            }
+            None => stmt.value(src, dst, &values),
+        };
+    }
+    Ok(stmt)
+}
+
+/// A literal that's already rendered.
+struct Literal(String);
+
+impl ToNgqlLiteral for Literal {
+    fn to_ngql_literal(&self) -> String {
+        self.0.clone()
+    }
+}
+
+/// The property values of a row, without the fields at `skip`.
+fn prop_values(row: &[String], skip: &[usize]) -> Vec<Literal> {
+    row.iter()
+        .enumerate()
+        .filter(|(i, _)| !skip.contains(i))
+        .map(|(_, v)| Literal(v.clone()))
+        .collect()
+}
+
+fn field_index(names: &[String], field: &str) -> Result<usize, NgqlSerializeError> {
+    names
+        .iter()
+        .position(|v| v == field)
+        .ok_or_else(|| NgqlSerializeError::MissingField(field.to_owned()))
+}
+
+fn prop_names<'a>(names: &'a [String], skip: &[usize]) -> Vec<&'a str> {
+    names
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !skip.contains(i))
+        .map(|(_, v)| v.as_str())
+        .collect()
+}
+
+/// Serialize every row into its field names and literals, and check that all
+/// rows have the same fields.
+fn serialize_rows<T: Serialize>(
+    rows: &[T],
+) -> Result<(Vec<String>, Vec<Vec<String>>), NgqlSerializeError> {
+    let mut names = None;
+    let mut values = Vec::with_capacity(rows.len());
+    for (i, row) in rows.iter().enumerate() {
+        let fields = row.serialize(RowSerializer)?;
+        let (row_names, row_values): (Vec<_>, Vec<_>) = fields.into_iter().unzip();
+        match &names {
+            None => names = Some(row_names),
+            Some(names) if *names != row_names => {
+                return Err(NgqlSerializeError::MismatchedFields(i))
+            }
+            Some(_) => {}
+        }
+        values.push(row_values);
+    }
+    let names = names.ok_or(NgqlSerializeError::NoRows)?;
+    Ok((names, values))
+}
+
+//
+//
+//
+#[derive(Debug, PartialEq, Eq)]
+pub enum NgqlSerializeError {
+    Custom(String),
+    /// The type can't be rendered, e.g. bytes or an enum with data
+    Unsupported(&'static str),
+    /// A row isn't a struct or a map
+    UnsupportedRow(&'static str),
+    MissingField(String),
+    /// The row at the index has other fields than the first one
+    MismatchedFields(usize),
+    InvalidRank(String),
+    NoRows,
+}
+
+impl fmt::Display for NgqlSerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Custom(msg) => write!(f, "Custom {msg}"),
+            Self::Unsupported(ty) => write!(f, "Unsupported {ty}"),
+            Self::UnsupportedRow(ty) => write!(f, "UnsupportedRow {ty}, expected a struct"),
+            Self::MissingField(field) => write!(f, "MissingField {field}"),
+            Self::MismatchedFields(i) => {
+                write!(f, "MismatchedFields row {i} differs from the first row")
+            }
+            Self::InvalidRank(rank) => write!(f, "InvalidRank {rank}"),
+            Self::NoRows => write!(f, "NoRows"),
+        }
+    }
+}
+
+impl std::error::Error for NgqlSerializeError {}
+
+impl ser::Error for NgqlSerializeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+//
+//
+//
+macro_rules! unsupported {
+    ($err:ident, $name:literal, $method:ident($($arg:ty),*) -> $ok:ty) => {
+        fn $method(self, $(_: $arg),*) -> Result<$ok, Self::Error> {
+            Err(NgqlSerializeError::$err($name))
+        }
+    };
+}
+
+macro_rules! unsupported_variants {
+    ($err:ident) => {
+        fn serialize_newtype_variant<T: ?Sized + Serialize>(
+            self,
+            _: &'static str,
+            _: u32,
+            _: &'static str,
+            _: &T,
+        ) -> Result<Self::Ok, Self::Error> {
+            Err(NgqlSerializeError::$err("newtype variant"))
+        }
+        fn serialize_tuple_variant(
+            self,
+            _: &'static str,
+            _: u32,
+            _: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+            Err(NgqlSerializeError::$err("tuple variant"))
+        }
+        fn serialize_struct_variant(
+            self,
+            _: &'static str,
+            _: u32,
+            _: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeStructVariant, Self::Error> {
+            Err(NgqlSerializeError::$err("struct variant"))
+        }
+    };
+}
+
+/// Serializes a row into `(field name, literal)` pairs.
+struct RowSerializer;
+
+struct RowFields(Vec<(String, String)>, Option<String>);
+
+impl ser::Serializer for RowSerializer {
+    type Ok = Vec<(String, String)>;
+    type Error = NgqlSerializeError;
+    type SerializeSeq = Impossible<Self::Ok, Self::Error>;
+    type SerializeTuple = Impossible<Self::Ok, Self::Error>;
+    type SerializeTupleStruct = Impossible<Self::Ok, Self::Error>;
+    type SerializeTupleVariant = Impossible<Self::Ok, Self::Error>;
+    type SerializeMap = RowFields;
+    type SerializeStruct = RowFields;
+    type SerializeStructVariant = Impossible<Self::Ok, Self::Error>;
+
+    unsupported!(UnsupportedRow, "bool", serialize_bool(bool) -> Self::Ok);
+    unsupported!(UnsupportedRow, "i8", serialize_i8(i8) -> Self::Ok);
+    unsupported!(UnsupportedRow, "i16", serialize_i16(i16) -> Self::Ok);
+    unsupported!(UnsupportedRow, "i32", serialize_i32(i32) -> Self::Ok);
+    unsupported!(UnsupportedRow, "i64", serialize_i64(i64) -> Self::Ok);
+    unsupported!(UnsupportedRow, "u8", serialize_u8(u8) -> Self::Ok);
+    unsupported!(UnsupportedRow, "u16", serialize_u16(u16) -> Self::Ok);
+    unsupported!(UnsupportedRow, "u32", serialize_u32(u32) -> Self::Ok);
+    unsupported!(UnsupportedRow, "u64", serialize_u64(u64) -> Self::Ok);
+    unsupported!(UnsupportedRow, "f32", serialize_f32(f32) -> Self::Ok);
+    unsupported!(UnsupportedRow, "f64", serialize_f64(f64) -> Self::Ok);
+    unsupported!(UnsupportedRow, "char", serialize_char(char) -> Self::Ok);
+    unsupported!(UnsupportedRow, "str", serialize_str(&str) -> Self::Ok);
+    unsupported!(UnsupportedRow, "bytes", serialize_bytes(&[u8]) -> Self::Ok);
+    unsupported!(UnsupportedRow, "none", serialize_none() -> Self::Ok);
+    unsupported!(UnsupportedRow, "unit", serialize_unit() -> Self::Ok);
+    unsupported!(
+        UnsupportedRow,
+        "unit struct",
+        serialize_unit_struct(&'static str) -> Self::Ok
+    );
+    unsupported!(
+        UnsupportedRow,
+        "unit variant",
+        serialize_unit_variant(&'static str, u32, &'static str) -> Self::Ok
+    );
+    unsupported!(
+        UnsupportedRow,
+        "seq",
+        serialize_seq(Option<usize>) -> Self::SerializeSeq
+    );
+    unsupported!(UnsupportedRow, "tuple", serialize_tuple(usize) -> Self::SerializeTuple);
+    unsupported!(
+        UnsupportedRow,
+        "tuple struct",
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct
+    );
+    unsupported_variants!(UnsupportedRow);
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(RowFields(Vec::with_capacity(len.unwrap_or(0)), None))
+    }
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(RowFields(Vec::with_capacity(len), None))
+    }
+}
+
+impl ser::SerializeStruct for RowFields {
+    type Ok = Vec<(String, String)>;
+    type Error = NgqlSerializeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.0
+            .push((key.to_owned(), value.serialize(LiteralSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.0)
+    }
+}
+
+/// Maps come from `#[serde(flatten)]`, their keys have to be strings.
+impl ser::SerializeMap for RowFields {
+    type Ok = Vec<(String, String)>;
+    type Error = NgqlSerializeError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.1 = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .1
+            .take()
+            .ok_or_else(|| NgqlSerializeError::Custom("value without a key".to_owned()))?;
+        self.0.push((key, value.serialize(LiteralSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.0)
+    }
+}
+
+//
+//
+//
+/// Serializes a map key, which has to be a string.
+struct KeySerializer;
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = NgqlSerializeError;
+    type SerializeSeq = Impossible<Self::Ok, Self::Error>;
+    type SerializeTuple = Impossible<Self::Ok, Self::Error>;
+    type SerializeTupleStruct = Impossible<Self::Ok, Self::Error>;
+    type SerializeTupleVariant = Impossible<Self::Ok, Self::Error>;
+    type SerializeMap = Impossible<Self::Ok, Self::Error>;
+    type SerializeStruct = Impossible<Self::Ok, Self::Error>;
+    type SerializeStructVariant = Impossible<Self::Ok, Self::Error>;
+
+    unsupported!(Unsupported, "bool key", serialize_bool(bool) -> Self::Ok);
+    unsupported!(Unsupported, "integer key", serialize_i8(i8) -> Self::Ok);
+    unsupported!(Unsupported, "integer key", serialize_i16(i16) -> Self::Ok);
+    unsupported!(Unsupported, "integer key", serialize_i32(i32) -> Self::Ok);
+    unsupported!(Unsupported, "integer key", serialize_i64(i64) -> Self::Ok);
+    unsupported!(Unsupported, "integer key", serialize_u8(u8) -> Self::Ok);
+    unsupported!(Unsupported, "integer key", serialize_u16(u16) -> Self::Ok);
+    unsupported!(Unsupported, "integer key", serialize_u32(u32) -> Self::Ok);
+    unsupported!(Unsupported, "integer key", serialize_u64(u64) -> Self::Ok);
+    unsupported!(Unsupported, "float key", serialize_f32(f32) -> Self::Ok);
+    unsupported!(Unsupported, "float key", serialize_f64(f64) -> Self::Ok);
+    unsupported!(Unsupported, "bytes key", serialize_bytes(&[u8]) -> Self::Ok);
+    unsupported!(Unsupported, "none key", serialize_none() -> Self::Ok);
+    unsupported!(Unsupported, "unit key", serialize_unit() -> Self::Ok);
+    unsupported!(
+        Unsupported,
+        "unit struct key",
+        serialize_unit_struct(&'static str) -> Self::Ok
+    );
+    unsupported!(
+        Unsupported,
+        "seq key",
+        serialize_seq(Option<usize>) -> Self::SerializeSeq
+    );
+    unsupported!(Unsupported, "tuple key", serialize_tuple(usize) -> Self::SerializeTuple);
+    unsupported!(
+        Unsupported,
+        "tuple struct key",
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct
+    );
+    unsupported!(
+        Unsupported,
+        "map key",
+        serialize_map(Option<usize>) -> Self::SerializeMap
+    );
+    unsupported!(
+        Unsupported,
+        "struct key",
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct
+    );
+    unsupported_variants!(Unsupported);
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(v.to_owned())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(variant.to_owned())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+}
+
+//
+//
+//
+/// Serializes a property value into its nGQL literal.
+///
+/// `None` and `()` are `NULL`, sequences are lists, maps and structs are maps,
+/// and unit variants of enums are strings of the variant name.
+struct LiteralSerializer;
+
+struct Compound {
+    items: Vec<String>,
+    key: Option<String>,
+    is_map: bool,
+}
+
+impl Compound {
+    fn new(len: usize, is_map: bool) -> Self {
+        Self {
+            items: Vec::with_capacity(len),
+            key: None,
+            is_map,
+        }
+    }
+
+    fn push_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), NgqlSerializeError> {
+        let value = value.serialize(LiteralSerializer)?;
+        self.items.push(format!("{}: {value}", identifier(key)));
+        Ok(())
+    }
+
+    fn finish(self) -> String {
+        if self.is_map {
+            format!("{{{}}}", self.items.join(", "))
+        } else {
+            format!("[{}]", self.items.join(", "))
+        }
+    }
+}
+
+macro_rules! serialize_as_literal {
+    ($($method:ident($t:ty)),*) => {
+        $(
+            fn $method(self, v: $t) -> Result<Self::Ok, Self::Error> {
+                Ok(v.to_ngql_literal())
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for LiteralSerializer {
+    type Ok = String;
+    type Error = NgqlSerializeError;
+    type SerializeSeq = Compound;
+    type SerializeTuple = Compound;
+    type SerializeTupleStruct = Compound;
+    type SerializeTupleVariant = Impossible<Self::Ok, Self::Error>;
+    type SerializeMap = Compound;
+    type SerializeStruct = Compound;
+    type SerializeStructVariant = Impossible<Self::Ok, Self::Error>;
+
+    serialize_as_literal!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str)
+    );
+    unsupported!(Unsupported, "bytes", serialize_bytes(&[u8]) -> Self::Ok);
+    unsupported_variants!(Unsupported);
+
+    /// graphd's integers are `i64`, larger values have no literal.
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        i64::try_from(v)
+            .map(|v| v.to_ngql_literal())
+            .map_err(|_| NgqlSerializeError::Unsupported("u64 above i64::MAX"))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok("NULL".to_owned())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok("NULL".to_owned())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok("NULL".to_owned())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(variant.to_ngql_literal())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(Compound::new(len.unwrap_or(0), false))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Ok(Compound::new(len, false))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Ok(Compound::new(len, false))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(Compound::new(len.unwrap_or(0), true))
+    }
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(Compound::new(len, true))
+    }
+}
+
+impl ser::SerializeSeq for Compound {
+    type Ok = String;
+    type Error = NgqlSerializeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.items.push(value.serialize(LiteralSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for Compound {
+    type Ok = String;
+    type Error = NgqlSerializeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound {
+    type Ok = String;
+    type Error = NgqlSerializeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeMap for Compound {
+    type Ok = String;
+    type Error = NgqlSerializeError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| NgqlSerializeError::Custom("value without a key".to_owned()))?;
+        self.push_field(&key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for Compound {
+    type Ok = String;
+    type Error = NgqlSerializeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.push_field(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Player {
+        vid: String,
+        #[serde(rename(serialize = "name"))]
+        player_name: String,
+        age: Option<i64>,
+        #[serde(skip)]
+        #[allow(dead_code)]
+        ignored: bool,
+    }
+
+    #[derive(Serialize)]
+    struct Follow {
+        src: String,
+        dst: String,
+        rank: i64,
+        degree: f64,
+        tags: Vec<&'static str>,
+    }
+
+    #[test]
+    fn test_to_insert_vertex() -> Result<(), Box<dyn std::error::Error>> {
+        let players = vec![
+            Player {
+                vid: "player100".to_owned(),
+                player_name: "Tim \"The Big\" Duncan".to_owned(),
+                age: Some(42),
+                ignored: true,
+            },
+            Player {
+                vid: "player101".to_owned(),
+                player_name: "Tony Parker".to_owned(),
+                age: None,
+                ignored: false,
+            },
+        ];
+        let stmt = to_insert_vertex("player", "vid", &players)?.if_not_exists();
+        assert_eq!(
+            stmt.to_string(),
+            r#"INSERT VERTEX IF NOT EXISTS `player`(`name`, `age`) VALUES "player100":("Tim \"The Big\" Duncan", 42), "player101":("Tony Parker", NULL)"#
+        );
+
+        assert_eq!(
+            to_insert_vertex("player", "id", &players).unwrap_err(),
+            NgqlSerializeError::MissingField("id".to_owned())
+        );
+        assert_eq!(
+            to_insert_vertex::<Player>("player", "vid", &[]).unwrap_err(),
+            NgqlSerializeError::NoRows
+        );
+        assert_eq!(
+            to_insert_vertex("player", "vid", &[1, 2]).unwrap_err(),
+            NgqlSerializeError::UnsupportedRow("i32")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_insert_vertex_with_map() -> Result<(), Box<dyn std::error::Error>> {
+        let rows = vec![
+            BTreeMap::from([("vid", 1), ("age", 42)]),
+            BTreeMap::from([("vid", 2), ("name", 36)]),
+        ];
+        assert_eq!(
+            to_insert_vertex("player", "vid", &rows).unwrap_err(),
+            NgqlSerializeError::MismatchedFields(1)
+        );
+
+        let stmt = to_insert_vertex("player", "vid", &rows[..1])?;
+        assert_eq!(
+            stmt.to_string(),
+            "INSERT VERTEX `player`(`age`) VALUES 1:(42)"
+        );
+
+        let rows = [BTreeMap::from([("vid", 1_u64), ("age", i64::MAX as u64)])];
+        assert_eq!(
+            to_insert_vertex("player", "vid", &rows)?.to_string(),
+            "INSERT VERTEX `player`(`age`) VALUES 1:(9223372036854775807)"
+        );
+        let rows = [BTreeMap::from([("vid", 1_usize), ("age", usize::MAX)])];
+        assert_eq!(
+            to_insert_vertex("player", "vid", &rows).unwrap_err(),
+            NgqlSerializeError::Unsupported("u64 above i64::MAX")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_insert_edge() -> Result<(), Box<dyn std::error::Error>> {
+        let follows = vec![Follow {
+            src: "a".to_owned(),
+            dst: "b".to_owned(),
+            rank: 1,
+            degree: 95.0,
+            tags: vec!["x", "y"],
+        }];
+        let stmt = to_insert_edge("follow", "src", "dst", Some("rank"), &follows)?;
+        assert_eq!(
+            stmt.to_string(),
+            r#"INSERT EDGE `follow`(`degree`, `tags`) VALUES "a"->"b"@1:(95.0, ["x", "y"])"#
+        );
+
+        let stmt = to_insert_edge("follow", "src", "dst", None, &follows)?;
+        assert_eq!(
+            stmt.to_string(),
+            r#"INSERT EDGE `follow`(`rank`, `degree`, `tags`) VALUES "a"->"b":(1, 95.0, ["x", "y"])"#
+        );
+
+        assert_eq!(
+            to_insert_edge("follow", "src", "dst", Some("src"), &follows).unwrap_err(),
+            NgqlSerializeError::InvalidRank(r#""a""#.to_owned())
+        );
+
+        Ok(())
+    }
+}
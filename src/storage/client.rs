@@ -6,10 +6,7 @@ use fbthrift::{
     BinaryProtocol, BufMutExt, Framing, FramingDecoded, FramingEncodedFinal, ProtocolEncoded,
     Transport,
 };
-use fbthrift_transport::{
-    impl_tokio::{TokioSleep, TokioTcpStream},
-    AsyncTransport, AsyncTransportConfiguration,
-};
+use fbthrift_transport::{impl_tokio::TokioSleep, AsyncTransport, AsyncTransportConfiguration};
 use nebula_fbthrift_storage_v3::{
     client::{GraphStorageService, GraphStorageServiceImpl},
     errors::graph_storage_service::{ScanEdgeError, ScanVertexError},
@@ -23,10 +20,10 @@ use super::{
 };
 use crate::{common::types::HostAddr, meta::client::MetaClientError};
 use crate::{storage::query::StorageQueryOutput, MetaTransportResponseHandler};
-use crate::{MetaClient, TimezoneInfo};
+use crate::{stream, MetaClient, NebulaStream, TimezoneInfo, TlsConfig};
 
 pub(super) struct StorageConnection<
-    T = AsyncTransport<TokioTcpStream, TokioSleep, StorageTransportResponseHandler>,
+    T = AsyncTransport<NebulaStream, TokioSleep, StorageTransportResponseHandler>,
 > where
     T: Transport + Framing<DecBuf = std::io::Cursor<Bytes>>,
     Bytes: Framing<DecBuf = FramingDecoded<T>>,
@@ -66,13 +63,20 @@ where
 }
 
 impl StorageConnection {
-    async fn new(addr: &str) -> Result<Self, StorageClientError> {
-        let transport = AsyncTransport::with_tokio_tcp_connect(
-            addr,
+    async fn new(addr: &HostAddr, tls: Option<&TlsConfig>) -> Result<Self, StorageClientError> {
+        let port = u16::try_from(addr.port).map_err(|err| {
+            StorageClientError::CreateTransportError(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                err,
+            ))
+        })?;
+        let stream = stream::connect(&addr.host, port, tls)
+            .await
+            .map_err(StorageClientError::CreateTransportError)?;
+        let transport = AsyncTransport::new(
+            stream,
             AsyncTransportConfiguration::new(StorageTransportResponseHandler),
-        )
-        .await
-        .map_err(StorageClientError::CreateTransportError)?;
+        );
         Ok(Self {
             service: GraphStorageServiceImpl::<BinaryProtocol, _>::new(transport),
         })
@@ -83,8 +87,8 @@ impl StorageConnection {
 //
 //
 pub struct StorageClient<
-    MT = AsyncTransport<TokioTcpStream, TokioSleep, MetaTransportResponseHandler>,
-    ST = AsyncTransport<TokioTcpStream, TokioSleep, StorageTransportResponseHandler>,
+    MT = AsyncTransport<NebulaStream, TokioSleep, MetaTransportResponseHandler>,
+    ST = AsyncTransport<NebulaStream, TokioSleep, StorageTransportResponseHandler>,
 > where
     MT: Transport + Framing<DecBuf = std::io::Cursor<Bytes>, EncBuf = bytes::BytesMut>,
     ST: Transport + Framing<DecBuf = std::io::Cursor<Bytes>, EncBuf = bytes::BytesMut>,
@@ -95,6 +99,7 @@ pub struct StorageClient<
     pub(super) connection_map: HashMap<HostAddr, StorageConnection<ST>>,
     mclient: MetaClient<MT>,
     pub(super) timezone_info: TimezoneInfo,
+    tls: Option<TlsConfig>,
 }
 
 const K_VID: &str = "_vid";
@@ -114,6 +119,17 @@ where
             connection_map: HashMap::new(),
             mclient,
             timezone_info: TimezoneInfo {},
+            tls: None,
+        }
+    }
+
+    /// Connect to the storaged servers with TLS.
+    pub async fn new_with_tls(mclient: MetaClient<MT>, tls: TlsConfig) -> Self {
+        Self {
+            connection_map: HashMap::new(),
+            mclient,
+            timezone_info: TimezoneInfo {},
+            tls: Some(tls),
         }
     }
 
@@ -159,9 +175,8 @@ where
             .await
             .map_err(StorageClientError::MetaClientError)?;
         for (_, host_addr) in result_map {
-            let saddr = format!("{}:{}", host_addr.host, host_addr.port);
             if !self.connection_map.contains_key(host_addr) {
-                let conn = StorageConnection::new(&saddr).await?;
+                let conn = StorageConnection::new(host_addr, self.tls.as_ref()).await?;
                 self.connection_map.insert(host_addr.clone(), conn);
             }
         }
@@ -218,9 +233,8 @@ where
             .await
             .map_err(StorageClientError::MetaClientError)?;
         for (_, host_addr) in result_map {
-            let saddr = format!("{}:{}", host_addr.host, host_addr.port);
             if !self.connection_map.contains_key(host_addr) {
-                let conn = StorageConnection::new(&saddr).await?;
+                let conn = StorageConnection::new(host_addr, self.tls.as_ref()).await?;
                 self.connection_map.insert(host_addr.clone(), conn);
             }
         }
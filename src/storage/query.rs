@@ -5,10 +5,7 @@ use fbthrift::{
     BinaryProtocol, BufMutExt, Framing, FramingDecoded, FramingEncodedFinal, ProtocolEncoded,
     Transport,
 };
-use fbthrift_transport::{
-    impl_tokio::{TokioSleep, TokioTcpStream},
-    AsyncTransport,
-};
+use fbthrift_transport::{impl_tokio::TokioSleep, AsyncTransport};
 use nebula_fbthrift_storage_v3::{
     errors::graph_storage_service::{ScanEdgeError, ScanVertexError},
     types::{ScanEdgeRequest, ScanResponse, ScanVertexRequest},
@@ -19,11 +16,11 @@ use serde::de::DeserializeOwned;
 use crate::dataset_wrapper::{DataSetError, DataSetWrapper, Record};
 use crate::dataset_wrapper_proxy;
 use crate::value_wrapper::ValueWrapper;
-use crate::TimezoneInfo;
 use crate::{
     common::{types::HostAddr, Row},
     MetaTransportResponseHandler,
 };
+use crate::{NebulaStream, TimezoneInfo};
 
 use super::{StorageClient, StorageTransportResponseHandler};
 
@@ -33,8 +30,8 @@ const DEFAULT_LIMIT: i64 = 1000;
 
 pub struct StorageScanVertexOutput<
     'a,
-    MT = AsyncTransport<TokioTcpStream, TokioSleep, MetaTransportResponseHandler>,
-    ST = AsyncTransport<TokioTcpStream, TokioSleep, StorageTransportResponseHandler>,
+    MT = AsyncTransport<NebulaStream, TokioSleep, MetaTransportResponseHandler>,
+    ST = AsyncTransport<NebulaStream, TokioSleep, StorageTransportResponseHandler>,
 > where
     MT: Transport + Framing<DecBuf = std::io::Cursor<bytes::Bytes>, EncBuf = bytes::BytesMut>,
     ST: Transport + Framing<DecBuf = std::io::Cursor<bytes::Bytes>, EncBuf = bytes::BytesMut>,
@@ -72,46 +69,72 @@ where
 
     pub async fn execute(&mut self) -> Result<Vec<StorageQueryOutput>, StorageQueryError> {
         let mut data_set = vec![];
-
         for (part_id, leader) in &self.leader_map {
-            println!("Part ID: {}, Leader: {:?}", part_id, leader);
-
-            let cursor = ScanCursor {
-                next_cursor: None, // Option 为空
-                ..Default::default()
-            };
-
-            let mut part: BTreeMap<i32, ScanCursor> = BTreeMap::new();
-            part.insert(*part_id, cursor);
-
-            let resp = self.sclient.connection_map[leader]
-                .scan_vertex(&ScanVertexRequest {
-                    space_id: self.space_id,
-                    parts: part,
-                    return_columns: vec![self.vertex_prop.clone().unwrap()],
-                    limit: DEFAULT_LIMIT,
-                    start_time: Some(DEFAULT_START_TIME),
-                    end_time: Some(DEFAULT_END_TIME),
-                    filter: None,
-                    only_latest_version: false,
-                    enable_read_from_follower: true,
-                    common: None,
-                    ..Default::default()
-                })
-                .await
-                .map_err(StorageQueryError::ScanVertexError)?;
-            let resp = StorageQueryOutput::new(resp, self.sclient.timezone_info.clone());
-
-            data_set.push(resp);
+            data_set.push(self.scan_part(*part_id, leader).await?);
         }
         Ok(data_set)
     }
+
+    /// Scan a partition on its leader.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "scan_vertex",
+            level = "debug",
+            skip(self, leader),
+            fields(space_id = self.space_id, host = ?leader, error_code = tracing::field::Empty),
+            err(Display)
+        )
+    )]
+    async fn scan_part(
+        &self,
+        part_id: i32,
+        leader: &HostAddr,
+    ) -> Result<StorageQueryOutput, StorageQueryError> {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(part_id, leader = ?leader, "scan partition");
+
+        let cursor = ScanCursor {
+            next_cursor: None, // Option 为空
+            ..Default::default()
+        };
+
+        let mut part: BTreeMap<i32, ScanCursor> = BTreeMap::new();
+        part.insert(part_id, cursor);
+
+        let resp = self.sclient.connection_map[leader]
+            .scan_vertex(&ScanVertexRequest {
+                space_id: self.space_id,
+                parts: part,
+                return_columns: vec![self.vertex_prop.clone().unwrap()],
+                limit: DEFAULT_LIMIT,
+                start_time: Some(DEFAULT_START_TIME),
+                end_time: Some(DEFAULT_END_TIME),
+                filter: None,
+                only_latest_version: false,
+                enable_read_from_follower: true,
+                common: None,
+                ..Default::default()
+            })
+            .await
+            .map_err(StorageQueryError::ScanVertexError)?;
+
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record(
+            "error_code",
+            tracing::field::display(scan_error_code(&resp)),
+        );
+        Ok(StorageQueryOutput::new(
+            resp,
+            self.sclient.timezone_info.clone(),
+        ))
+    }
 }
 
 pub struct StorageScanEdgeOutput<
     'a,
-    MT = AsyncTransport<TokioTcpStream, TokioSleep, MetaTransportResponseHandler>,
-    ST = AsyncTransport<TokioTcpStream, TokioSleep, StorageTransportResponseHandler>,
+    MT = AsyncTransport<NebulaStream, TokioSleep, MetaTransportResponseHandler>,
+    ST = AsyncTransport<NebulaStream, TokioSleep, StorageTransportResponseHandler>,
 > where
     MT: Transport + Framing<DecBuf = std::io::Cursor<bytes::Bytes>, EncBuf = bytes::BytesMut>,
     ST: Transport + Framing<DecBuf = std::io::Cursor<bytes::Bytes>, EncBuf = bytes::BytesMut>,
@@ -149,40 +172,75 @@ where
 
     pub async fn execute(&mut self) -> Result<Vec<StorageQueryOutput>, StorageQueryError> {
         let mut data_set = vec![];
-
         for (part_id, leader) in &self.leader_map {
-            println!("Part ID: {}, Leader: {:?}", part_id, leader);
-
-            let cursor = ScanCursor {
-                next_cursor: None, // Option 为空
-                ..Default::default()
-            };
-
-            let mut part: BTreeMap<i32, ScanCursor> = BTreeMap::new();
-            part.insert(*part_id, cursor);
-
-            let resp = self.sclient.connection_map[leader]
-                .scan_edge(&ScanEdgeRequest {
-                    space_id: self.space_id,
-                    parts: part,
-                    return_columns: vec![self.edge_prop.clone().unwrap()],
-                    limit: DEFAULT_LIMIT,
-                    start_time: Some(DEFAULT_START_TIME),
-                    end_time: Some(DEFAULT_END_TIME),
-                    filter: None,
-                    only_latest_version: false,
-                    enable_read_from_follower: true,
-                    common: None,
-                    ..Default::default()
-                })
-                .await
-                .map_err(StorageQueryError::ScanEdgeError)?;
-            let resp = StorageQueryOutput::new(resp, self.sclient.timezone_info.clone());
-
-            data_set.push(resp);
+            data_set.push(self.scan_part(*part_id, leader).await?);
         }
         Ok(data_set)
     }
+
+    /// Scan a partition on its leader.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "scan_edge",
+            level = "debug",
+            skip(self, leader),
+            fields(space_id = self.space_id, host = ?leader, error_code = tracing::field::Empty),
+            err(Display)
+        )
+    )]
+    async fn scan_part(
+        &self,
+        part_id: i32,
+        leader: &HostAddr,
+    ) -> Result<StorageQueryOutput, StorageQueryError> {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(part_id, leader = ?leader, "scan partition");
+
+        let cursor = ScanCursor {
+            next_cursor: None, // Option 为空
+            ..Default::default()
+        };
+
+        let mut part: BTreeMap<i32, ScanCursor> = BTreeMap::new();
+        part.insert(part_id, cursor);
+
+        let resp = self.sclient.connection_map[leader]
+            .scan_edge(&ScanEdgeRequest {
+                space_id: self.space_id,
+                parts: part,
+                return_columns: vec![self.edge_prop.clone().unwrap()],
+                limit: DEFAULT_LIMIT,
+                start_time: Some(DEFAULT_START_TIME),
+                end_time: Some(DEFAULT_END_TIME),
+                filter: None,
+                only_latest_version: false,
+                enable_read_from_follower: true,
+                common: None,
+                ..Default::default()
+            })
+            .await
+            .map_err(StorageQueryError::ScanEdgeError)?;
+
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record(
+            "error_code",
+            tracing::field::display(scan_error_code(&resp)),
+        );
+        Ok(StorageQueryOutput::new(
+            resp,
+            self.sclient.timezone_info.clone(),
+        ))
+    }
+}
+
+/// Code of the first failed partition of the response, if any.
+#[cfg(feature = "tracing")]
+fn scan_error_code(resp: &ScanResponse) -> crate::common::types::ErrorCode {
+    resp.result
+        .failed_parts
+        .first()
+        .map_or(crate::common::types::ErrorCode::SUCCEEDED, |v| v.code)
 }
 
 #[derive(Debug)]
@@ -0,0 +1,82 @@
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::io::Error as IoError;
+
+use futures_util::io::{AsyncRead, AsyncWrite};
+
+use crate::TlsConfig;
+
+/// The stream used by the transports to graphd, metad and storaged.
+/// It's a plain TCP connection, or a TLS connection with the `tls` feature.
+pub enum NebulaStream {
+    Tcp(async_compat::Compat<tokio::net::TcpStream>),
+    #[cfg(feature = "tls")]
+    Tls(Box<async_compat::Compat<tokio_rustls::client::TlsStream<tokio::net::TcpStream>>>),
+}
+
+/// Connect to `host:port`, with a TLS handshake if `tls` is set.
+pub(crate) async fn connect(
+    host: &str,
+    port: u16,
+    tls: Option<&TlsConfig>,
+) -> Result<NebulaStream, IoError> {
+    let tcp_stream = tokio::net::TcpStream::connect((host, port)).await?;
+    match tls {
+        #[cfg(feature = "tls")]
+        Some(tls) => {
+            let tls_stream = tls.connect(host, tcp_stream).await?;
+            Ok(NebulaStream::Tls(Box::new(async_compat::Compat::new(
+                tls_stream,
+            ))))
+        }
+        #[cfg(not(feature = "tls"))]
+        Some(tls) => match *tls {},
+        None => Ok(NebulaStream::Tcp(async_compat::Compat::new(tcp_stream))),
+    }
+}
+
+impl AsyncRead for NebulaStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, IoError>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for NebulaStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, IoError>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_close(cx),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_close(cx),
+        }
+    }
+}
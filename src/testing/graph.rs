@@ -0,0 +1,840 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use fbthrift::ProtocolReader as _;
+use nebula_fbthrift_graph_v3::{
+    dependencies::common::types::{DataSet, ErrorCode, Row, Value},
+    services::graph_service::{
+        AuthenticateExn, ExecuteExn, ExecuteWithParameterExn, VerifyClientVersionExn,
+    },
+    types::{AuthResponse, ExecutionResponse, VerifyClientVersionReq, VerifyClientVersionResp},
+};
+
+use super::server::{
+    read, read_args, read_req, serve, BoxError, Deserializer, Outcome, Reply, ServerHandle, Service,
+};
+use crate::HostAddress;
+
+/// A call received by `FakeGraphServer`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphRequest {
+    VerifyClientVersion(String),
+    Authenticate {
+        username: String,
+        password: String,
+    },
+    Execute {
+        session_id: i64,
+        stmt: String,
+        params: Option<BTreeMap<Vec<u8>, Value>>,
+    },
+    Signout(i64),
+}
+
+#[derive(Default)]
+struct GraphScript {
+    next_session_id: i64,
+    /// Authenticated sessions and their current space
+    sessions: HashMap<i64, Option<String>>,
+    verify_client_version: VecDeque<Reply<VerifyClientVersionResp>>,
+    authenticate: VecDeque<Reply<AuthResponse>>,
+    execute: VecDeque<Reply<ExecutionResponse>>,
+    statements: HashMap<String, Reply<ExecutionResponse>>,
+    requests: Vec<GraphRequest>,
+}
+
+impl GraphScript {
+    fn authenticate(&mut self) -> Reply<AuthResponse> {
+        let mut reply = self.authenticate.pop_front().unwrap_or_else(|| {
+            Reply::new(AuthResponse {
+                error_code: ErrorCode::SUCCEEDED,
+                ..Default::default()
+            })
+        });
+        if let Some(res) = reply.response_mut() {
+            if res.error_code == ErrorCode::SUCCEEDED {
+                let session_id = *res.session_id.get_or_insert_with(|| {
+                    self.next_session_id += 1;
+                    self.next_session_id
+                });
+                self.sessions.insert(session_id, None);
+            }
+        }
+        reply
+    }
+
+    fn execute(&mut self, session_id: i64, stmt: &str) -> Reply<ExecutionResponse> {
+        let space = match self.sessions.get_mut(&session_id) {
+            Some(space) => space,
+            None => {
+                return Reply::new(error_response(
+                    ErrorCode::E_SESSION_INVALID,
+                    "Session not existed!",
+                ))
+            }
+        };
+        let mut reply = self
+            .statements
+            .get(stmt)
+            .cloned()
+            .or_else(|| self.execute.pop_front())
+            .unwrap_or_else(|| Reply::new(response(None)));
+        if let Some(res) = reply.response_mut() {
+            if res.error_code == ErrorCode::SUCCEEDED {
+                if let Some(name) = used_space(stmt) {
+                    *space = Some(name);
+                }
+                if res.space_name.is_none() {
+                    res.space_name = space.clone().map(String::into_bytes);
+                }
+            }
+        }
+        reply
+    }
+}
+
+/// The space of a `USE` statement.
+fn used_space(stmt: &str) -> Option<String> {
+    let stmt = stmt.trim().trim_end_matches(';').trim();
+    let (keyword, name) = stmt.split_once(char::is_whitespace)?;
+    if !keyword.eq_ignore_ascii_case("USE") {
+        return None;
+    }
+    Some(name.trim().trim_matches('`').to_owned())
+}
+
+struct GraphService {
+    script: Mutex<GraphScript>,
+}
+
+impl Service for GraphService {
+    fn call(&self, name: &str, seqid: u32, des: &mut Deserializer) -> Result<Outcome, BoxError> {
+        let mut script = self.script.lock().unwrap();
+        match name {
+            "verifyClientVersion" => {
+                let req: VerifyClientVersionReq = read_req(des)?;
+                script.requests.push(GraphRequest::VerifyClientVersion(
+                    String::from_utf8_lossy(&req.version).to_string(),
+                ));
+                let reply = script.verify_client_version.pop_front().unwrap_or_else(|| {
+                    Reply::new(VerifyClientVersionResp {
+                        error_code: ErrorCode::SUCCEEDED,
+                        ..Default::default()
+                    })
+                });
+                Ok(reply.into_outcome(name, seqid, VerifyClientVersionExn::Success))
+            }
+            "authenticate" => {
+                let (mut username, mut password) = (vec![], vec![]);
+                read_args(des, |des, id, field_type| {
+                    match id {
+                        1 => username = read(des)?,
+                        2 => password = read(des)?,
+                        _ => des.skip(field_type)?,
+                    }
+                    Ok(())
+                })?;
+                script.requests.push(GraphRequest::Authenticate {
+                    username: String::from_utf8_lossy(&username).to_string(),
+                    password: String::from_utf8_lossy(&password).to_string(),
+                });
+                Ok(script
+                    .authenticate()
+                    .into_outcome(name, seqid, AuthenticateExn::Success))
+            }
+            "signout" => {
+                let mut session_id = 0;
+                read_args(des, |des, id, field_type| {
+                    match id {
+                        1 => session_id = read(des)?,
+                        _ => des.skip(field_type)?,
+                    }
+                    Ok(())
+                })?;
+                script.requests.push(GraphRequest::Signout(session_id));
+                script.sessions.remove(&session_id);
+                Ok(Outcome::NoReply)
+            }
+            "execute" | "executeWithParameter" => {
+                let (mut session_id, mut stmt, mut params) = (0, vec![], None);
+                read_args(des, |des, id, field_type| {
+                    match id {
+                        1 => session_id = read(des)?,
+                        2 => stmt = read(des)?,
+                        3 => params = Some(read(des)?),
+                        _ => des.skip(field_type)?,
+                    }
+                    Ok(())
+                })?;
+                let stmt = String::from_utf8_lossy(&stmt).to_string();
+                let reply = script.execute(session_id, &stmt);
+                script.requests.push(GraphRequest::Execute {
+                    session_id,
+                    stmt,
+                    params,
+                });
+                Ok(if name == "execute" {
+                    reply.into_outcome(name, seqid, ExecuteExn::Success)
+                } else {
+                    reply.into_outcome(name, seqid, ExecuteWithParameterExn::Success)
+                })
+            }
+            _ => Err(format!("GraphService doesn't implement {name}").into()),
+        }
+    }
+}
+
+//
+//
+//
+/// An in-process graphd, answering from scripted responses.
+///
+/// Without a script, `authenticate` opens a new session, and `execute` succeeds
+/// with an empty response for authenticated sessions and fails with
+/// `E_SESSION_INVALID` otherwise. `USE` statements switch the space of the
+/// session, which is reported in the responses. `executeJson` isn't supported.
+/// ## Example
+/// ```ignore
+/// use rust_nebula::testing::{graph, FakeGraphServer, Reply};
+///
+/// let server = FakeGraphServer::start().await?;
+/// server.on_statement(
+///     "YIELD 1 AS n;",
+///     Reply::new(graph::response(Some(graph::data_set(&["n"], vec![vec![Value::iVal(1)]])))),
+/// );
+/// let conf = SingleConnSessionConf::new(vec![server.addr()], "root".to_owned(), "nebula".to_owned(), None);
+/// ```
+pub struct FakeGraphServer {
+    handle: ServerHandle,
+    service: Arc<GraphService>,
+}
+
+impl FakeGraphServer {
+    /// Listen on a free port of localhost.
+    pub async fn start() -> io::Result<Self> {
+        let service = Arc::new(GraphService {
+            script: Mutex::new(GraphScript::default()),
+        });
+        let handle = serve(service.clone()).await?;
+        Ok(Self { handle, service })
+    }
+
+    pub fn addr(&self) -> HostAddress {
+        let addr = self.handle.addr();
+        HostAddress::new(&addr.ip().to_string(), addr.port())
+    }
+
+    fn script(&self) -> std::sync::MutexGuard<'_, GraphScript> {
+        self.service.script.lock().unwrap()
+    }
+
+    /// Answer the next `verifyClientVersion` with `reply`.
+    pub fn push_verify_client_version(&self, reply: Reply<VerifyClientVersionResp>) {
+        self.script().verify_client_version.push_back(reply);
+    }
+
+    /// Answer the next `authenticate` with `reply`. A successful response without
+    /// a session id gets a new one.
+    pub fn push_authenticate(&self, reply: Reply<AuthResponse>) {
+        self.script().authenticate.push_back(reply);
+    }
+
+    /// Answer the next statement that has no reply of `on_statement` with `reply`.
+    pub fn push_execute(&self, reply: Reply<ExecutionResponse>) {
+        self.script().execute.push_back(reply);
+    }
+
+    /// Answer every execution of `stmt` with `reply`.
+    pub fn on_statement(&self, stmt: &str, reply: Reply<ExecutionResponse>) {
+        self.script().statements.insert(stmt.to_owned(), reply);
+    }
+
+    /// Forget the session, like graphd does when it times out.
+    pub fn expire_session(&self, session_id: i64) {
+        self.script().sessions.remove(&session_id);
+    }
+
+    /// The sessions that are authenticated and not signed out, sorted.
+    pub fn sessions(&self) -> Vec<i64> {
+        let mut sessions = self.script().sessions.keys().copied().collect::<Vec<_>>();
+        sessions.sort();
+        sessions
+    }
+
+    /// The calls received so far, in order.
+    pub fn requests(&self) -> Vec<GraphRequest> {
+        self.script().requests.clone()
+    }
+
+    /// The statements executed so far, in order.
+    pub fn statements(&self) -> Vec<String> {
+        self.requests()
+            .into_iter()
+            .filter_map(|v| match v {
+                GraphRequest::Execute { stmt, .. } => Some(stmt),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A successful response with `data`.
+pub fn response(data: Option<DataSet>) -> ExecutionResponse {
+    ExecutionResponse {
+        error_code: ErrorCode::SUCCEEDED,
+        data,
+        ..Default::default()
+    }
+}
+
+/// A failed response.
+pub fn error_response(error_code: ErrorCode, error_msg: &str) -> ExecutionResponse {
+    ExecutionResponse {
+        error_code,
+        error_msg: Some(error_msg.as_bytes().to_vec()),
+        ..Default::default()
+    }
+}
+
+pub fn data_set(column_names: &[&str], rows: Vec<Vec<Value>>) -> DataSet {
+    DataSet {
+        column_names: column_names.iter().map(|v| v.as_bytes().to_vec()).collect(),
+        rows: rows
+            .into_iter()
+            .map(|values| Row {
+                values,
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use serde::Deserialize;
+
+    use crate::graph::{
+        ConnectionPool, ConnectionPoolConf, GraphQuery as _, GraphQueryError, ReauthPolicy,
+        SessionPoolManager,
+    };
+    use crate::{SingleConnSessionConf, SingleConnSessionError, SingleConnSessionManager};
+
+    #[derive(Deserialize, Debug)]
+    struct N {
+        n: i64,
+    }
+
+    fn conf(server: &FakeGraphServer) -> SingleConnSessionConf {
+        SingleConnSessionConf::new(
+            vec![server.addr()],
+            "root".to_owned(),
+            "nebula".to_owned(),
+            Some("test".to_owned()),
+        )
+    }
+
+    #[test]
+    fn test_used_space() {
+        assert_eq!(used_space("USE `my space`;"), Some("my space".to_owned()));
+        assert_eq!(used_space(" use test "), Some("test".to_owned()));
+        assert_eq!(used_space("USER"), None);
+    }
+
+    #[tokio::test]
+    async fn test_session() -> Result<(), Box<dyn std::error::Error>> {
+        let server = FakeGraphServer::start().await?;
+        server.on_statement(
+            "YIELD 1 AS n;",
+            Reply::new(response(Some(data_set(&["n"], vec![vec![Value::iVal(1)]])))),
+        );
+
+        let manager = SingleConnSessionManager::new(conf(&server));
+        let mut session = manager.get_session().await?;
+        let output = session.query("YIELD 1 AS n;").await?;
+        assert_eq!(output.scan::<N>()?[0].n, 1);
+        assert_eq!(output.get_space_name(), Some("test".to_owned()));
+        session.signout().await?;
+
+        assert_eq!(
+            server.requests()[..3],
+            [
+                GraphRequest::VerifyClientVersion("3.0.0".to_owned()),
+                GraphRequest::Authenticate {
+                    username: "root".to_owned(),
+                    password: "nebula".to_owned()
+                },
+                GraphRequest::Execute {
+                    session_id: 1,
+                    stmt: "Use test;".to_owned(),
+                    params: None
+                },
+            ]
+        );
+        assert_eq!(server.statements().len(), 2);
+        // `signout` isn't answered, so wait for the server to read it.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(server.sessions().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pool() -> Result<(), Box<dyn std::error::Error>> {
+        let server = FakeGraphServer::start().await?;
+        let pool = bb8::Pool::builder()
+            .max_size(2)
+            .build(SingleConnSessionManager::new(conf(&server)))
+            .await?;
+
+        for _ in 0..2 {
+            let mut first = pool.get().await?;
+            let mut second = pool.get().await?;
+            first.execute("YIELD 1;").await?;
+            second.execute("YIELD 2;").await?;
+        }
+        assert_eq!(pool.state().connections, 2);
+        assert_eq!(server.sessions(), vec![1, 2]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_errors() -> Result<(), Box<dyn std::error::Error>> {
+        let server = FakeGraphServer::start().await?;
+        let manager = SingleConnSessionManager::new(conf(&server));
+
+        server.push_authenticate(Reply::new(AuthResponse {
+            error_code: ErrorCode::E_BAD_USERNAME_PASSWORD,
+            ..Default::default()
+        }));
+        assert!(matches!(
+            manager.get_session().await,
+            Err(SingleConnSessionError::AuthenticateError(_))
+        ));
+
+        let mut session = manager.get_session().await?;
+        server.push_execute(Reply::new(error_response(
+            ErrorCode::E_SYNTAX_ERROR,
+            "syntax error",
+        )));
+        assert!(session.execute("MATC (v);").await.is_err());
+        assert!(!session.is_close_required());
+
+        server.push_execute(Reply::new(response(None)).delay(Duration::from_secs(1)));
+        assert!(matches!(
+            session
+                .query_with_timeout("YIELD 1;", Duration::from_millis(100))
+                .await,
+            Err(SingleConnSessionError::QueryTimeout(_, _))
+        ));
+        assert!(session.is_close_required());
+
+        let mut session = manager.get_session().await?;
+        server.push_execute(Reply::disconnect());
+        assert!(session.execute("YIELD 1;").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_kill_timed_out_query() -> Result<(), Box<dyn std::error::Error>> {
+        let server = FakeGraphServer::start().await?;
+        let manager = SingleConnSessionManager::new(conf(&server));
+        server.on_statement(
+            "SHOW ALL QUERIES;",
+            Reply::new(response(Some(data_set(
+                &["SessionID", "ExecutionPlanID", "Status"],
+                vec![
+                    vec![
+                        Value::iVal(1),
+                        Value::iVal(42),
+                        Value::sVal(b"RUNNING".to_vec()),
+                    ],
+                    vec![
+                        Value::iVal(3),
+                        Value::iVal(7),
+                        Value::sVal(b"RUNNING".to_vec()),
+                    ],
+                ],
+            )))),
+        );
+
+        let mut session = manager.get_session().await?;
+        server.push_execute(Reply::new(response(None)).delay(Duration::from_secs(1)));
+        assert!(matches!(
+            session
+                .query_with_timeout("YIELD 1;", Duration::from_millis(100))
+                .await,
+            Err(SingleConnSessionError::QueryTimeout(_, None))
+        ));
+        let statements = server.statements();
+        assert_eq!(
+            statements[statements.len() - 2..],
+            ["SHOW ALL QUERIES;", "KILL QUERY (session=1, plan=42);"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reauth() -> Result<(), Box<dyn std::error::Error>> {
+        let server = FakeGraphServer::start().await?;
+        let mut conf = conf(&server);
+        conf.set_reauth_policy(ReauthPolicy::RetryIdempotent);
+        let manager = SingleConnSessionManager::new(conf);
+
+        let mut session = manager.get_session().await?;
+        server.expire_session(1);
+        let output = session.query("YIELD 1;").await?;
+        assert_eq!(output.get_space_name(), Some("test".to_owned()));
+        assert_eq!(server.sessions(), vec![2]);
+        assert_eq!(
+            server.statements(),
+            ["Use test;", "YIELD 1;", "USE `test`;", "YIELD 1;"]
+        );
+        assert!(!session.is_close_required());
+
+        // A session that can't switch back to its space isn't reused.
+        server.expire_session(2);
+        server.on_statement(
+            "USE `test`;",
+            Reply::new(error_response(
+                ErrorCode::E_SPACE_NOT_FOUND,
+                "SpaceNotFound",
+            )),
+        );
+        assert!(session.query("YIELD 1;").await.is_err());
+        assert!(session.is_close_required());
+        Ok(())
+    }
+
+    fn count_requests(server: &FakeGraphServer, f: impl Fn(&GraphRequest) -> bool) -> usize {
+        server.requests().iter().filter(|v| f(v)).count()
+    }
+
+    #[tokio::test]
+    async fn test_connection_pool() -> Result<(), Box<dyn std::error::Error>> {
+        let server = FakeGraphServer::start().await?;
+        let connections = Arc::new(ConnectionPool::new(
+            conf(&server),
+            ConnectionPoolConf::default(),
+        ));
+        let manager = SessionPoolManager::new(connections.clone());
+
+        // The sessions share one connection.
+        let mut first = manager.get_session().await?;
+        let mut second = manager.get_session().await?;
+        let output = first.query("YIELD 1;").await?;
+        assert_eq!(output.get_space_name(), Some("test".to_owned()));
+        second.query("YIELD 2;").await?;
+        assert_eq!(server.sessions(), vec![1, 2]);
+        assert_eq!(server.statements()[..2], ["USE `test`;", "USE `test`;"]);
+        assert_eq!(connections.state()[0].1.connections, 1);
+
+        // A write isn't retried, and the broken connection is replaced without
+        // authenticating again.
+        server.push_execute(Reply::disconnect());
+        assert!(first
+            .query("INSERT VERTEX player(name) VALUES \"a\":(\"b\");")
+            .await
+            .is_err());
+        first.query("YIELD 3;").await?;
+        assert!(!first.is_close_required());
+
+        // A read is retried once on a new connection.
+        server.push_execute(Reply::disconnect());
+        second.query("YIELD 4;").await?;
+
+        assert_eq!(
+            count_requests(&server, |v| matches!(
+                v,
+                GraphRequest::VerifyClientVersion(_)
+            )),
+            3
+        );
+        assert_eq!(
+            count_requests(&server, |v| matches!(v, GraphRequest::Authenticate { .. })),
+            2
+        );
+        assert_eq!(connections.state()[0].1.connections, 1);
+        let executed = server
+            .requests()
+            .into_iter()
+            .filter_map(|v| match v {
+                GraphRequest::Execute {
+                    session_id, stmt, ..
+                } => Some((session_id, stmt)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            executed[4..],
+            [
+                (
+                    1,
+                    "INSERT VERTEX player(name) VALUES \"a\":(\"b\");".to_owned()
+                ),
+                (1, "YIELD 3;".to_owned()),
+                (2, "YIELD 4;".to_owned()),
+                (2, "YIELD 4;".to_owned()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_connection_pool_idle() -> Result<(), Box<dyn std::error::Error>> {
+        let server = FakeGraphServer::start().await?;
+        let mut pool_conf = ConnectionPoolConf::default();
+        pool_conf.set_min_idle(Some(1));
+        pool_conf.set_idle_timeout(Some(Duration::from_millis(200)));
+        let connections = ConnectionPool::new(conf(&server), pool_conf);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(connections.state()[0].1.idle_connections, 1);
+
+        let (first, second, third) =
+            tokio::join!(connections.get(), connections.get(), connections.get());
+        assert_eq!(connections.state()[0].1.connections, 3);
+        drop((first?, second?, third?));
+        assert_eq!(connections.state()[0].1.idle_connections, 3);
+
+        // The connections above `min_idle` are closed after `idle_timeout`.
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(connections.state()[0].1.connections, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_connection_pool_failover() -> Result<(), Box<dyn std::error::Error>> {
+        let down = FakeGraphServer::start().await?.addr();
+        // Let the aborted server close its listener.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let server = FakeGraphServer::start().await?;
+        let mut conf = conf(&server);
+        conf.host_addrs = vec![down.clone(), server.addr()];
+        let connections = Arc::new(ConnectionPool::new(
+            conf.clone(),
+            ConnectionPoolConf::default(),
+        ));
+        let manager = SessionPoolManager::new(connections.clone());
+
+        // The unreachable host is skipped without waiting for `connection_timeout`.
+        let mut session =
+            tokio::time::timeout(Duration::from_secs(5), manager.get_session()).await??;
+        session.query("YIELD 1;").await?;
+        assert_eq!(server.sessions(), vec![1]);
+
+        conf.host_addrs = vec![down.clone()];
+        let connections = ConnectionPool::new(conf, ConnectionPoolConf::default());
+        match tokio::time::timeout(Duration::from_secs(5), connections.get()).await? {
+            Err(SingleConnSessionError::NoAvailableHost(failures)) => {
+                assert_eq!(failures.len(), 1);
+                assert_eq!(failures[0].0, down);
+                assert!(matches!(
+                    failures[0].1,
+                    SingleConnSessionError::TransportBuildError(_)
+                ));
+            }
+            res => panic!("unexpected {:?}", res.map(|_| ())),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_version_rejected() -> Result<(), Box<dyn std::error::Error>> {
+        let server = FakeGraphServer::start().await?;
+        let manager = SingleConnSessionManager::new(conf(&server));
+        server.push_verify_client_version(Reply::new(VerifyClientVersionResp {
+            error_code: ErrorCode::E_CLIENT_SERVER_INCOMPATIBLE,
+            error_msg: Some(b"Graph client version(3.0.0) is not accepted".to_vec()),
+            ..Default::default()
+        }));
+
+        match manager.get_session().await {
+            Err(SingleConnSessionError::ClientVersionRejected(version, msg)) => {
+                assert_eq!(version, "3.0.0");
+                assert_eq!(msg, "Graph client version(3.0.0) is not accepted");
+            }
+            res => panic!("unexpected {:?}", res.map(|_| ())),
+        }
+        // The host is reachable, so it isn't quarantined.
+        assert_eq!(manager.config.quarantined_until(&server.addr()), None);
+        assert!(server.sessions().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_session() -> Result<(), Box<dyn std::error::Error>> {
+        use bb8::ManageConnection as _;
+
+        let server = FakeGraphServer::start().await?;
+        let mut conf = conf(&server);
+        conf.set_health_check(true);
+        conf.set_ping_timeout(100);
+        conf.set_ping_idle_threshold(60_000);
+        let manager = SingleConnSessionManager::new(conf.clone());
+
+        // A recently used session isn't pinged.
+        let mut session = manager.get_session().await?;
+        manager.is_valid(&mut session).await?;
+        assert_eq!(server.statements(), ["Use test;"]);
+
+        conf.ping_idle_threshold = None;
+        let manager = SingleConnSessionManager::new(conf);
+        let mut session = manager.get_session().await?;
+        manager.is_valid(&mut session).await?;
+        assert_eq!(server.statements()[1..], ["Use test;", "YIELD 1;"]);
+
+        server.push_execute(Reply::new(ExecutionResponse {
+            space_name: Some(b"other".to_vec()),
+            ..response(None)
+        }));
+        match manager.is_valid(&mut session).await {
+            Err(SingleConnSessionError::SpaceMismatch(expected, current)) => {
+                assert_eq!(expected, "test");
+                assert_eq!(current.as_deref(), Some("other"));
+            }
+            res => panic!("unexpected {res:?}"),
+        }
+
+        server.push_execute(Reply::new(response(None)).delay(Duration::from_secs(1)));
+        assert!(matches!(
+            manager.is_valid(&mut session).await,
+            Err(SingleConnSessionError::PingTimeout(_))
+        ));
+        assert!(session.is_close_required());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_params() -> Result<(), Box<dyn std::error::Error>> {
+        let server = FakeGraphServer::start().await?;
+        server.on_statement(
+            "YIELD $n AS n;",
+            Reply::new(response(Some(data_set(&["n"], vec![vec![Value::iVal(1)]])))),
+        );
+        let manager = SingleConnSessionManager::new(conf(&server));
+        let mut session = manager.get_session().await?;
+
+        let params = HashMap::from([(b"n".to_vec(), Value::iVal(1))]);
+        let output = session.query_with_params("YIELD $n AS n;", &params).await?;
+        assert_eq!(output.scan::<N>()?[0].n, 1);
+        session
+            .execute_with_params("INSERT VERTEX player(age) VALUES \"a\":($n);", &params)
+            .await?;
+
+        let params = Some(BTreeMap::from([(b"n".to_vec(), Value::iVal(1))]));
+        assert_eq!(
+            server.requests()[3..],
+            [
+                GraphRequest::Execute {
+                    session_id: 1,
+                    stmt: "YIELD $n AS n;".to_owned(),
+                    params: params.clone(),
+                },
+                GraphRequest::Execute {
+                    session_id: 1,
+                    stmt: "INSERT VERTEX player(age) VALUES \"a\":($n);".to_owned(),
+                    params,
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failover() -> Result<(), Box<dyn std::error::Error>> {
+        let down = FakeGraphServer::start().await?.addr();
+        let other_down = FakeGraphServer::start().await?.addr();
+        // Let the aborted servers close their listeners.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let server = FakeGraphServer::start().await?;
+
+        let mut conf = conf(&server);
+        conf.host_addrs = vec![down.clone(), server.addr()];
+        let manager = SingleConnSessionManager::new(conf.clone());
+        let mut session = manager.get_session().await?;
+        session.execute("YIELD 1;").await?;
+        assert_eq!(server.sessions(), vec![1]);
+        assert!(manager.config.quarantined_until(&down).is_some());
+        assert_eq!(manager.config.quarantined_until(&server.addr()), None);
+
+        conf.host_addrs = vec![down.clone(), other_down.clone()];
+        let manager = SingleConnSessionManager::new(conf);
+        match manager.get_session().await {
+            Err(SingleConnSessionError::NoAvailableHost(failures)) => {
+                let mut hosts = failures.iter().map(|(v, _)| v.clone()).collect::<Vec<_>>();
+                hosts.sort_by_key(|v| v.port());
+                let mut expected = vec![down, other_down];
+                expected.sort_by_key(|v| v.port());
+                assert_eq!(hosts, expected);
+                assert!(failures
+                    .iter()
+                    .all(|(_, err)| matches!(err, SingleConnSessionError::TransportBuildError(_))));
+            }
+            res => panic!("unexpected {:?}", res.map(|_| ())),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_script() -> Result<(), Box<dyn std::error::Error>> {
+        let server = FakeGraphServer::start().await?;
+        server.on_statement(
+            "YIELD 1 AS n",
+            Reply::new(response(Some(data_set(&["n"], vec![vec![Value::iVal(1)]])))),
+        );
+        server.on_statement(
+            "MATCH (v) RETURN v",
+            Reply::new(error_response(
+                ErrorCode::E_SEMANTIC_ERROR,
+                "semantic error",
+            )),
+        );
+        let manager = SingleConnSessionManager::new(conf(&server));
+        let mut session = manager.get_session().await?;
+
+        let outputs = session.execute_script("YIELD 1 AS n; YIELD 2;").await?;
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[0].scan::<N>()?[0].n, 1);
+
+        let script = r#"
+# Switch to another space
+USE other;
+YIELD 1 AS n;
+/* fails */
+MATCH (v) RETURN v;
+YIELD 2;
+"#;
+        let err = session.execute_script(script).await.unwrap_err();
+        assert_eq!(err.index, 2);
+        assert_eq!(err.line, 6);
+        assert_eq!(err.statement, "MATCH (v) RETURN v");
+        assert_eq!(err.space.as_deref(), Some("other"));
+        assert!(matches!(
+            err.error,
+            SingleConnSessionError::GraphQueryError(GraphQueryError::ResponseError(
+                ErrorCode::E_SEMANTIC_ERROR,
+                _
+            ))
+        ));
+        assert_eq!(err.outputs.len(), 2);
+        assert_eq!(err.outputs[1].get_space_name(), Some("other".to_owned()));
+        assert_eq!(err.outputs[1].scan::<N>()?[0].n, 1);
+
+        assert_eq!(
+            server.statements()[1..],
+            [
+                "YIELD 1 AS n",
+                "YIELD 2",
+                "USE other",
+                "YIELD 1 AS n",
+                "MATCH (v) RETURN v",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,497 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use fbthrift::ProtocolReader as _;
+use nebula_fbthrift_meta_v3::{
+    services::meta_service::{
+        GetPartsAllocExn, GetSpaceExn, ListEdgesExn, ListHostsExn, ListPartsExn, ListSpacesExn,
+        ListTagsExn,
+    },
+    types::{
+        GetPartsAllocReq, GetPartsAllocResp, GetSpaceReq, GetSpaceResp, ListEdgesReq,
+        ListEdgesResp, ListHostType, ListHostsReq, ListHostsResp, ListPartsReq, ListPartsResp,
+        ListSpacesResp, ListTagsReq, ListTagsResp,
+    },
+    EdgeItem, HostItem, HostRole, HostStatus, IdName, PartItem, SpaceDesc, SpaceItem, TagItem, ID,
+};
+
+use super::server::{
+    read_args, read_req, serve, BoxError, Deserializer, Outcome, Reply, ServerHandle, Service,
+};
+use crate::common::{ErrorCode, HostAddr};
+use crate::HostAddress;
+
+#[derive(Default)]
+struct Space {
+    name: Vec<u8>,
+    tags: Vec<TagItem>,
+    edges: Vec<EdgeItem>,
+    /// The peers of every part, the leader first
+    parts: BTreeMap<i32, Vec<HostAddr>>,
+}
+
+#[derive(Default)]
+struct MetaCatalog {
+    /// The address of the fake, reported as the leader in the responses
+    leader: HostAddr,
+    spaces: BTreeMap<i32, Space>,
+    errors: HashMap<String, VecDeque<Reply<ErrorCode>>>,
+    requests: Vec<String>,
+}
+
+impl MetaCatalog {
+    fn space(&mut self, space_id: i32) -> &mut Space {
+        self.spaces
+            .get_mut(&space_id)
+            .unwrap_or_else(|| panic!("space {space_id} isn't added"))
+    }
+
+    fn storage_hosts(&self) -> Vec<HostItem> {
+        let addrs = self
+            .spaces
+            .values()
+            .flat_map(|space| space.parts.values().flatten())
+            .collect::<BTreeSet<_>>();
+        addrs
+            .into_iter()
+            .map(|addr| {
+                let mut leader_parts = BTreeMap::new();
+                let mut all_parts = BTreeMap::new();
+                for space in self.spaces.values() {
+                    for (part_id, peers) in &space.parts {
+                        if peers.first() == Some(addr) {
+                            leader_parts
+                                .entry(space.name.clone())
+                                .or_insert_with(Vec::new)
+                                .push(*part_id);
+                        }
+                        if peers.contains(addr) {
+                            all_parts
+                                .entry(space.name.clone())
+                                .or_insert_with(Vec::new)
+                                .push(*part_id);
+                        }
+                    }
+                }
+                HostItem {
+                    hostAddr: addr.clone(),
+                    status: HostStatus::ONLINE,
+                    leader_parts,
+                    all_parts,
+                    role: HostRole::STORAGE,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    /// Answer `name` with the injected error if any, with `ok` otherwise.
+    fn answer<R, E, F>(&mut self, name: &str, seqid: u32, wrap: fn(R) -> E, ok: F) -> Outcome
+    where
+        R: MetaResponse,
+        E: fbthrift::Serialize<super::server::Serializer>,
+        F: FnOnce(&Self) -> R,
+    {
+        self.requests.push(name.to_owned());
+        let leader = self.leader.clone();
+        let reply = match self.errors.get_mut(name).and_then(VecDeque::pop_front) {
+            Some(reply) => reply.map(|code| R::error(code, leader)),
+            None => Reply::new(ok(self)),
+        };
+        reply.into_outcome(name, seqid, wrap)
+    }
+}
+
+/// The responses of metad, which all carry a code and the leader.
+trait MetaResponse {
+    fn error(code: ErrorCode, leader: HostAddr) -> Self;
+}
+
+macro_rules! impl_meta_response {
+    ($($resp:ty),*) => {
+        $(
+            impl MetaResponse for $resp {
+                fn error(code: ErrorCode, leader: HostAddr) -> Self {
+                    Self {
+                        code,
+                        leader,
+                        ..Default::default()
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_meta_response!(
+    ListSpacesResp,
+    GetSpaceResp,
+    ListHostsResp,
+    ListPartsResp,
+    ListTagsResp,
+    ListEdgesResp,
+    GetPartsAllocResp
+);
+
+struct MetaService {
+    catalog: Mutex<MetaCatalog>,
+}
+
+impl Service for MetaService {
+    fn call(&self, name: &str, seqid: u32, des: &mut Deserializer) -> Result<Outcome, BoxError> {
+        let mut catalog = self.catalog.lock().unwrap();
+        let outcome = match name {
+            "listSpaces" => {
+                read_args(des, |des, _, field_type| Ok(des.skip(field_type)?))?;
+                catalog.answer(name, seqid, ListSpacesExn::Success, |catalog| {
+                    ListSpacesResp {
+                        code: ErrorCode::SUCCEEDED,
+                        leader: catalog.leader.clone(),
+                        spaces: catalog
+                            .spaces
+                            .iter()
+                            .map(|(space_id, space)| IdName {
+                                id: ID::space_id(*space_id),
+                                name: space.name.clone(),
+                                ..Default::default()
+                            })
+                            .collect(),
+                        ..Default::default()
+                    }
+                })
+            }
+            "getSpace" => {
+                let req: GetSpaceReq = read_req(des)?;
+                catalog.answer(name, seqid, GetSpaceExn::Success, |catalog| {
+                    match catalog
+                        .spaces
+                        .iter()
+                        .find(|(_, space)| space.name == req.space_name)
+                    {
+                        Some((space_id, space)) => GetSpaceResp {
+                            code: ErrorCode::SUCCEEDED,
+                            leader: catalog.leader.clone(),
+                            item: SpaceItem {
+                                space_id: *space_id,
+                                properties: SpaceDesc {
+                                    space_name: space.name.clone(),
+                                    partition_num: space.parts.len() as i32,
+                                    replica_factor: space
+                                        .parts
+                                        .values()
+                                        .map(Vec::len)
+                                        .max()
+                                        .unwrap_or(0)
+                                        as i32,
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        None => GetSpaceResp::error(
+                            ErrorCode::E_SPACE_NOT_FOUND,
+                            catalog.leader.clone(),
+                        ),
+                    }
+                })
+            }
+            "listHosts" => {
+                let req: ListHostsReq = read_req(des)?;
+                catalog.answer(name, seqid, ListHostsExn::Success, |catalog| {
+                    ListHostsResp {
+                        code: ErrorCode::SUCCEEDED,
+                        leader: catalog.leader.clone(),
+                        hosts: match req.r#type {
+                            ListHostType::STORAGE | ListHostType::ALLOC => catalog.storage_hosts(),
+                            _ => vec![],
+                        },
+                        ..Default::default()
+                    }
+                })
+            }
+            "listParts" => {
+                let req: ListPartsReq = read_req(des)?;
+                catalog.answer(name, seqid, ListPartsExn::Success, |catalog| {
+                    let parts = match catalog.spaces.get(&req.space_id) {
+                        Some(space) => &space.parts,
+                        None => {
+                            return ListPartsResp::error(
+                                ErrorCode::E_SPACE_NOT_FOUND,
+                                catalog.leader.clone(),
+                            )
+                        }
+                    };
+                    ListPartsResp {
+                        code: ErrorCode::SUCCEEDED,
+                        leader: catalog.leader.clone(),
+                        parts: parts
+                            .iter()
+                            .filter(|(part_id, _)| {
+                                req.part_ids.is_empty() || req.part_ids.contains(part_id)
+                            })
+                            .map(|(part_id, peers)| PartItem {
+                                part_id: *part_id,
+                                leader: peers.first().cloned(),
+                                peers: peers.clone(),
+                                ..Default::default()
+                            })
+                            .collect(),
+                        ..Default::default()
+                    }
+                })
+            }
+            "listTags" => {
+                let req: ListTagsReq = read_req(des)?;
+                catalog.answer(name, seqid, ListTagsExn::Success, |catalog| {
+                    match catalog.spaces.get(&req.space_id) {
+                        Some(space) => ListTagsResp {
+                            code: ErrorCode::SUCCEEDED,
+                            leader: catalog.leader.clone(),
+                            tags: space.tags.clone(),
+                            ..Default::default()
+                        },
+                        None => ListTagsResp::error(
+                            ErrorCode::E_SPACE_NOT_FOUND,
+                            catalog.leader.clone(),
+                        ),
+                    }
+                })
+            }
+            "listEdges" => {
+                let req: ListEdgesReq = read_req(des)?;
+                catalog.answer(name, seqid, ListEdgesExn::Success, |catalog| match catalog
+                    .spaces
+                    .get(&req.space_id)
+                {
+                    Some(space) => ListEdgesResp {
+                        code: ErrorCode::SUCCEEDED,
+                        leader: catalog.leader.clone(),
+                        edges: space.edges.clone(),
+                        ..Default::default()
+                    },
+                    None => {
+                        ListEdgesResp::error(ErrorCode::E_SPACE_NOT_FOUND, catalog.leader.clone())
+                    }
+                })
+            }
+            "getPartsAlloc" => {
+                let req: GetPartsAllocReq = read_req(des)?;
+                catalog.answer(
+                    name,
+                    seqid,
+                    GetPartsAllocExn::Success,
+                    |catalog| match catalog.spaces.get(&req.space_id) {
+                        Some(space) => GetPartsAllocResp {
+                            code: ErrorCode::SUCCEEDED,
+                            leader: catalog.leader.clone(),
+                            parts: space.parts.clone(),
+                            ..Default::default()
+                        },
+                        None => GetPartsAllocResp::error(
+                            ErrorCode::E_SPACE_NOT_FOUND,
+                            catalog.leader.clone(),
+                        ),
+                    },
+                )
+            }
+            _ => return Err(format!("MetaService doesn't implement {name}").into()),
+        };
+        Ok(outcome)
+    }
+}
+
+//
+//
+//
+/// An in-process metad, serving a catalog of spaces, tags, edges and part
+/// allocations.
+///
+/// The first peer of a part is its leader, and every host of the allocations is
+/// listed as an online storaged. Errors, like `E_LEADER_CHANGED`, are injected
+/// per method with `push_error`.
+/// ## Example
+/// ```ignore
+/// use rust_nebula::testing::{FakeMetaServer, FakeStorageServer};
+///
+/// let storage = FakeStorageServer::start().await?;
+/// let meta = FakeMetaServer::start().await?;
+/// meta.add_space(1, "test", [(1, vec![storage.addr()])].into());
+/// let mclient = MetaClient::new(&[meta.addr()]).await?;
+/// ```
+pub struct FakeMetaServer {
+    handle: ServerHandle,
+    service: Arc<MetaService>,
+}
+
+impl FakeMetaServer {
+    /// Listen on a free port of localhost.
+    pub async fn start() -> io::Result<Self> {
+        let service = Arc::new(MetaService {
+            catalog: Mutex::new(MetaCatalog::default()),
+        });
+        let handle = serve(service.clone()).await?;
+        service.catalog.lock().unwrap().leader = HostAddr {
+            host: handle.addr().ip().to_string(),
+            port: handle.addr().port() as i32,
+            ..Default::default()
+        };
+        Ok(Self { handle, service })
+    }
+
+    pub fn addr(&self) -> HostAddress {
+        let addr = self.handle.addr();
+        HostAddress::new(&addr.ip().to_string(), addr.port())
+    }
+
+    fn catalog(&self) -> std::sync::MutexGuard<'_, MetaCatalog> {
+        self.service.catalog.lock().unwrap()
+    }
+
+    /// Add or replace a space, `parts` maps every part to its peers, the leader
+    /// first.
+    pub fn add_space(&self, space_id: i32, name: &str, parts: BTreeMap<i32, Vec<HostAddr>>) {
+        self.catalog().spaces.insert(
+            space_id,
+            Space {
+                name: name.as_bytes().to_vec(),
+                parts,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// ## Panics
+    /// If the space isn't added.
+    pub fn add_tag(&self, space_id: i32, tag: TagItem) {
+        self.catalog().space(space_id).tags.push(tag);
+    }
+
+    /// ## Panics
+    /// If the space isn't added.
+    pub fn add_edge(&self, space_id: i32, edge: EdgeItem) {
+        self.catalog().space(space_id).edges.push(edge);
+    }
+
+    /// Move the leadership of a part to `leader`, like after a leader election.
+    /// ## Panics
+    /// If the space isn't added.
+    pub fn set_part_leader(&self, space_id: i32, part_id: i32, leader: HostAddr) {
+        let mut catalog = self.catalog();
+        let peers = catalog.space(space_id).parts.entry(part_id).or_default();
+        peers.retain(|v| v != &leader);
+        peers.insert(0, leader);
+    }
+
+    /// Answer the next call of `method`, e.g. `listSpaces`, with an error code
+    /// or a disconnect.
+    pub fn push_error(&self, method: &str, reply: Reply<ErrorCode>) {
+        self.catalog()
+            .errors
+            .entry(method.to_owned())
+            .or_default()
+            .push_back(reply);
+    }
+
+    /// The methods called so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.catalog().requests.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{MetaClient, MetaClientError};
+
+    fn host_addr(host: &str, port: i32) -> HostAddr {
+        HostAddr {
+            host: host.to_owned(),
+            port,
+            ..Default::default()
+        }
+    }
+
+    async fn fake_meta() -> io::Result<FakeMetaServer> {
+        let meta = FakeMetaServer::start().await?;
+        let (h1, h2) = (host_addr("h1", 9779), host_addr("h2", 9779));
+        meta.add_space(
+            1,
+            "test",
+            [(1, vec![h1.clone(), h2.clone()]), (2, vec![h2, h1])].into(),
+        );
+        meta.add_tag(
+            1,
+            TagItem {
+                tag_id: 2,
+                tag_name: b"player".to_vec(),
+                ..Default::default()
+            },
+        );
+        meta.add_edge(
+            1,
+            EdgeItem {
+                edge_type: 3,
+                edge_name: b"follow".to_vec(),
+                ..Default::default()
+            },
+        );
+        Ok(meta)
+    }
+
+    #[tokio::test]
+    async fn test_load_all() -> Result<(), Box<dyn std::error::Error>> {
+        let meta = fake_meta().await?;
+        let mut client = MetaClient::new(&[meta.addr()]).await?;
+
+        assert_eq!(client.get_space_id("test").await?, 1);
+        assert_eq!(client.get_tag_id("test", "player").await?, 2);
+        assert_eq!(client.get_edge_type("test", "follow").await?, 3);
+        assert_eq!(client.get_part_leader("test", 2).await?.host, "h2");
+        assert_eq!(client.get_all_storage_addrs().await?.len(), 2);
+        assert!(matches!(
+            client.get_space_id("other").await,
+            Err(MetaClientError::SpaceNotFoundError(_))
+        ));
+        assert_eq!(
+            meta.requests()[..5],
+            [
+                "listSpaces",
+                "getPartsAlloc",
+                "listTags",
+                "listEdges",
+                "listHosts"
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_leader_change() -> Result<(), Box<dyn std::error::Error>> {
+        let meta = fake_meta().await?;
+        meta.set_part_leader(1, 1, host_addr("h3", 9779));
+
+        let mut client = MetaClient::new(&[meta.addr()]).await?;
+        meta.push_error("listSpaces", Reply::new(ErrorCode::E_LEADER_CHANGED));
+        match client.get_space_id("test").await {
+            Err(MetaClientError::ResponseError(code, leader)) => {
+                assert_eq!(code, ErrorCode::E_LEADER_CHANGED);
+                assert_eq!(leader.port, meta.addr().port() as i32);
+            }
+            res => panic!("unexpected {res:?}"),
+        }
+        assert_eq!(client.get_space_id("test").await?, 1);
+        assert_eq!(client.get_part_leader("test", 1).await?.host, "h3");
+        assert_eq!(client.get_all_storage_addrs().await?.len(), 3);
+
+        meta.push_error("listSpaces", Reply::disconnect());
+        let mut client = MetaClient::new(&[meta.addr()]).await?;
+        assert!(matches!(
+            client.get_space_id("test").await,
+            Err(MetaClientError::LoadError(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+//! In-process fake servers for tests, behind the `testing` feature.
+//!
+//! The servers listen on a free port of localhost and speak the thrift
+//! protocol of Nebula, so the clients of this crate are tested end to end
+//! without a cluster. They are shut down when dropped.
+
+mod server;
+pub use server::Reply;
+
+pub mod graph;
+pub use graph::{FakeGraphServer, GraphRequest};
+
+pub mod meta;
+pub use meta::FakeMetaServer;
+
+pub mod storage;
+pub use storage::{FakeStorageServer, ScanRequest, ScanTarget};
+
+pub mod transport;
+pub use transport::{Exchange, Recording, RecordingTransport, ReplayError, ReplayTransport};
@@ -0,0 +1,222 @@
+use std::io::{self, Cursor};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use fbthrift::{
+    binary_protocol::{BinaryProtocolDeserializer, BinaryProtocolSerializer},
+    ApplicationException, ApplicationExceptionErrorCode, Deserialize, MessageType, ProtocolReader,
+    ProtocolWriter, Serialize, TType,
+};
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::sleep;
+
+pub(crate) type Deserializer<'a> = BinaryProtocolDeserializer<Cursor<&'a [u8]>>;
+pub(crate) type Serializer = BinaryProtocolSerializer<BytesMut>;
+pub(crate) type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// How a fake server answers a call.
+#[derive(Debug, Clone)]
+pub struct Reply<T> {
+    /// `None` closes the connection instead of responding
+    response: Option<T>,
+    delay: Duration,
+}
+
+impl<T> Reply<T> {
+    pub fn new(response: T) -> Self {
+        Self {
+            response: Some(response),
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Close the connection without responding.
+    pub fn disconnect() -> Self {
+        Self {
+            response: None,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Wait before responding or disconnecting.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub(crate) fn response_mut(&mut self) -> Option<&mut T> {
+        self.response.as_mut()
+    }
+
+    pub(crate) fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Reply<U> {
+        Reply {
+            response: self.response.map(f),
+            delay: self.delay,
+        }
+    }
+
+    /// Serialize the response as the reply of `name`.
+    pub(crate) fn into_outcome<E, F>(self, name: &str, seqid: u32, wrap: F) -> Outcome
+    where
+        E: Serialize<Serializer>,
+        F: FnOnce(T) -> E,
+    {
+        match self.response {
+            Some(response) => Outcome::Reply(reply(name, seqid, &wrap(response)), self.delay),
+            None => Outcome::Disconnect(self.delay),
+        }
+    }
+}
+
+pub(crate) enum Outcome {
+    Reply(Bytes, Duration),
+    /// The client doesn't wait for a response, e.g. `signout`
+    NoReply,
+    Disconnect(Duration),
+}
+
+/// A thrift service over the binary protocol without framing, like the
+/// servers of Nebula speak it.
+pub(crate) trait Service: Send + Sync + 'static {
+    /// Answer the call `name`, `des` is positioned at its arguments.
+    fn call(&self, name: &str, seqid: u32, des: &mut Deserializer) -> Result<Outcome, BoxError>;
+}
+
+/// Closes the listener and every connection when dropped.
+pub(crate) struct ServerHandle {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl ServerHandle {
+    pub(crate) fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Serve `service` on a free port of localhost.
+pub(crate) async fn serve<S: Service>(service: Arc<S>) -> io::Result<ServerHandle> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let task = tokio::spawn(async move {
+        let mut connections = JoinSet::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            connections.spawn(handle_connection(stream, service.clone()));
+        }
+    });
+    Ok(ServerHandle { addr, task })
+}
+
+async fn handle_connection<S: Service>(mut stream: TcpStream, service: Arc<S>) {
+    let mut buf = BytesMut::with_capacity(4096);
+    loop {
+        while let Some((len, outcome)) = next_call(&*service, &buf) {
+            let _ = buf.split_to(len);
+            match outcome {
+                Outcome::Reply(bytes, delay) => {
+                    sleep(delay).await;
+                    if stream.write_all(&bytes).await.is_err() {
+                        return;
+                    }
+                }
+                Outcome::NoReply => {}
+                Outcome::Disconnect(delay) => {
+                    sleep(delay).await;
+                    return;
+                }
+            }
+        }
+        match stream.read_buf(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+    }
+}
+
+/// Answer the first message of `buf`, if it's complete. Returns its length.
+fn next_call<S: Service + ?Sized>(service: &S, buf: &[u8]) -> Option<(usize, Outcome)> {
+    let mut des = BinaryProtocolDeserializer::new(Cursor::new(buf));
+    let (name, _, seqid) = des
+        .read_message_begin(|v| String::from_utf8_lossy(v).to_string())
+        .ok()?;
+    des.skip(TType::Struct).ok()?;
+    des.read_message_end().ok()?;
+    let len = des.into_inner().position() as usize;
+
+    let mut des = BinaryProtocolDeserializer::new(Cursor::new(&buf[..len]));
+    des.read_message_begin(|_| ()).ok()?;
+    let outcome = service
+        .call(&name, seqid, &mut des)
+        .unwrap_or_else(|err| Outcome::Reply(exception(&name, seqid, &err), Duration::ZERO));
+    Some((len, outcome))
+}
+
+/// Read the fields of the arguments struct, `field` is called with the id and
+/// the type of every field.
+pub(crate) fn read_args<F>(des: &mut Deserializer, mut field: F) -> Result<(), BoxError>
+where
+    F: FnMut(&mut Deserializer, i16, TType) -> Result<(), BoxError>,
+{
+    des.read_struct_begin(|_| ())?;
+    loop {
+        let (_, field_type, id) = des.read_field_begin(|_| (), &[])?;
+        if field_type == TType::Stop {
+            break;
+        }
+        field(des, id, field_type)?;
+        des.read_field_end()?;
+    }
+    des.read_struct_end()?;
+    Ok(())
+}
+
+pub(crate) fn read<T>(des: &mut Deserializer) -> Result<T, BoxError>
+where
+    for<'a> T: Deserialize<Deserializer<'a>>,
+{
+    Ok(T::read(des)?)
+}
+
+/// Read the request, the only argument of the methods of metad and storaged.
+pub(crate) fn read_req<T>(des: &mut Deserializer) -> Result<T, BoxError>
+where
+    T: Default,
+    for<'a> T: Deserialize<Deserializer<'a>>,
+{
+    let mut req = T::default();
+    read_args(des, |des, id, field_type| {
+        match id {
+            1 => req = read(des)?,
+            _ => des.skip(field_type)?,
+        }
+        Ok(())
+    })?;
+    Ok(req)
+}
+
+fn reply<T: Serialize<Serializer>>(name: &str, seqid: u32, value: &T) -> Bytes {
+    let mut ser = Serializer::with_buffer(BytesMut::with_capacity(1024));
+    ser.write_message_begin(name, MessageType::Reply, seqid);
+    value.write(&mut ser);
+    ser.write_message_end();
+    ser.finish()
+}
+
+fn exception(name: &str, seqid: u32, err: &BoxError) -> Bytes {
+    let mut ser = Serializer::with_buffer(BytesMut::with_capacity(1024));
+    ser.write_message_begin(name, MessageType::Exception, seqid);
+    ApplicationException::new(ApplicationExceptionErrorCode::Unknown, err.to_string())
+        .write(&mut ser);
+    ser.write_message_end();
+    ser.finish()
+}
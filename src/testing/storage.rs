@@ -0,0 +1,381 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use nebula_fbthrift_storage_v3::{
+    services::graph_storage_service::{ScanEdgeExn, ScanVertexExn},
+    types::{ScanEdgeRequest, ScanResponse, ScanVertexRequest},
+    PartitionResult, ScanCursor,
+};
+
+use super::server::{
+    read_req, serve, BoxError, Deserializer, Outcome, Reply, ServerHandle, Service,
+};
+use crate::common::{DataSet, ErrorCode, HostAddr};
+
+/// What a scan reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScanTarget {
+    /// The vertices of a tag
+    Vertex(i32),
+    /// The edges of an edge type
+    Edge(i32),
+}
+
+/// A part of a scan received by `FakeStorageServer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanRequest {
+    pub target: ScanTarget,
+    pub space_id: i32,
+    pub part_id: i32,
+    pub cursor: Option<Vec<u8>>,
+    pub limit: i64,
+}
+
+#[derive(Default)]
+struct StorageData {
+    data: HashMap<(i32, i32, ScanTarget), DataSet>,
+    page_size: Option<usize>,
+    /// Failures of the next scans of a part
+    part_errors: HashMap<i32, VecDeque<PartitionResult>>,
+    requests: Vec<ScanRequest>,
+}
+
+impl StorageData {
+    fn scan(
+        &mut self,
+        target: ScanTarget,
+        space_id: i32,
+        parts: BTreeMap<i32, ScanCursor>,
+        limit: i64,
+        columns: Vec<Vec<u8>>,
+    ) -> ScanResponse {
+        let mut resp = ScanResponse::default();
+        let mut props: Option<DataSet> = None;
+        for (part_id, cursor) in parts {
+            self.requests.push(ScanRequest {
+                target,
+                space_id,
+                part_id,
+                cursor: cursor.next_cursor.clone(),
+                limit,
+            });
+            if let Some(failure) = self
+                .part_errors
+                .get_mut(&part_id)
+                .and_then(VecDeque::pop_front)
+            {
+                resp.result.failed_parts.push(failure);
+                continue;
+            }
+
+            let offset = match cursor.next_cursor.as_deref().map(parse_cursor) {
+                None => 0,
+                Some(Some(offset)) => offset,
+                Some(None) => {
+                    resp.result.failed_parts.push(PartitionResult {
+                        code: ErrorCode::E_INVALID_PARM,
+                        part_id,
+                        ..Default::default()
+                    });
+                    continue;
+                }
+            };
+            let page_size = self
+                .page_size
+                .unwrap_or(usize::MAX)
+                .min(limit.max(0) as usize);
+            let mut next_cursor = None;
+            if let Some(data) = self.data.get(&(space_id, part_id, target)) {
+                let props = props.get_or_insert_with(|| DataSet {
+                    column_names: data.column_names.clone(),
+                    ..Default::default()
+                });
+                let end = offset.saturating_add(page_size).min(data.rows.len());
+                if offset < end {
+                    props.rows.extend_from_slice(&data.rows[offset..end]);
+                }
+                if end < data.rows.len() {
+                    next_cursor = Some(end.to_string().into_bytes());
+                }
+            }
+            resp.cursors.insert(
+                part_id,
+                ScanCursor {
+                    next_cursor,
+                    ..Default::default()
+                },
+            );
+        }
+        resp.props = Some(props.unwrap_or(DataSet {
+            column_names: columns,
+            ..Default::default()
+        }));
+        resp
+    }
+}
+
+fn parse_cursor(cursor: &[u8]) -> Option<usize> {
+    std::str::from_utf8(cursor).ok()?.parse().ok()
+}
+
+struct StorageService {
+    data: Mutex<StorageData>,
+}
+
+impl Service for StorageService {
+    fn call(&self, name: &str, seqid: u32, des: &mut Deserializer) -> Result<Outcome, BoxError> {
+        let mut data = self.data.lock().unwrap();
+        match name {
+            "scanVertex" => {
+                let req: ScanVertexRequest = read_req(des)?;
+                let prop = req.return_columns.into_iter().next().unwrap_or_default();
+                let resp = data.scan(
+                    ScanTarget::Vertex(prop.tag),
+                    req.space_id,
+                    req.parts,
+                    req.limit,
+                    prop.props,
+                );
+                Ok(Reply::new(resp).into_outcome(name, seqid, ScanVertexExn::Success))
+            }
+            "scanEdge" => {
+                let req: ScanEdgeRequest = read_req(des)?;
+                let prop = req.return_columns.into_iter().next().unwrap_or_default();
+                let resp = data.scan(
+                    ScanTarget::Edge(prop.r#type),
+                    req.space_id,
+                    req.parts,
+                    req.limit,
+                    prop.props,
+                );
+                Ok(Reply::new(resp).into_outcome(name, seqid, ScanEdgeExn::Success))
+            }
+            _ => Err(format!("GraphStorageService doesn't implement {name}").into()),
+        }
+    }
+}
+
+//
+//
+//
+/// An in-process storaged, serving scans of vertices and edges.
+///
+/// Every part answers its scans page by page, a page ends with a `next_cursor`
+/// while rows are left. Failures of a part, like `E_LEADER_CHANGED`, are
+/// injected with `push_part_error` and reported in `failed_parts`. The fake
+/// doesn't project the requested properties, it returns the columns the rows
+/// were added with.
+pub struct FakeStorageServer {
+    handle: ServerHandle,
+    service: Arc<StorageService>,
+}
+
+impl FakeStorageServer {
+    /// Listen on a free port of localhost.
+    pub async fn start() -> io::Result<Self> {
+        let service = Arc::new(StorageService {
+            data: Mutex::new(StorageData::default()),
+        });
+        let handle = serve(service.clone()).await?;
+        Ok(Self { handle, service })
+    }
+
+    /// The address to allocate parts on, see `FakeMetaServer::add_space`.
+    pub fn addr(&self) -> HostAddr {
+        HostAddr {
+            host: self.handle.addr().ip().to_string(),
+            port: self.handle.addr().port() as i32,
+            ..Default::default()
+        }
+    }
+
+    fn data(&self) -> std::sync::MutexGuard<'_, StorageData> {
+        self.service.data.lock().unwrap()
+    }
+
+    /// Add the rows of `data_set` to the vertices of a tag in a part.
+    pub fn add_vertices(&self, space_id: i32, part_id: i32, tag_id: i32, data_set: DataSet) {
+        self.add(space_id, part_id, ScanTarget::Vertex(tag_id), data_set);
+    }
+
+    /// Add the rows of `data_set` to the edges of an edge type in a part.
+    pub fn add_edges(&self, space_id: i32, part_id: i32, edge_type: i32, data_set: DataSet) {
+        self.add(space_id, part_id, ScanTarget::Edge(edge_type), data_set);
+    }
+
+    fn add(&self, space_id: i32, part_id: i32, target: ScanTarget, data_set: DataSet) {
+        let mut data = self.data();
+        match data.data.get_mut(&(space_id, part_id, target)) {
+            Some(v) => v.rows.extend(data_set.rows),
+            None => {
+                data.data.insert((space_id, part_id, target), data_set);
+            }
+        }
+    }
+
+    /// Limit the pages to `page_size` rows, below the `limit` of the requests.
+    pub fn set_page_size(&self, page_size: usize) {
+        self.data().page_size = Some(page_size);
+    }
+
+    /// Fail the next scan of a part with `code`, `leader` is reported along with
+    /// `E_LEADER_CHANGED`.
+    pub fn push_part_error(&self, part_id: i32, code: ErrorCode, leader: Option<HostAddr>) {
+        self.data()
+            .part_errors
+            .entry(part_id)
+            .or_default()
+            .push_back(PartitionResult {
+                code,
+                part_id,
+                leader,
+                ..Default::default()
+            });
+    }
+
+    /// The parts scanned so far, in order.
+    pub fn requests(&self) -> Vec<ScanRequest> {
+        self.data().requests.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use fbthrift::BinaryProtocol;
+    use fbthrift_transport::{impl_tokio::TokioSleep, AsyncTransport, AsyncTransportConfiguration};
+    use nebula_fbthrift_meta_v3::TagItem;
+    use nebula_fbthrift_storage_v3::{
+        client::{GraphStorageService, GraphStorageServiceImpl},
+        VertexProp,
+    };
+
+    use crate::common::Value;
+    use crate::testing::{graph::data_set, FakeMetaServer};
+    use crate::{stream, MetaClient, StorageClient, StorageTransportResponseHandler};
+
+    fn players(names: &[&str]) -> DataSet {
+        data_set(
+            &["player._vid", "player.name"],
+            names
+                .iter()
+                .map(|v| {
+                    vec![
+                        Value::sVal(v.as_bytes().to_vec()),
+                        Value::sVal(v.as_bytes().to_vec()),
+                    ]
+                })
+                .collect(),
+        )
+    }
+
+    async fn cluster() -> io::Result<(FakeMetaServer, FakeStorageServer)> {
+        let storage = FakeStorageServer::start().await?;
+        storage.set_page_size(2);
+        storage.add_vertices(1, 1, 2, players(&["a", "b", "c"]));
+        storage.add_vertices(1, 2, 2, players(&["d"]));
+
+        let meta = FakeMetaServer::start().await?;
+        meta.add_space(
+            1,
+            "test",
+            [(1, vec![storage.addr()]), (2, vec![storage.addr()])].into(),
+        );
+        meta.add_tag(
+            1,
+            TagItem {
+                tag_id: 2,
+                tag_name: b"player".to_vec(),
+                ..Default::default()
+            },
+        );
+        Ok((meta, storage))
+    }
+
+    #[tokio::test]
+    async fn test_scan_vertex() -> Result<(), Box<dyn std::error::Error>> {
+        let (meta, storage) = cluster().await?;
+        let mclient = MetaClient::new(&[meta.addr()]).await?;
+        let mut sclient = StorageClient::new(mclient).await;
+
+        let outputs = sclient
+            .scan_vertex("test", "player", Some(vec!["name"]))
+            .await?;
+        assert_eq!(outputs.iter().map(|v| v.get_row_size()).sum::<usize>(), 3);
+        let next_cursors = outputs
+            .iter()
+            .flat_map(|v| v.resp.cursors.iter())
+            .map(|(part_id, cursor)| (*part_id, cursor.next_cursor.clone()))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(next_cursors, [(1, Some(b"2".to_vec())), (2, None)].into());
+        assert!(storage
+            .requests()
+            .iter()
+            .all(|v| v.target == ScanTarget::Vertex(2) && v.cursor.is_none()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pagination() -> Result<(), Box<dyn std::error::Error>> {
+        let (_meta, storage) = cluster().await?;
+        let addr = storage.addr();
+        let stream = stream::connect(&addr.host, addr.port as u16, None).await?;
+        let transport = AsyncTransport::<_, TokioSleep, _>::new(
+            stream,
+            AsyncTransportConfiguration::new(StorageTransportResponseHandler),
+        );
+        let service = GraphStorageServiceImpl::<BinaryProtocol, _>::new(transport);
+
+        let mut req = ScanVertexRequest {
+            space_id: 1,
+            parts: [(1, ScanCursor::default())].into(),
+            return_columns: vec![VertexProp {
+                tag: 2,
+                ..Default::default()
+            }],
+            limit: 1000,
+            ..Default::default()
+        };
+        let mut rows = 0;
+        loop {
+            let resp = service.scanVertex(&req).await?;
+            rows += resp.props.map_or(0, |v| v.rows.len());
+            match resp.cursors[&1].next_cursor.clone() {
+                Some(next_cursor) => {
+                    req.parts.insert(
+                        1,
+                        ScanCursor {
+                            next_cursor: Some(next_cursor),
+                            ..Default::default()
+                        },
+                    );
+                }
+                None => break,
+            }
+        }
+        assert_eq!(rows, 3);
+        assert_eq!(
+            storage
+                .requests()
+                .into_iter()
+                .map(|v| v.cursor)
+                .collect::<Vec<_>>(),
+            [None, Some(b"2".to_vec())]
+        );
+
+        storage.push_part_error(1, ErrorCode::E_LEADER_CHANGED, Some(addr.clone()));
+        let resp = service.scanVertex(&req).await?;
+        let failure = &resp.result.failed_parts[0];
+        assert_eq!(failure.code, ErrorCode::E_LEADER_CHANGED);
+        assert_eq!(failure.leader, Some(addr));
+        assert!(service
+            .scanVertex(&req)
+            .await?
+            .result
+            .failed_parts
+            .is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,435 @@
+//! Transports recording the exchanges of a session and replaying them, for
+//! golden tests that run offline.
+//!
+//! A recording is a file of JSON lines, one per call, with the thrift frames of
+//! the request and the response in hex. The requests of `authenticate` carry the
+//! password of the session.
+//! ## Example
+//! ```ignore
+//! use rust_nebula::testing::{RecordingTransport, ReplayTransport};
+//!
+//! // Once, against a cluster.
+//! let transport = RecordingTransport::connect(&addr, GraphTransportResponseHandler).await?;
+//! let recording = transport.recording();
+//! let mut session = SingleConnSession::new_with_transport(&addr, transport, "root", "nebula").await?;
+//! session.query("MATCH (v:player) RETURN v LIMIT 10;").await?;
+//! recording.save("tests/golden/match_player.jsonl")?;
+//!
+//! // In the tests.
+//! let transport = ReplayTransport::load("tests/golden/match_player.jsonl")?;
+//! let replay = transport.clone();
+//! let mut session = SingleConnSession::new_with_transport(&addr, transport, "root", "nebula").await?;
+//! let output = session.query("MATCH (v:player) RETURN v LIMIT 10;").await?;
+//! replay.finish()?;
+//! ```
+
+use std::ffi::CStr;
+use std::fs;
+use std::io::{self, BufRead as _, Cursor, Write as _};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use bytes::{Bytes, BytesMut};
+use fbthrift::{Framing, FramingDecoded, FramingEncodedFinal, Transport};
+use fbthrift_transport::{impl_tokio::TokioSleep, AsyncTransport, AsyncTransportConfiguration};
+use fbthrift_transport_response_handler::ResponseHandler;
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::{stream, HostAddress, NebulaStream};
+
+/// A call and its response, as thrift frames.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exchange {
+    pub service: String,
+    /// e.g. `GraphService.execute`
+    pub method: String,
+    pub request: Vec<u8>,
+    pub response: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExchangeLine {
+    service: String,
+    method: String,
+    request: String,
+    response: String,
+}
+
+impl Exchange {
+    fn to_line(&self) -> String {
+        let line = ExchangeLine {
+            service: self.service.clone(),
+            method: self.method.clone(),
+            request: to_hex(&self.request),
+            response: to_hex(&self.response),
+        };
+        serde_json::to_string(&line).expect("ExchangeLine is serializable")
+    }
+
+    fn from_line(line: &str) -> io::Result<Self> {
+        let line: ExchangeLine = serde_json::from_str(line)?;
+        let invalid = |_| io::Error::new(io::ErrorKind::InvalidData, "invalid hex");
+        Ok(Self {
+            service: line.service,
+            method: line.method,
+            request: from_hex(&line.request).map_err(invalid)?,
+            response: from_hex(&line.response).map_err(invalid)?,
+        })
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|v| format!("{v:02x}")).collect()
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>, std::num::ParseIntError> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2).unwrap_or("x"), 16))
+        .collect()
+}
+
+//
+//
+//
+/// The exchanges recorded by a `RecordingTransport`, shared with it.
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    exchanges: Arc<Mutex<Vec<Exchange>>>,
+}
+
+impl Recording {
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.exchanges.lock().unwrap().clone()
+    }
+
+    /// Write the exchanges recorded so far to `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        for exchange in self.exchanges.lock().unwrap().iter() {
+            writeln!(file, "{}", exchange.to_line())?;
+        }
+        file.flush()
+    }
+}
+
+/// Records the calls to `T` that got a response.
+pub struct RecordingTransport<T> {
+    inner: T,
+    recording: Recording,
+}
+
+impl<T> RecordingTransport<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            recording: Recording::default(),
+        }
+    }
+
+    pub fn recording(&self) -> Recording {
+        self.recording.clone()
+    }
+}
+
+impl<H> RecordingTransport<AsyncTransport<NebulaStream, TokioSleep, H>>
+where
+    H: ResponseHandler + Unpin + Send + Sync + 'static,
+{
+    /// Connect to `addr` without TLS, `handler` is the response handler of the
+    /// service, e.g. `GraphTransportResponseHandler`.
+    pub async fn connect(addr: &HostAddress, handler: H) -> io::Result<Self> {
+        let stream = stream::connect(addr.host(), addr.port(), None).await?;
+        Ok(Self::new(AsyncTransport::new(
+            stream,
+            AsyncTransportConfiguration::new(handler),
+        )))
+    }
+}
+
+impl<T> Framing for RecordingTransport<T>
+where
+    T: Framing<EncBuf = BytesMut, DecBuf = Cursor<Bytes>>,
+{
+    type EncBuf = BytesMut;
+    type DecBuf = Cursor<Bytes>;
+
+    fn enc_with_capacity(cap: usize) -> Self::EncBuf {
+        T::enc_with_capacity(cap)
+    }
+}
+
+impl<T> Transport for RecordingTransport<T>
+where
+    T: Transport + Framing<EncBuf = BytesMut, DecBuf = Cursor<Bytes>>,
+{
+    type RpcOptions = T::RpcOptions;
+
+    fn call(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<FramingDecoded<Self>>> {
+        let request = req.to_vec();
+        let call = self.inner.call(service_name, fn_name, req, rpc_options);
+        let exchanges = self.recording.exchanges.clone();
+        Box::pin(async move {
+            let response = call.await?;
+            exchanges.lock().unwrap().push(Exchange {
+                service: service_name.to_string_lossy().to_string(),
+                method: fn_name.to_string_lossy().to_string(),
+                request,
+                response: response.get_ref().to_vec(),
+            });
+            Ok(response)
+        })
+    }
+}
+
+//
+//
+//
+#[derive(Debug, Default)]
+struct ReplayState {
+    exchanges: Vec<Exchange>,
+    next: usize,
+    /// The first divergence, every later call fails with it
+    error: Option<ReplayError>,
+}
+
+/// Answers the calls from a recording, in order.
+///
+/// A call that differs from the next recorded one fails, and so does every
+/// later call. `finish` reports the divergence, or the recorded calls that were
+/// never made.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayTransport {
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl ReplayTransport {
+    pub fn new(exchanges: Vec<Exchange>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ReplayState {
+                exchanges,
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Read a recording saved by `Recording::save`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = io::BufReader::new(fs::File::open(path)?);
+        let mut exchanges = vec![];
+        for line in file.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                exchanges.push(Exchange::from_line(&line)?);
+            }
+        }
+        Ok(Self::new(exchanges))
+    }
+
+    /// Check that the calls followed the recording to its end.
+    pub fn finish(&self) -> Result<(), ReplayError> {
+        let state = self.state.lock().unwrap();
+        if let Some(err) = &state.error {
+            return Err(err.clone());
+        }
+        match state.exchanges.len() - state.next {
+            0 => Ok(()),
+            unused => Err(ReplayError::Unused(unused)),
+        }
+    }
+
+    fn replay(&self, method: &str, request: &[u8]) -> Result<Bytes, ReplayError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(err) = &state.error {
+            return Err(err.clone());
+        }
+        let index = state.next;
+        let res = match state.exchanges.get(index) {
+            None => Err(ReplayError::Exhausted(index, method.to_owned())),
+            Some(exchange) if exchange.method != method || exchange.request != request => {
+                let offset = exchange
+                    .request
+                    .iter()
+                    .zip(request)
+                    .position(|(a, b)| a != b)
+                    .unwrap_or_else(|| exchange.request.len().min(request.len()));
+                Err(ReplayError::Mismatch(
+                    index,
+                    exchange.method.clone(),
+                    method.to_owned(),
+                    offset,
+                ))
+            }
+            Some(exchange) => Ok(Bytes::from(exchange.response.clone())),
+        };
+        match &res {
+            Ok(_) => state.next += 1,
+            Err(err) => state.error = Some(err.clone()),
+        }
+        res
+    }
+}
+
+impl Framing for ReplayTransport {
+    type EncBuf = BytesMut;
+    type DecBuf = Cursor<Bytes>;
+
+    fn enc_with_capacity(cap: usize) -> Self::EncBuf {
+        BytesMut::with_capacity(cap)
+    }
+}
+
+impl Transport for ReplayTransport {
+    type RpcOptions = ();
+
+    fn call(
+        &self,
+        _service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        _rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<FramingDecoded<Self>>> {
+        let res = self
+            .replay(&fn_name.to_string_lossy(), &req)
+            .map(Cursor::new)
+            .map_err(anyhow::Error::from);
+        Box::pin(async move { res })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The call at the index, the recorded method, the called method and the
+    /// offset of the first differing byte of the request
+    Mismatch(usize, String, String, usize),
+    /// The call at the index is past the end of the recording
+    Exhausted(usize, String),
+    /// Recorded calls that were never made
+    Unused(usize),
+}
+
+impl core::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Mismatch(index, expected, actual, offset) => write!(
+                f,
+                "Mismatch index:{index} expected:{expected} actual:{actual} offset:{offset}"
+            ),
+            Self::Exhausted(index, method) => {
+                write!(f, "Exhausted index:{index} method:{method}")
+            }
+            Self::Unused(count) => write!(f, "Unused {count}"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::common::Value;
+    use crate::graph::GraphQuery as _;
+    use crate::testing::{graph, FakeGraphServer, Reply};
+    use crate::{GraphTransportResponseHandler, SingleConnSession};
+
+    const STMT: &str = "YIELD 1 AS n;";
+
+    #[derive(serde::Deserialize)]
+    struct N {
+        n: i64,
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(to_hex(&[0, 0x7f, 0xff]), "007fff");
+        assert_eq!(from_hex("007fff"), Ok(vec![0, 0x7f, 0xff]));
+        assert!(from_hex("0").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_record_replay() -> Result<(), Box<dyn std::error::Error>> {
+        let server = FakeGraphServer::start().await?;
+        server.on_statement(
+            STMT,
+            Reply::new(graph::response(Some(graph::data_set(
+                &["n"],
+                vec![vec![Value::iVal(1)]],
+            )))),
+        );
+        let transport =
+            RecordingTransport::connect(&server.addr(), GraphTransportResponseHandler).await?;
+        let recording = transport.recording();
+        let mut session =
+            SingleConnSession::new_with_transport(&server.addr(), transport, "root", "nebula")
+                .await?;
+        session.query(STMT).await?;
+        drop(server);
+
+        let path = std::env::temp_dir().join(format!("rust-nebula-{}.jsonl", std::process::id()));
+        recording.save(&path)?;
+        let transport = ReplayTransport::load(&path)?;
+        fs::remove_file(&path)?;
+        assert_eq!(
+            recording
+                .exchanges()
+                .iter()
+                .map(|v| v.method.as_str())
+                .collect::<Vec<_>>(),
+            ["GraphService.authenticate", "GraphService.execute"]
+        );
+
+        let replay = transport.clone();
+        let mut session = SingleConnSession::new_with_transport(
+            &HostAddress::new("127.0.0.1", 9669),
+            transport,
+            "root",
+            "nebula",
+        )
+        .await?;
+        assert!(replay.finish().is_err());
+        let output = session.query(STMT).await?;
+        assert_eq!(output.scan::<N>()?[0].n, 1);
+        replay.finish()?;
+
+        assert!(session.query(STMT).await.is_err());
+        assert_eq!(
+            replay.finish(),
+            Err(ReplayError::Exhausted(2, "GraphService.execute".to_owned()))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mismatch() -> Result<(), Box<dyn std::error::Error>> {
+        let server = FakeGraphServer::start().await?;
+        let transport =
+            RecordingTransport::connect(&server.addr(), GraphTransportResponseHandler).await?;
+        let recording = transport.recording();
+        let mut session =
+            SingleConnSession::new_with_transport(&server.addr(), transport, "root", "nebula")
+                .await?;
+        session.query(STMT).await?;
+
+        let transport = ReplayTransport::new(recording.exchanges());
+        let replay = transport.clone();
+        let mut session =
+            SingleConnSession::new_with_transport(&server.addr(), transport, "root", "nebula")
+                .await?;
+        assert!(session.query("YIELD 2 AS n;").await.is_err());
+        assert!(session.query(STMT).await.is_err());
+        assert!(matches!(
+            replay.finish(),
+            Err(ReplayError::Mismatch(1, _, _, _))
+        ));
+        Ok(())
+    }
+}
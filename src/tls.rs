@@ -0,0 +1,283 @@
+/// TLS settings of the connections to graphd, metad or storaged.
+/// ## Notice
+/// Without the `tls` feature this type has no values, so TLS can't be enabled.
+#[cfg(not(feature = "tls"))]
+#[derive(Debug, Clone)]
+pub enum TlsConfig {}
+
+#[cfg(feature = "tls")]
+pub use self::enabled::TlsConfig;
+
+#[cfg(feature = "tls")]
+mod enabled {
+    use std::{
+        fs::File,
+        io::{BufReader, Error as IoError, ErrorKind as IoErrorKind},
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    };
+
+    use tokio::net::TcpStream;
+    use tokio_rustls::{
+        client::TlsStream,
+        rustls::{
+            client::{
+                danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+                WebPkiServerVerifier,
+            },
+            crypto::ring,
+            pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+            CertificateError, ClientConfig, DigitallySignedStruct, Error as TlsError,
+            RootCertStore, SignatureScheme,
+        },
+        TlsConnector,
+    };
+
+    /// TLS settings of the connections to graphd, metad or storaged.
+    #[derive(Debug, Clone, Default)]
+    pub struct TlsConfig {
+        /// PEM file with the CA certificates used to verify the server.
+        /// The Mozilla root certificates are used if it's not set.
+        pub ca_cert_path: Option<PathBuf>,
+        /// PEM file with the client certificate chain, for mutual TLS
+        pub client_cert_path: Option<PathBuf>,
+        /// PEM file with the private key of the client certificate
+        pub client_key_path: Option<PathBuf>,
+        /// The name sent with SNI and verified against the server certificate.
+        /// The host of the address being connected is used if it's not set.
+        pub server_name: Option<String>,
+        /// Accept server certificates that don't match the server name.
+        /// The certificate chain is still verified.
+        /// ## Notice
+        /// Only meant for test clusters with self-signed certificates.
+        pub disable_hostname_verification: bool,
+        /// The client config of the last connection and the settings it was built
+        /// from, shared by the clones
+        client_config: Arc<Mutex<Option<CachedClientConfig>>>,
+    }
+
+    /// The paths and `disable_hostname_verification` a client config was built from.
+    type ClientConfigKey = (Option<PathBuf>, Option<PathBuf>, Option<PathBuf>, bool);
+    type CachedClientConfig = (ClientConfigKey, Arc<ClientConfig>);
+
+    impl TlsConfig {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn set_ca_cert_path(&mut self, path: impl Into<PathBuf>) {
+            self.ca_cert_path = Some(path.into());
+        }
+        pub fn set_client_cert(
+            &mut self,
+            cert_path: impl Into<PathBuf>,
+            key_path: impl Into<PathBuf>,
+        ) {
+            self.client_cert_path = Some(cert_path.into());
+            self.client_key_path = Some(key_path.into());
+        }
+        pub fn set_server_name(&mut self, server_name: &str) {
+            self.server_name = Some(server_name.to_owned());
+        }
+        pub fn set_disable_hostname_verification(&mut self, disabled: bool) {
+            self.disable_hostname_verification = disabled;
+        }
+
+        pub(crate) async fn connect(
+            &self,
+            host: &str,
+            stream: TcpStream,
+        ) -> Result<TlsStream<TcpStream>, IoError> {
+            let server_name = self.server_name.as_deref().unwrap_or(host);
+            let server_name = ServerName::try_from(server_name.to_owned())
+                .map_err(|err| IoError::new(IoErrorKind::InvalidInput, err))?;
+            let connector = TlsConnector::from(self.cached_client_config()?);
+            connector.connect(server_name, stream).await
+        }
+
+        /// Build the client config once, the PEM files are read again only if
+        /// their paths change.
+        fn cached_client_config(&self) -> Result<Arc<ClientConfig>, IoError> {
+            let key = (
+                self.ca_cert_path.clone(),
+                self.client_cert_path.clone(),
+                self.client_key_path.clone(),
+                self.disable_hostname_verification,
+            );
+            let mut cached = self.client_config.lock().unwrap();
+            if let Some((cached_key, config)) = cached.as_ref() {
+                if *cached_key == key {
+                    return Ok(config.clone());
+                }
+            }
+            let config = Arc::new(self.client_config()?);
+            *cached = Some((key, config.clone()));
+            Ok(config)
+        }
+
+        fn client_config(&self) -> Result<ClientConfig, IoError> {
+            let provider = Arc::new(ring::default_provider());
+
+            let mut roots = RootCertStore::empty();
+            match &self.ca_cert_path {
+                Some(path) => {
+                    for cert in load_certs(path)? {
+                        roots.add(cert).map_err(tls_error)?;
+                    }
+                }
+                None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+            }
+            let verifier =
+                WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                    .build()
+                    .map_err(|err| IoError::new(IoErrorKind::InvalidInput, err))?;
+
+            let builder = ClientConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()
+                .map_err(tls_error)?;
+            let builder = if self.disable_hostname_verification {
+                builder
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(NoHostnameVerifier(verifier)))
+            } else {
+                builder.with_webpki_verifier(verifier)
+            };
+
+            match (&self.client_cert_path, &self.client_key_path) {
+                (Some(cert_path), Some(key_path)) => builder
+                    .with_client_auth_cert(load_certs(cert_path)?, load_key(key_path)?)
+                    .map_err(tls_error),
+                (None, None) => Ok(builder.with_no_client_auth()),
+                _ => Err(IoError::new(
+                    IoErrorKind::InvalidInput,
+                    "Both client_cert_path and client_key_path must be set for client authentication",
+                )),
+            }
+        }
+    }
+
+    fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, IoError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+        if certs.is_empty() {
+            return Err(IoError::new(
+                IoErrorKind::InvalidData,
+                format!("No certificate found in {}", path.display()),
+            ));
+        }
+        Ok(certs)
+    }
+
+    fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, IoError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        rustls_pemfile::private_key(&mut reader)?.ok_or_else(|| {
+            IoError::new(
+                IoErrorKind::InvalidData,
+                format!("No private key found in {}", path.display()),
+            )
+        })
+    }
+
+    fn tls_error(err: TlsError) -> IoError {
+        IoError::new(IoErrorKind::InvalidInput, err)
+    }
+
+    /// Verifies the certificate chain like the default verifier, but ignores
+    /// a mismatching server name.
+    #[derive(Debug)]
+    struct NoHostnameVerifier(Arc<WebPkiServerVerifier>);
+
+    impl ServerCertVerifier for NoHostnameVerifier {
+        fn verify_server_cert(
+            &self,
+            end_entity: &CertificateDer<'_>,
+            intermediates: &[CertificateDer<'_>],
+            server_name: &ServerName<'_>,
+            ocsp_response: &[u8],
+            now: UnixTime,
+        ) -> Result<ServerCertVerified, TlsError> {
+            match self.0.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            ) {
+                Err(TlsError::InvalidCertificate(
+                    CertificateError::NotValidForName
+                    | CertificateError::NotValidForNameContext { .. },
+                )) => Ok(ServerCertVerified::assertion()),
+                res => res,
+            }
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, TlsError> {
+            self.0.verify_tls12_signature(message, cert, dss)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, TlsError> {
+            self.0.verify_tls13_signature(message, cert, dss)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.0.supported_verify_schemes()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_client_config() {
+            let tls = TlsConfig::new();
+            assert!(tls.client_config().is_ok());
+
+            let mut tls = TlsConfig::new();
+            tls.set_disable_hostname_verification(true);
+            assert!(tls.client_config().is_ok());
+
+            let mut tls = TlsConfig::new();
+            tls.set_ca_cert_path("/nonexistent/ca.pem");
+            let err = tls.client_config().unwrap_err();
+            assert_eq!(err.kind(), IoErrorKind::NotFound);
+
+            let mut tls = TlsConfig::new();
+            tls.client_cert_path = Some("/nonexistent/client.pem".into());
+            let err = tls.client_config().unwrap_err();
+            assert_eq!(err.kind(), IoErrorKind::InvalidInput);
+        }
+
+        #[test]
+        fn test_cached_client_config() {
+            let tls = TlsConfig::new();
+            let config = tls.cached_client_config().unwrap();
+            assert!(Arc::ptr_eq(&config, &tls.cached_client_config().unwrap()));
+            assert!(Arc::ptr_eq(
+                &config,
+                &tls.clone().cached_client_config().unwrap()
+            ));
+
+            let mut changed = tls.clone();
+            changed.set_disable_hostname_verification(true);
+            assert!(!Arc::ptr_eq(
+                &config,
+                &changed.cached_client_config().unwrap()
+            ));
+
+            changed.set_ca_cert_path("/nonexistent/ca.pem");
+            assert!(changed.cached_client_config().is_err());
+            assert!(changed.cached_client_config().is_err());
+        }
+    }
+}